@@ -9,6 +9,7 @@ fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
         .insert_resource(MeshHandleRes(None))
+        .init_resource::<PTessellator>()
         .add_systems(Startup, setup)
         .add_systems(Update, update)
         .run();
@@ -33,6 +34,8 @@ fn setup(
 
 fn update(
     mesh_handle_res: ResMut<MeshHandleRes>,
+    mut tessellator: ResMut<PTessellator>,
+    mut mesh: Local<PMesh<u32>>,
     mut assets: ResMut<Assets<Mesh>>,
     windows: Query<&Window>,
     camera_q: Query<(&Camera, &GlobalTransform)>,
@@ -42,8 +45,8 @@ fn update(
     let points = 5;
     let angle = std::f32::consts::PI / points as f32;
 
-    let mut mesh = PMesh::<u32>::new();
-    mesh.fill(0.1, |builder| {
+    mesh.clear();
+    tessellator.fill_into(&mut *mesh, &PFillOptions::new(0.1), |builder| {
         builder.push().begin(Vec2::new(inner_radius, 0.0));
         for _ in 0..points {
             builder
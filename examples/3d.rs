@@ -21,6 +21,7 @@ fn main() {
             ..default()
         })
         .insert_resource(MeshHandleRes(None))
+        .init_resource::<PTessellator>()
         .add_systems(Startup, setup)
         .add_systems(Update, update)
         .run();
@@ -62,6 +63,8 @@ fn setup(
 
 fn update(
     mesh_handle_res: ResMut<MeshHandleRes>,
+    mut tessellator: ResMut<PTessellator>,
+    mut mesh: Local<PMesh<u32>>,
     mut assets: ResMut<Assets<Mesh>>,
     windows: Query<&Window>,
     camera_q: Query<(&Camera, &GlobalTransform)>,
@@ -71,8 +74,8 @@ fn update(
     let points = 5;
     let angle = std::f32::consts::PI / points as f32;
 
-    let mut mesh = PMesh::<u32>::new();
-    mesh.fill(0.1, |builder| {
+    mesh.clear();
+    tessellator.fill_extruded_into(&mut *mesh, 0.1, 0.5, |builder| {
         builder.push().begin(Vec2::new(inner_radius, 0.0));
         for _ in 0..points {
             builder
@@ -101,10 +104,8 @@ fn update(
         }
     });
 
-    // TODO: Extrude the whole shape! However, sorting the vertices is not sufficient for complex shapes.
-    // fill.build::<u16>(false).get_vertices_mut().sort_clockwise().extrude(Vec3::Z);
-
-    mesh.flip_yz().bevy_set(
+    // The shape is drawn in the xy-plane and extruded along z. Flipping y and z is a reflection, so flip the winding, too.
+    mesh.flip_yz().flip_winding().bevy_set(
         &mut assets
             .get_mut(mesh_handle_res.0.clone().unwrap().id())
             .unwrap(),
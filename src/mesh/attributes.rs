@@ -0,0 +1,585 @@
+//! Additional vertex attributes of a mesh.
+
+use super::{IndexType, MissingAttributes, OrPanic, PMesh, PMeshError, PVertices};
+use bevy::{
+    mesh::{MeshVertexAttribute, MeshVertexAttributeId, VertexAttributeValues},
+    prelude::*,
+    render::render_resource::VertexFormat,
+};
+use std::collections::BTreeMap;
+
+/// The components of a single attribute value.
+trait Components {
+    /// Returns the maximum absolute difference of the components.
+    fn max_difference(&self, other: &Self) -> f64;
+}
+
+impl<C: Copy + Into<f64>, const N: usize> Components for [C; N] {
+    fn max_difference(&self, other: &Self) -> f64 {
+        self.iter()
+            .zip(other.iter())
+            .map(|(a, b)| ((*a).into() - (*b).into()).abs())
+            .fold(0.0, f64::max)
+    }
+}
+
+macro_rules! impl_scalar_components {
+    ($($t:ty),*) => {
+        $(impl Components for $t {
+            fn max_difference(&self, other: &Self) -> f64 {
+                (f64::from(*self) - f64::from(*other)).abs()
+            }
+        })*
+    };
+}
+
+impl_scalar_components!(f32, i32, u32);
+
+/// Implements the operations on `VertexAttributeValues` for all of its variants.
+macro_rules! impl_value_ops {
+    ($($variant:ident),*) => {
+        /// Returns the values at the given indices.
+        pub(crate) fn gather_values(
+            values: &VertexAttributeValues,
+            indices: impl Iterator<Item = usize>,
+        ) -> VertexAttributeValues {
+            match values {
+                $(VertexAttributeValues::$variant(v) => {
+                    VertexAttributeValues::$variant(indices.map(|i| v[i]).collect())
+                })*
+            }
+        }
+
+        /// Appends the values of `other` to `values`. Returns false if the formats don't match.
+        pub(crate) fn extend_values(
+            values: &mut VertexAttributeValues,
+            other: &VertexAttributeValues,
+        ) -> bool {
+            match (values, other) {
+                $((VertexAttributeValues::$variant(a), VertexAttributeValues::$variant(b)) => {
+                    a.extend_from_slice(b);
+                    true
+                })*
+                _ => false,
+            }
+        }
+
+        /// Returns whether all components of the values at `i` and `j` differ by at most `tolerance`.
+        pub(crate) fn values_close(
+            values: &VertexAttributeValues,
+            i: usize,
+            j: usize,
+            tolerance: f32,
+        ) -> bool {
+            match values {
+                $(VertexAttributeValues::$variant(v) => v[i].max_difference(&v[j]) <= tolerance as f64,)*
+            }
+        }
+
+        /// Truncates the values or pads them with default values (zeros) to the given length.
+        pub(crate) fn resize_values(values: &mut VertexAttributeValues, len: usize) {
+            match values {
+                $(VertexAttributeValues::$variant(v) => v.resize(len, Default::default()),)*
+            }
+        }
+
+        /// Returns `len` default values (zeros) with the same format.
+        pub(crate) fn default_values(values: &VertexAttributeValues, len: usize) -> VertexAttributeValues {
+            match values {
+                $(VertexAttributeValues::$variant(_) => {
+                    VertexAttributeValues::$variant(vec![Default::default(); len])
+                })*
+            }
+        }
+    };
+}
+
+impl_value_ops!(
+    Float32, Sint32, Uint32, Float32x2, Sint32x2, Uint32x2, Float32x3, Sint32x3, Uint32x3,
+    Float32x4, Sint32x4, Uint32x4, Sint16x2, Snorm16x2, Uint16x2, Unorm16x2, Sint16x4, Snorm16x4,
+    Uint16x4, Unorm16x4, Sint8x2, Snorm8x2, Uint8x2, Unorm8x2, Sint8x4, Snorm8x4, Uint8x4,
+    Unorm8x4
+);
+
+/// A set of additional vertex attributes like colors, tangents, or joint weights, keyed by their bevy attribute.
+///
+/// Positions, normals, and the first uv channel are stored directly in the `PMesh`.
+#[derive(Clone, Debug, Default)]
+pub struct PAttributes {
+    attributes: BTreeMap<MeshVertexAttributeId, (MeshVertexAttribute, VertexAttributeValues)>,
+}
+
+impl PAttributes {
+    /// Creates an empty set of attributes.
+    pub fn new() -> Self {
+        PAttributes {
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the number of attributes.
+    pub fn len(&self) -> usize {
+        self.attributes.len()
+    }
+
+    /// Returns whether there are no attributes.
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty()
+    }
+
+    /// Inserts or replaces an attribute. Panics if the values don't match the format of the attribute.
+    pub fn insert(&mut self, attribute: MeshVertexAttribute, values: VertexAttributeValues) {
+        self.try_insert(attribute, values).or_panic();
+    }
+
+    /// Inserts or replaces an attribute. Returns `PMeshError::AttributeFormat` if the values don't match
+    /// the format of the attribute.
+    pub fn try_insert(
+        &mut self,
+        attribute: MeshVertexAttribute,
+        values: VertexAttributeValues,
+    ) -> Result<(), PMeshError> {
+        if VertexFormat::from(&values) != attribute.format {
+            return Err(PMeshError::AttributeFormat(attribute.name));
+        }
+        self.attributes.insert(attribute.id, (attribute, values));
+        Ok(())
+    }
+
+    /// Returns the values of an attribute.
+    pub fn get(&self, id: impl Into<MeshVertexAttributeId>) -> Option<&VertexAttributeValues> {
+        self.attributes.get(&id.into()).map(|(_, v)| v)
+    }
+
+    /// Returns the values of an attribute to be modified in-place.
+    pub fn get_mut(
+        &mut self,
+        id: impl Into<MeshVertexAttributeId>,
+    ) -> Option<&mut VertexAttributeValues> {
+        self.attributes.get_mut(&id.into()).map(|(_, v)| v)
+    }
+
+    /// Removes an attribute and returns its values.
+    pub fn remove(
+        &mut self,
+        id: impl Into<MeshVertexAttributeId>,
+    ) -> Option<VertexAttributeValues> {
+        self.attributes.remove(&id.into()).map(|(_, v)| v)
+    }
+
+    /// Returns whether the attribute exists.
+    pub fn contains(&self, id: impl Into<MeshVertexAttributeId>) -> bool {
+        self.attributes.contains_key(&id.into())
+    }
+
+    /// Iterates over the attributes and their values.
+    pub fn iter(&self) -> impl Iterator<Item = (&MeshVertexAttribute, &VertexAttributeValues)> {
+        self.attributes.values().map(|(a, v)| (a, v))
+    }
+
+    /// Returns a new set of attributes with the values at the given vertex indices.
+    pub fn gather(&self, indices: &[usize]) -> PAttributes {
+        PAttributes {
+            attributes: self
+                .attributes
+                .iter()
+                .map(|(id, (a, v))| (*id, (*a, gather_values(v, indices.iter().copied()))))
+                .collect(),
+        }
+    }
+
+    /// Truncates all attributes or pads them with default values to the given number of vertices.
+    pub(crate) fn resize(&mut self, len: usize) {
+        for (_, v) in self.attributes.values_mut() {
+            resize_values(v, len);
+        }
+    }
+
+    /// Returns the name of the first attribute that only one of the sets has or that has different formats.
+    /// `len` and `other_len` are the numbers of vertices; empty sets are treated as having every attribute.
+    pub(crate) fn find_missing(
+        &self,
+        len: usize,
+        other: &PAttributes,
+        other_len: usize,
+    ) -> Option<&'static str> {
+        let mismatch =
+            self.attributes
+                .iter()
+                .find_map(|(id, (a, v))| match other.attributes.get(id) {
+                    Some((_, v2)) => {
+                        (VertexFormat::from(v) != VertexFormat::from(v2)).then_some(a.name)
+                    }
+                    None => (other_len > 0).then_some(a.name),
+                });
+        mismatch.or_else(|| {
+            other
+                .attributes
+                .iter()
+                .find(|(id, _)| len > 0 && !self.attributes.contains_key(id))
+                .map(|(_, (a, _))| a.name)
+        })
+    }
+
+    /// Returns whether all attributes of the vertices `i` and `j` differ by at most the given tolerance.
+    pub(crate) fn close(
+        &self,
+        i: usize,
+        j: usize,
+        tolerance: impl Fn(MeshVertexAttributeId) -> f32,
+    ) -> bool {
+        self.attributes
+            .iter()
+            .all(|(id, (_, v))| values_close(v, i, j, tolerance(*id)))
+    }
+
+    /// Appends the values of another set of attributes. `len` and `other_len` are the numbers of vertices.
+    ///
+    /// Attributes that only one of the non-empty sets has are removed with `MissingAttributes::Drop` and
+    /// filled with default values otherwise. Attributes with different formats are removed.
+    pub(crate) fn extend(
+        &mut self,
+        len: usize,
+        other: &PAttributes,
+        other_len: usize,
+        missing: MissingAttributes,
+    ) {
+        let fill = missing != MissingAttributes::Drop;
+        let mut added = Vec::new();
+        for (id, (a, v2)) in &other.attributes {
+            if self.attributes.contains_key(id) || (len > 0 && !fill) {
+                continue;
+            }
+            let mut v = default_values(v2, len);
+            extend_values(&mut v, v2);
+            added.push((*id, (*a, v)));
+        }
+        self.attributes
+            .retain(|id, (_, v)| match other.attributes.get(id) {
+                Some((_, v2)) => extend_values(v, v2),
+                None if other_len == 0 => true,
+                None => fill && extend_values(v, &default_values(v, other_len)),
+            });
+        self.attributes.extend(added);
+    }
+}
+
+impl<T> PMesh<T>
+where
+    T: IndexType,
+{
+    /// Returns the additional vertex attributes of the mesh.
+    pub fn get_attributes(&self) -> &PAttributes {
+        &self.attributes
+    }
+
+    /// Returns the additional vertex attributes of the mesh to be modified in-place.
+    pub fn get_attributes_mut(&mut self) -> &mut PAttributes {
+        &mut self.attributes
+    }
+
+    /// Replaces the positions, uv coordinates, normals, and attributes with the ones of the given vertices.
+    /// Vertices can be repeated or dropped. The indices are not changed.
+    pub(crate) fn gather_vertices(&mut self, kept: &[usize]) {
+        self.vertices = PVertices::build(kept.iter().map(|i| self.vertices[*i]).collect());
+        self.uv = self
+            .uv
+            .as_ref()
+            .map(|uv| kept.iter().map(|i| uv[*i]).collect());
+        self.normals = self
+            .normals
+            .as_ref()
+            .map(|normals| kept.iter().map(|i| normals[*i]).collect());
+        self.attributes = self.attributes.gather(kept);
+    }
+
+    /// Sets an additional vertex attribute, e.g., `Mesh::ATTRIBUTE_COLOR` or a custom attribute.
+    ///
+    /// Positions, normals, and the first uv channel are not stored as attributes; use `get_vertices_mut`,
+    /// `get_normals_mut`, and `get_uv_mut` instead.
+    ///
+    /// Panics if the attribute can't be set. See `try_set_attribute`.
+    pub fn set_attribute(
+        &mut self,
+        attribute: MeshVertexAttribute,
+        values: impl Into<VertexAttributeValues>,
+    ) -> &mut PMesh<T> {
+        self.try_set_attribute(attribute, values).or_panic();
+        self
+    }
+
+    /// Sets an additional vertex attribute. Returns `PMeshError::ReservedAttribute` for positions, normals,
+    /// and the first uv channel, `PMeshError::AttributeLength` if there isn't one value per vertex,
+    /// and `PMeshError::AttributeFormat` if the values don't match the format of the attribute.
+    pub fn try_set_attribute(
+        &mut self,
+        attribute: MeshVertexAttribute,
+        values: impl Into<VertexAttributeValues>,
+    ) -> Result<&mut PMesh<T>, PMeshError> {
+        if attribute.id == Mesh::ATTRIBUTE_POSITION.id
+            || attribute.id == Mesh::ATTRIBUTE_NORMAL.id
+            || attribute.id == Mesh::ATTRIBUTE_UV_0.id
+        {
+            return Err(PMeshError::ReservedAttribute(attribute.name));
+        }
+        let values = values.into();
+        if values.len() != self.vertices.len() {
+            return Err(PMeshError::AttributeLength {
+                name: attribute.name,
+                len: values.len(),
+                expected: self.vertices.len(),
+            });
+        }
+        self.attributes.try_insert(attribute, values)?;
+        Ok(self)
+    }
+
+    /// Returns the values of an additional vertex attribute.
+    pub fn get_attribute(
+        &self,
+        id: impl Into<MeshVertexAttributeId>,
+    ) -> Option<&VertexAttributeValues> {
+        self.attributes.get(id)
+    }
+
+    /// Returns the values of an additional vertex attribute to be modified in-place.
+    pub fn get_attribute_mut(
+        &mut self,
+        id: impl Into<MeshVertexAttributeId>,
+    ) -> Option<&mut VertexAttributeValues> {
+        self.attributes.get_mut(id)
+    }
+
+    /// Removes an additional vertex attribute and returns its values.
+    pub fn remove_attribute(
+        &mut self,
+        id: impl Into<MeshVertexAttributeId>,
+    ) -> Option<VertexAttributeValues> {
+        self.attributes.remove(id)
+    }
+
+    /// Sets the linear RGBA vertex colors of the mesh.
+    ///
+    /// Panics if there isn't one color per vertex. See `try_set_colors`.
+    pub fn set_colors(&mut self, colors: Vec<[f32; 4]>) -> &mut PMesh<T> {
+        self.try_set_colors(colors).or_panic();
+        self
+    }
+
+    /// Sets the linear RGBA vertex colors of the mesh. Returns `PMeshError::AttributeLength`
+    /// if there isn't one color per vertex.
+    pub fn try_set_colors(&mut self, colors: Vec<[f32; 4]>) -> Result<&mut PMesh<T>, PMeshError> {
+        self.try_set_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+    }
+
+    /// Returns the linear RGBA vertex colors of the mesh.
+    pub fn get_colors(&self) -> Option<&Vec<[f32; 4]>> {
+        match self.attributes.get(Mesh::ATTRIBUTE_COLOR) {
+            Some(VertexAttributeValues::Float32x4(colors)) => Some(colors),
+            _ => None,
+        }
+    }
+
+    /// Sets the tangents of the mesh. The w component is the handedness of the bitangent.
+    ///
+    /// Panics if there isn't one tangent per vertex. See `try_set_tangents`.
+    pub fn set_tangents(&mut self, tangents: Vec<[f32; 4]>) -> &mut PMesh<T> {
+        self.try_set_tangents(tangents).or_panic();
+        self
+    }
+
+    /// Sets the tangents of the mesh. Returns `PMeshError::AttributeLength`
+    /// if there isn't one tangent per vertex.
+    pub fn try_set_tangents(
+        &mut self,
+        tangents: Vec<[f32; 4]>,
+    ) -> Result<&mut PMesh<T>, PMeshError> {
+        self.try_set_attribute(Mesh::ATTRIBUTE_TANGENT, tangents)
+    }
+
+    /// Returns the tangents of the mesh.
+    pub fn get_tangents(&self) -> Option<&Vec<[f32; 4]>> {
+        match self.attributes.get(Mesh::ATTRIBUTE_TANGENT) {
+            Some(VertexAttributeValues::Float32x4(tangents)) => Some(tangents),
+            _ => None,
+        }
+    }
+
+    /// Sets the second UV channel of the mesh, e.g., for lightmaps.
+    ///
+    /// Panics if there isn't one uv coordinate per vertex. See `try_set_uv1`.
+    pub fn set_uv1(&mut self, uv: Vec<[f32; 2]>) -> &mut PMesh<T> {
+        self.try_set_uv1(uv).or_panic();
+        self
+    }
+
+    /// Sets the second UV channel of the mesh. Returns
+    /// `PMeshError::AttributeLength` if there isn't one uv coordinate per vertex.
+    pub fn try_set_uv1(&mut self, uv: Vec<[f32; 2]>) -> Result<&mut PMesh<T>, PMeshError> {
+        self.try_set_attribute(Mesh::ATTRIBUTE_UV_1, uv)
+    }
+
+    /// Returns the second UV channel of the mesh.
+    pub fn get_uv1(&self) -> Option<&Vec<[f32; 2]>> {
+        match self.attributes.get(Mesh::ATTRIBUTE_UV_1) {
+            Some(VertexAttributeValues::Float32x2(uv)) => Some(uv),
+            _ => None,
+        }
+    }
+
+    /// Sets the joint indices and weights of the mesh for skinning.
+    ///
+    /// Panics if there aren't as many indices and weights as vertices. See `try_set_joints`.
+    pub fn set_joints(&mut self, indices: Vec<[u16; 4]>, weights: Vec<[f32; 4]>) -> &mut PMesh<T> {
+        self.try_set_joints(indices, weights).or_panic();
+        self
+    }
+
+    /// Sets the joint indices and weights of the mesh for skinning. Returns `PMeshError::AttributeLength`
+    /// and keeps the old joints if there aren't as many indices and weights as vertices.
+    pub fn try_set_joints(
+        &mut self,
+        indices: Vec<[u16; 4]>,
+        weights: Vec<[f32; 4]>,
+    ) -> Result<&mut PMesh<T>, PMeshError> {
+        if weights.len() != self.vertices.len() {
+            return Err(PMeshError::AttributeLength {
+                name: Mesh::ATTRIBUTE_JOINT_WEIGHT.name,
+                len: weights.len(),
+                expected: self.vertices.len(),
+            });
+        }
+        self.try_set_attribute(Mesh::ATTRIBUTE_JOINT_INDEX, indices)?
+            .try_set_attribute(Mesh::ATTRIBUTE_JOINT_WEIGHT, weights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::WeldSettings;
+    use bevy::{asset::RenderAssetUsages, render::render_resource::PrimitiveTopology};
+
+    const ATTRIBUTE_X: MeshVertexAttribute =
+        MeshVertexAttribute::new("Vertex_X", 988_540_917, VertexFormat::Float32);
+
+    /// A cuboid with a custom attribute that stores the x coordinate of each vertex.
+    fn cuboid() -> PMesh<u32> {
+        let mut mesh = PMesh::<u32>::cuboid(1.0, 2.0, 3.0, 1);
+        let x: Vec<f32> = mesh.vertices.get_vertices().iter().map(|v| v[0]).collect();
+        mesh.set_attribute(ATTRIBUTE_X, x);
+        mesh
+    }
+
+    /// Asserts that the custom attribute still matches the x coordinates of the vertices.
+    fn assert_aligned(mesh: &PMesh<u32>) {
+        let Some(VertexAttributeValues::Float32(x)) = mesh.get_attribute(ATTRIBUTE_X) else {
+            panic!("the custom attribute is missing");
+        };
+        let vertices = mesh.vertices.get_vertices();
+        assert_eq!(x.len(), vertices.len());
+        assert!(x.iter().zip(vertices).all(|(x, v)| *x == v[0]));
+    }
+
+    #[test]
+    fn custom_attributes_survive_duplicate() {
+        let mut mesh = cuboid();
+        mesh.duplicate();
+        assert_eq!(mesh.vertices.len(), 36);
+        assert_aligned(&mesh);
+    }
+
+    #[test]
+    fn custom_attributes_survive_extend() {
+        let mut mesh = cuboid();
+        let mut other = cuboid();
+        other.translate(5.0, 0.0, 0.0);
+        let x: Vec<f32> = other.vertices.get_vertices().iter().map(|v| v[0]).collect();
+        other.set_attribute(ATTRIBUTE_X, x);
+        mesh.extend(&other);
+        assert_eq!(mesh.vertices.len(), 48);
+        assert_aligned(&mesh);
+    }
+
+    #[test]
+    fn custom_attributes_survive_weld() {
+        let mut mesh = cuboid();
+        mesh.weld(&WeldSettings::positions_only(0.0001));
+        assert_eq!(mesh.vertices.len(), 8);
+        assert_aligned(&mesh);
+
+        let mut mesh = cuboid();
+        mesh.duplicate().weld(&WeldSettings::default());
+        assert_eq!(mesh.vertices.len(), 24);
+        assert_aligned(&mesh);
+    }
+
+    #[test]
+    fn custom_attributes_survive_bevy_set() {
+        let mesh = cuboid();
+        let mut bevy_mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        );
+        mesh.bevy_set(&mut bevy_mesh);
+        let Some(VertexAttributeValues::Float32(x)) = bevy_mesh.attribute(ATTRIBUTE_X) else {
+            panic!("the custom attribute is missing");
+        };
+        assert_eq!(
+            Some(&VertexAttributeValues::Float32(x.clone())),
+            mesh.get_attribute(ATTRIBUTE_X)
+        );
+    }
+
+    #[test]
+    fn setters_return_errors() {
+        let length = |name: &'static str| {
+            Some(PMeshError::AttributeLength {
+                name,
+                len: 3,
+                expected: 24,
+            })
+        };
+        let mut mesh = cuboid();
+        assert_eq!(
+            mesh.try_set_colors(vec![[1.0; 4]; 3]).err(),
+            length(Mesh::ATTRIBUTE_COLOR.name)
+        );
+        assert_eq!(
+            mesh.try_set_tangents(vec![[1.0; 4]; 3]).err(),
+            length(Mesh::ATTRIBUTE_TANGENT.name)
+        );
+        assert_eq!(
+            mesh.try_set_uv1(vec![[1.0; 2]; 3]).err(),
+            length(Mesh::ATTRIBUTE_UV_1.name)
+        );
+        assert_eq!(
+            mesh.try_set_attribute(ATTRIBUTE_X, vec![[1.0f32; 2]; 24])
+                .err(),
+            Some(PMeshError::AttributeFormat(ATTRIBUTE_X.name))
+        );
+        assert_eq!(
+            mesh.try_set_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0f32; 3]; 24])
+                .err(),
+            Some(PMeshError::ReservedAttribute(Mesh::ATTRIBUTE_NORMAL.name))
+        );
+        assert!(mesh.get_colors().is_none());
+
+        mesh.set_joints(vec![[0; 4]; 24], vec![[1.0; 4]; 24]);
+        assert_eq!(
+            mesh.try_set_joints(vec![[1; 4]; 24], vec![[0.5; 4]; 3])
+                .err(),
+            length(Mesh::ATTRIBUTE_JOINT_WEIGHT.name)
+        );
+        assert_eq!(
+            mesh.get_attribute(Mesh::ATTRIBUTE_JOINT_INDEX),
+            Some(&VertexAttributeValues::Uint16x4(vec![[0; 4]; 24]))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "has 0 values but the mesh has 24 vertices")]
+    fn setters_panic() {
+        cuboid().set_colors(Vec::new());
+    }
+}
@@ -1,4 +1,4 @@
-use super::PMesh;
+use super::{OrPanic, PIndices, PMesh, PMeshError, SmoothNormalsSettings};
 use crate::IndexType;
 use bevy::{asset::RenderAssetUsages, mesh::VertexAttributeValues, prelude::*, render::render_resource::PrimitiveTopology};
 
@@ -7,13 +7,22 @@ where
     T: IndexType,
 {
     /// Copies the mesh into an existing bevy mesh.
+    ///
+    /// Panics if the mesh is invalid or the bevy mesh is not a triangle list. See `try_bevy_set`.
     pub fn bevy_set(&self, mesh: &mut Mesh) {
-        assert!(self.indices.iter_usize().all(|i| i < self.vertices.len()));
+        self.try_bevy_set(mesh).or_panic();
+    }
 
-        assert!(
-            mesh.primitive_topology() == PrimitiveTopology::TriangleList,
-            "Only triangle lists are supported"
-        );
+    /// Copies the mesh into an existing bevy mesh.
+    /// Returns an error without modifying the bevy mesh if the mesh is invalid or the bevy mesh is not a triangle list.
+    ///
+    /// If the mesh has no normals, the bevy mesh gets flat normals (see `flat_normals`) without modifying the mesh.
+    /// Tangents are copied if they were computed with `compute_tangents`.
+    pub fn try_bevy_set(&self, mesh: &mut Mesh) -> Result<(), PMeshError> {
+        self.validate_structure()?;
+        if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+            return Err(PMeshError::UnsupportedTopology);
+        }
 
         mesh.remove_indices();
         let mut attributes_to_remove = Vec::new();
@@ -24,40 +33,65 @@ where
             mesh.remove_attribute(attr_id);
         }
 
+        let Some(normals) = &self.normals else {
+            // split the vertices for flat normals while copying them instead of cloning the whole mesh first
+            let (kept, indices, normals) =
+                self.split_normals(&SmoothNormalsSettings::flat(), Vec::new());
+            mesh.insert_indices(PIndices::build(indices).get_bevy());
+            mesh.insert_attribute(
+                Mesh::ATTRIBUTE_POSITION,
+                VertexAttributeValues::Float32x3(kept.iter().map(|i| self.vertices[*i]).collect()),
+            );
+            if let Some(uv) = &self.uv {
+                mesh.insert_attribute(
+                    Mesh::ATTRIBUTE_UV_0,
+                    VertexAttributeValues::Float32x2(kept.iter().map(|i| uv[*i]).collect()),
+                );
+            }
+            for (attribute, values) in self.attributes.gather(&kept).iter() {
+                mesh.insert_attribute(*attribute, values.clone());
+            }
+            mesh.insert_attribute(
+                Mesh::ATTRIBUTE_NORMAL,
+                VertexAttributeValues::Float32x3(normals),
+            );
+            return Ok(());
+        };
+
         mesh.insert_indices(self.indices.get_bevy());
         mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.vertices.to_bevy());
         if let Some(uv) = &self.uv {
-            assert!(self.vertices.len() == uv.len());
             mesh.insert_attribute(
                 Mesh::ATTRIBUTE_UV_0,
                 VertexAttributeValues::Float32x2(uv.clone()),
             );
         }
 
-        if let Some(normals) = &self.normals {
-            assert!(self.vertices.len() == normals.len());
-            mesh.insert_attribute(
-                Mesh::ATTRIBUTE_NORMAL,
-                VertexAttributeValues::Float32x3(normals.clone()),
-            );
-        } else if mesh.primitive_topology() == PrimitiveTopology::TriangleList {
-            mesh.duplicate_vertices();
-            mesh.compute_flat_normals();
+        for (attribute, values) in self.attributes.iter() {
+            mesh.insert_attribute(*attribute, values.clone());
         }
 
-        /*if mesh.contains_attribute(Mesh::ATTRIBUTE_NORMAL) {
-            // This will sometimes panic when the mesh is weird. Not very stable at all!
-            if mesh.generate_tangents().is_err() {
-                // TODO
-                println!("WARN: Failed to generate tangents");
-            }
-        }*/
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_NORMAL,
+            VertexAttributeValues::Float32x3(normals.clone()),
+        );
+
+        Ok(())
     }
 
     /// Creates a bevy mesh from the mesh.
+    ///
+    /// Panics if the mesh is invalid. See `try_to_bevy`.
     pub fn to_bevy(&self, usage: RenderAssetUsages) -> Mesh {
         let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, usage);
         self.bevy_set(&mut mesh);
         mesh
     }
+
+    /// Creates a bevy mesh from the mesh. Returns an error if the mesh is invalid.
+    pub fn try_to_bevy(&self, usage: RenderAssetUsages) -> Result<Mesh, PMeshError> {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, usage);
+        self.try_bevy_set(&mut mesh)?;
+        Ok(mesh)
+    }
 }
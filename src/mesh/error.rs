@@ -0,0 +1,170 @@
+//! Errors of mesh operations.
+
+/// An error of a mesh operation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PMeshError {
+    /// Only one of the meshes has the given vertex attribute or the formats don't match.
+    MissingAttribute(&'static str),
+
+    /// The attribute is stored directly in the mesh and can't be set as an additional attribute.
+    ReservedAttribute(&'static str),
+
+    /// The values of an attribute don't match its format.
+    AttributeFormat(&'static str),
+
+    /// The number of values of an attribute doesn't match the number of vertices.
+    AttributeLength {
+        /// The name of the attribute.
+        name: &'static str,
+        /// The number of values.
+        len: usize,
+        /// The number of vertices.
+        expected: usize,
+    },
+
+    /// The number of indices is not a multiple of 3.
+    IndexCount(usize),
+
+    /// An index refers to a vertex that doesn't exist.
+    IndexOutOfRange {
+        /// The invalid index.
+        index: usize,
+        /// The number of vertices.
+        vertex_count: usize,
+    },
+
+    /// The index is too large for the index type of the mesh.
+    IndexTypeOverflow(usize),
+
+    /// The position of the vertex with the given index is NaN or infinite.
+    InvalidPosition(usize),
+
+    /// The triangle with the given index has no area.
+    DegenerateTriangle(usize),
+
+    /// The uv coordinates of the triangle with the given index have no area or are not finite,
+    /// so no tangents can be computed for it.
+    DegenerateUv(usize),
+
+    /// The generation of tangents failed.
+    TangentGeneration(String),
+
+    /// The bevy mesh doesn't use a triangle list topology.
+    UnsupportedTopology,
+
+    /// The mesh couldn't be unwrapped into a uv atlas for the given reason.
+    UvUnwrap(&'static str),
+
+    /// The SVG path data is invalid.
+    #[cfg(feature = "lyon")]
+    SvgPath {
+        /// The byte offset of the invalid token.
+        position: usize,
+        /// What was expected at the position.
+        message: &'static str,
+    },
+
+    /// The SVG document couldn't be read or parsed.
+    #[cfg(feature = "svg")]
+    Svg(String),
+
+    /// The font couldn't be parsed.
+    #[cfg(feature = "text")]
+    Font(String),
+
+    /// The tessellation of a lyon path failed.
+    #[cfg(feature = "lyon")]
+    Tessellation(lyon::tessellation::TessellationError),
+}
+
+impl std::fmt::Display for PMeshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PMeshError::MissingAttribute(name) => {
+                write!(
+                    f,
+                    "the vertex attribute {} is missing in one of the meshes",
+                    name
+                )
+            }
+            PMeshError::ReservedAttribute(name) => {
+                write!(
+                    f,
+                    "the attribute {} can't be set as an additional attribute",
+                    name
+                )
+            }
+            PMeshError::AttributeFormat(name) => {
+                write!(f, "the values of attribute {} don't match its format", name)
+            }
+            PMeshError::AttributeLength {
+                name,
+                len,
+                expected,
+            } => write!(
+                f,
+                "the attribute {} has {} values but the mesh has {} vertices",
+                name, len, expected
+            ),
+            PMeshError::IndexCount(count) => {
+                write!(f, "the number of indices {} is not a multiple of 3", count)
+            }
+            PMeshError::IndexOutOfRange {
+                index,
+                vertex_count,
+            } => write!(
+                f,
+                "the index {} is out of range for {} vertices",
+                index, vertex_count
+            ),
+            PMeshError::IndexTypeOverflow(index) => {
+                write!(f, "the index {} doesn't fit into the index type", index)
+            }
+            PMeshError::InvalidPosition(i) => {
+                write!(f, "the position of vertex {} is not finite", i)
+            }
+            PMeshError::DegenerateTriangle(i) => write!(f, "the triangle {} is degenerate", i),
+            PMeshError::DegenerateUv(i) => {
+                write!(f, "the uv coordinates of triangle {} are degenerate", i)
+            }
+            PMeshError::TangentGeneration(e) => write!(f, "failed to generate tangents: {}", e),
+            PMeshError::UnsupportedTopology => write!(f, "only triangle lists are supported"),
+            PMeshError::UvUnwrap(reason) => write!(f, "failed to unwrap the mesh: {}", reason),
+            #[cfg(feature = "lyon")]
+            PMeshError::SvgPath { position, message } => {
+                write!(f, "invalid svg path data at {}: {}", position, message)
+            }
+            #[cfg(feature = "svg")]
+            PMeshError::Svg(e) => write!(f, "failed to load the svg document: {}", e),
+            #[cfg(feature = "text")]
+            PMeshError::Font(e) => write!(f, "failed to parse the font: {}", e),
+            #[cfg(feature = "lyon")]
+            PMeshError::Tessellation(e) => write!(f, "tessellation failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PMeshError {}
+
+/// Unwraps the results of the `try_*` methods in their panicking counterparts.
+pub(crate) trait OrPanic<T> {
+    /// Returns the value or panics with the message of the error.
+    fn or_panic(self) -> T;
+}
+
+impl<T> OrPanic<T> for Result<T, PMeshError> {
+    #[track_caller]
+    fn or_panic(self) -> T {
+        match self {
+            Ok(value) => value,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+#[cfg(feature = "lyon")]
+impl From<lyon::tessellation::TessellationError> for PMeshError {
+    fn from(e: lyon::tessellation::TessellationError) -> Self {
+        PMeshError::Tessellation(e)
+    }
+}
@@ -0,0 +1,264 @@
+//! Appending meshes.
+
+use super::{IndexType, OrPanic, PMesh, PMeshError};
+use bevy::prelude::*;
+
+/// How to handle vertex attributes that only one of the meshes has when appending meshes.
+///
+/// Empty meshes are treated as having every attribute, so extending an empty mesh copies all attributes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissingAttributes {
+    /// Removes the attribute from the result.
+    Drop,
+
+    /// Fills the missing values with zeros.
+    Default,
+
+    /// Computes smooth normals for the mesh that is missing them. Other attributes are filled with zeros.
+    #[default]
+    Compute,
+
+    /// Returns an error.
+    Error,
+}
+
+/// Appends the per-vertex values `b` of a mesh with `k` vertices to the values `a` of a mesh with `n` vertices.
+/// The `fill` functions provide the values for the mesh that is missing them.
+///
+/// The `fill` functions must return exactly one value per vertex. Returns their error if they fail.
+fn merge_buffer<A: Clone>(
+    a: Option<Vec<A>>,
+    n: usize,
+    b: Option<&Vec<A>>,
+    k: usize,
+    missing: MissingAttributes,
+    fill_a: impl FnOnce() -> Result<Vec<A>, PMeshError>,
+    fill_b: impl FnOnce() -> Result<Vec<A>, PMeshError>,
+) -> Result<Option<Vec<A>>, PMeshError> {
+    Ok(match (a, b) {
+        (Some(mut a), Some(b)) => {
+            a.extend_from_slice(b);
+            Some(a)
+        }
+        (None, None) => None,
+        (Some(a), None) if k == 0 => Some(a),
+        (None, Some(b)) if n == 0 => Some(b.clone()),
+        _ if missing == MissingAttributes::Drop => None,
+        (Some(mut a), None) => {
+            a.extend(fill_b()?);
+            Some(a)
+        }
+        (None, Some(b)) => {
+            let mut a = fill_a()?;
+            a.extend_from_slice(b);
+            Some(a)
+        }
+    })
+}
+
+impl<T> PMesh<T>
+where
+    T: IndexType,
+{
+    /// Appends another mesh to this one.
+    ///
+    /// Attributes that only one of the meshes has are computed or filled with zeros (see `MissingAttributes::Compute`).
+    ///
+    /// Panics if one of the meshes is invalid. See `extend_with`.
+    pub fn extend(&mut self, m: &PMesh<T>) -> &mut PMesh<T> {
+        self.extend_with(m, MissingAttributes::Compute).or_panic();
+        self
+    }
+
+    /// Appends another mesh to this one with the given policy for attributes that only one of the meshes has.
+    ///
+    /// Returns an error without changing the mesh if the indices or the attribute lengths of one of the meshes
+    /// are invalid, or if an attribute is missing and the policy is `MissingAttributes::Error`.
+    pub fn extend_with(
+        &mut self,
+        m: &PMesh<T>,
+        missing: MissingAttributes,
+    ) -> Result<&mut PMesh<T>, PMeshError> {
+        self.validate_structure()?;
+        m.validate_structure()?;
+        if missing == MissingAttributes::Error {
+            if let Some(name) = self.find_missing(m) {
+                return Err(PMeshError::MissingAttribute(name));
+            }
+        }
+        self.append(m, missing)?;
+        Ok(self)
+    }
+
+    /// Returns the name of the first attribute that only one of the meshes has.
+    fn find_missing(&self, m: &PMesh<T>) -> Option<&'static str> {
+        let (n, k) = (self.vertices.len(), m.vertices.len());
+        if n > 0 && k > 0 {
+            if self.uv.is_some() != m.uv.is_some() {
+                return Some(Mesh::ATTRIBUTE_UV_0.name);
+            }
+            if self.normals.is_some() != m.normals.is_some() {
+                return Some(Mesh::ATTRIBUTE_NORMAL.name);
+            }
+        }
+        self.attributes.find_missing(n, &m.attributes, k)
+    }
+
+    /// Returns smooth normals of the mesh without modifying it, one for each vertex.
+    /// Returns an error if the indices are invalid.
+    pub(crate) fn computed_normals(&self) -> Result<Vec<[f32; 3]>, PMeshError> {
+        let mut m = PMesh::try_build_ex(
+            self.vertices.get_vertices().clone(),
+            self.indices.get_indices().clone(),
+            None,
+            None,
+        )?;
+        m.smooth_normals(false);
+        match m.normals {
+            Some(normals) if normals.len() == self.vertices.len() => Ok(normals),
+            normals => Err(PMeshError::AttributeLength {
+                name: Mesh::ATTRIBUTE_NORMAL.name,
+                len: normals.map_or(0, |normals| normals.len()),
+                expected: self.vertices.len(),
+            }),
+        }
+    }
+
+    fn append(&mut self, m: &PMesh<T>, missing: MissingAttributes) -> Result<(), PMeshError> {
+        let (n, k) = (self.vertices.len(), m.vertices.len());
+        let compute = missing == MissingAttributes::Compute;
+
+        self.normals = merge_buffer(
+            self.normals.take(),
+            n,
+            m.normals.as_ref(),
+            k,
+            missing,
+            || match compute {
+                true => self.computed_normals(),
+                false => Ok(vec![[0.0; 3]; n]),
+            },
+            || match compute {
+                true => m.computed_normals(),
+                false => Ok(vec![[0.0; 3]; k]),
+            },
+        )?;
+        self.uv = merge_buffer(
+            self.uv.take(),
+            n,
+            m.uv.as_ref(),
+            k,
+            missing,
+            || Ok(vec![[0.0; 2]; n]),
+            || Ok(vec![[0.0; 2]; k]),
+        )?;
+        self.attributes.extend(n, &m.attributes, k, missing);
+
+        self.vertices.extend(&m.vertices);
+        self.indices
+            .extend(m.indices.clone().map(|i: T| i.add(T::new(n))));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A counter-clockwise triangle in the xy-plane.
+    fn triangle(uv: bool, normals: bool) -> PMesh<u32> {
+        let mut mesh = PMesh::build(
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            vec![0, 1, 2],
+            uv.then(|| vec![[0.5, 0.5]; 3]),
+        );
+        if normals {
+            mesh.normals = Some(vec![[1.0, 0.0, 0.0]; 3]);
+        }
+        mesh
+    }
+
+    fn extended(missing: MissingAttributes) -> Result<PMesh<u32>, PMeshError> {
+        let mut mesh = triangle(true, true);
+        mesh.extend_with(&triangle(false, false), missing)?;
+        Ok(mesh)
+    }
+
+    #[test]
+    fn drop_removes_missing_attributes() {
+        let mesh = extended(MissingAttributes::Drop).unwrap();
+        assert_eq!(mesh.vertices.len(), 6);
+        assert_eq!(mesh.uv, None);
+        assert_eq!(mesh.normals, None);
+        assert_eq!(mesh.indices.get_indices(), &vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn default_fills_missing_attributes_with_zeros() {
+        let mesh = extended(MissingAttributes::Default).unwrap();
+        let mut uv = vec![[0.5, 0.5]; 3];
+        uv.extend([[0.0, 0.0]; 3]);
+        assert_eq!(mesh.uv, Some(uv));
+        let mut normals = vec![[1.0, 0.0, 0.0]; 3];
+        normals.extend([[0.0, 0.0, 0.0]; 3]);
+        assert_eq!(mesh.normals, Some(normals));
+    }
+
+    #[test]
+    fn compute_computes_missing_normals() {
+        let mesh = extended(MissingAttributes::Compute).unwrap();
+        let mut uv = vec![[0.5, 0.5]; 3];
+        uv.extend([[0.0, 0.0]; 3]);
+        assert_eq!(mesh.uv, Some(uv));
+        let mut normals = vec![[1.0, 0.0, 0.0]; 3];
+        normals.extend([[0.0, 0.0, 1.0]; 3]);
+        assert_eq!(mesh.normals, Some(normals));
+
+        // the same in the other direction
+        let mut mesh = triangle(false, false);
+        mesh.extend(&triangle(true, true));
+        assert_eq!(mesh.normals.unwrap()[..3], [[0.0, 0.0, 1.0]; 3]);
+    }
+
+    #[test]
+    fn error_leaves_the_mesh_unchanged() {
+        assert_eq!(
+            extended(MissingAttributes::Error).err(),
+            Some(PMeshError::MissingAttribute(Mesh::ATTRIBUTE_UV_0.name))
+        );
+        let mut mesh = triangle(true, true);
+        assert!(mesh
+            .extend_with(&triangle(true, false), MissingAttributes::Error)
+            .is_err());
+        assert_eq!(mesh.vertices.len(), 3);
+
+        // empty meshes have every attribute
+        let mut mesh = PMesh::new();
+        mesh.extend_with(&triangle(true, true), MissingAttributes::Error)
+            .unwrap();
+        assert_eq!(mesh.uv, Some(vec![[0.5, 0.5]; 3]));
+    }
+
+    #[test]
+    fn invalid_meshes_are_an_error() {
+        let mut invalid = triangle(true, false);
+        invalid.indices.get_indices_mut()[2] = 7;
+        let mut mesh = triangle(true, false);
+        assert_eq!(
+            mesh.extend_with(&invalid, MissingAttributes::Compute).err(),
+            Some(PMeshError::IndexOutOfRange {
+                index: 7,
+                vertex_count: 3
+            })
+        );
+        assert_eq!(mesh.vertices.len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "has 1 values but the mesh has 3 vertices")]
+    fn extend_panics_on_invalid_meshes() {
+        let mut invalid = triangle(false, false);
+        invalid.uv = Some(vec![[0.0, 0.0]]);
+        triangle(false, false).extend(&invalid);
+    }
+}
@@ -126,6 +126,14 @@ where
         PIndices { indices }
     }
 
+    /// Flips the winding order of every triangle in place.
+    pub fn flip_winding(&mut self) -> &mut PIndices<T> {
+        for face in self.indices.chunks_exact_mut(3) {
+            face.swap(1, 2);
+        }
+        self
+    }
+
     /// Adds a reversed copy of the indices to the end of the list.
     pub fn add_backfaces(&mut self) -> &mut PIndices<T> {
         self.extend(&self.reversed())
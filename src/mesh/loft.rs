@@ -0,0 +1,274 @@
+//! Lofting a surface through a series of cross-sections.
+
+use super::{profile::CREASE_ANGLE, IndexType, PMesh, PVertices};
+use bevy::math::Vec3;
+
+/// How the derivative along the profile is taken at a column of the loft.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Side {
+    Both,
+    Incoming,
+    Outgoing,
+}
+
+impl<T> PMesh<T>
+where
+    T: IndexType,
+{
+    /// Creates a skinned surface through the given cross-sections.
+    ///
+    /// The profiles can have different numbers of vertices. They are resampled at the union of the
+    /// normalized arc-length parameters of all their vertices, so the corners of every profile are preserved.
+    /// Closed profiles should start at corresponding points and run in the same direction.
+    /// Looking from the first towards the last profile, counter-clockwise profiles produce outward-facing surfaces.
+    /// The uv coordinates follow the normalized arc length of the profiles (u) and the index of the profile (v).
+    /// Profiles with non-finite vertices don't panic, but produce non-finite vertices.
+    pub fn loft(profiles: &[PVertices], closed: bool) -> PMesh<T> {
+        let profiles: Vec<Vec<Vec3>> = profiles
+            .iter()
+            .map(|p| p.get_vertices().iter().map(|v| Vec3::from(*v)).collect())
+            .filter(|p: &Vec<Vec3>| p.len() >= 2)
+            .collect();
+        if profiles.len() < 2 {
+            return PMesh::new();
+        }
+
+        // union of the arc-length parameters of all profiles
+        let mut params: Vec<f32> = profiles
+            .iter()
+            .flat_map(|p| arc_length_params(p, closed))
+            .collect();
+        params.sort_by(f32::total_cmp);
+        params.dedup_by(|a, b| (*a - *b).abs() < 1e-5);
+
+        let grid: Vec<Vec<Vec3>> = profiles
+            .iter()
+            .map(|p| {
+                let t = arc_length_params(p, closed);
+                params.iter().map(|u| sample(p, &t, *u, closed)).collect()
+            })
+            .collect();
+        let m = grid.len();
+        let n = params.len();
+
+        // split the columns at corners of any profile
+        let is_crease = |j: usize| {
+            let (prev, next) = if closed && (j == 0 || j == n - 1) {
+                (n - 2, 1)
+            } else if j == 0 || j == n - 1 {
+                return false;
+            } else {
+                (j - 1, j + 1)
+            };
+            grid.iter().any(|row| {
+                let a = row[j] - row[prev];
+                let b = row[next] - row[j];
+                a.length_squared() > 0.0
+                    && b.length_squared() > 0.0
+                    && a.angle_between(b) > CREASE_ANGLE
+            })
+        };
+        let mut columns: Vec<(usize, Side)> = Vec::new();
+        for j in 0..n {
+            if is_crease(j) {
+                if j > 0 {
+                    columns.push((j, Side::Incoming));
+                }
+                if j < n - 1 {
+                    columns.push((j, Side::Outgoing));
+                }
+            } else {
+                columns.push((j, Side::Both));
+            }
+        }
+
+        let mut vertices: Vec<[f32; 3]> = Vec::new();
+        let mut uv: Vec<[f32; 2]> = Vec::new();
+        let mut normals: Vec<[f32; 3]> = Vec::new();
+        for (k, row) in grid.iter().enumerate() {
+            let across = grid[(k + 1).min(m - 1)][0..n]
+                .iter()
+                .zip(grid[k.saturating_sub(1)].iter())
+                .map(|(a, b)| *a - *b)
+                .collect::<Vec<_>>();
+            for (j, side) in &columns {
+                let j = *j;
+                let prev = if j > 0 {
+                    row[j - 1]
+                } else if closed {
+                    row[n - 2]
+                } else {
+                    row[j]
+                };
+                let next = if j < n - 1 {
+                    row[j + 1]
+                } else if closed {
+                    row[1]
+                } else {
+                    row[j]
+                };
+                let along = match side {
+                    Side::Both => next - prev,
+                    Side::Incoming => row[j] - prev,
+                    Side::Outgoing => next - row[j],
+                };
+                vertices.push(row[j].to_array());
+                uv.push([params[j], k as f32 / (m - 1) as f32]);
+                normals.push(across[j].cross(along).normalize_or_zero().to_array());
+            }
+        }
+
+        let mut indices: Vec<T> = Vec::new();
+        let stride = columns.len();
+        for k in 0..m - 1 {
+            for c in 0..stride - 1 {
+                if columns[c].0 == columns[c + 1].0 {
+                    continue;
+                }
+                let a = k * stride + c;
+                let b = a + stride;
+                indices.extend([a, b, b + 1, a, b + 1, a + 1].map(T::new));
+            }
+        }
+
+        PMesh::build_ex(vertices, indices, Some(uv), Some(normals))
+    }
+}
+
+/// Returns the normalized arc-length parameter of each vertex. Closed profiles get an additional parameter 1 at the end.
+fn arc_length_params(points: &[Vec3], closed: bool) -> Vec<f32> {
+    let n = points.len();
+    let mut t = vec![0.0];
+    let steps = if closed { n } else { n - 1 };
+    for i in 0..steps {
+        t.push(t[i] + points[i].distance(points[(i + 1) % n]));
+    }
+    let total = t[steps];
+    if total > 0.0 {
+        t.iter_mut().for_each(|x| *x /= total);
+    }
+    t
+}
+
+/// Evaluates the polyline at the normalized arc-length parameter `u`.
+fn sample(points: &[Vec3], t: &[f32], u: f32, closed: bool) -> Vec3 {
+    let n = points.len();
+    let steps = if closed { n } else { n - 1 };
+    let i = t[1..steps].partition_point(|x| *x <= u);
+    let (a, b) = (points[i], points[(i + 1) % n]);
+    let len = t[i + 1] - t[i];
+    if len <= 0.0 {
+        a
+    } else {
+        a.lerp(b, ((u - t[i]) / len).clamp(0.0, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::Vec2;
+
+    /// A profile at height `z` through the given points in the xy-plane. Looking along the z-axis, like from the
+    /// first towards the last profile, the points of the test profiles are counter-clockwise.
+    fn profile(points: &[[f32; 2]], z: f32) -> PVertices {
+        PVertices::build(points.iter().map(|p| [p[0], p[1], z]).collect())
+    }
+
+    const SQUARE: [[f32; 2]; 4] = [[1.0, 1.0], [1.0, -1.0], [-1.0, -1.0], [-1.0, 1.0]];
+
+    /// Asserts that the normals point away from the z-axis and agree with the winding order.
+    fn assert_outwards(mesh: &PMesh<u32>) {
+        let normals = mesh.get_normals().unwrap();
+        for (i, n) in normals.iter().enumerate() {
+            let p = mesh.vec3_at(i);
+            assert!(Vec3::from(*n).dot(Vec3::new(p.x, p.y, 0.0)) > 0.0);
+        }
+        for [a, b, c] in mesh.iter_faces() {
+            let (pa, pb, pc) = (mesh.vec3_at(a), mesh.vec3_at(b), mesh.vec3_at(c));
+            let face = (pb - pa).cross(pc - pa);
+            assert!(face.dot(Vec3::from(normals[a])) > 0.0);
+        }
+    }
+
+    #[test]
+    fn creases_split_the_columns() {
+        let mesh = PMesh::<u32>::loft(&[profile(&SQUARE, 0.0), profile(&SQUARE, 1.0)], true);
+        // the first and last column only have one side, the three other corners are split
+        assert_eq!(mesh.vertices.len(), 2 * 8);
+        assert_eq!(mesh.indices.len(), 4 * 6);
+        assert!(mesh.validate().is_ok());
+        assert_outwards(&mesh);
+        // every side is flat
+        let normals = mesh.get_normals().unwrap();
+        for [a, b, c] in mesh.iter_faces() {
+            assert_eq!(normals[a], normals[b]);
+            assert_eq!(normals[a], normals[c]);
+        }
+
+        let circle: Vec<[f32; 2]> = (0..32)
+            .map(|i| Vec2::from_angle(-(i as f32) / 32.0 * std::f32::consts::TAU).to_array())
+            .collect();
+        let mesh = PMesh::<u32>::loft(&[profile(&circle, 0.0), profile(&circle, 1.0)], true);
+        assert_eq!(mesh.vertices.len(), 2 * 33);
+        assert_outwards(&mesh);
+    }
+
+    #[test]
+    fn profiles_with_different_point_counts() {
+        let triangle = [[1.0, 0.0], [-0.5, -0.866], [-0.5, 0.866]];
+        let octagon: Vec<[f32; 2]> = SQUARE
+            .iter()
+            .zip(SQUARE.iter().cycle().skip(1))
+            .flat_map(|(a, b)| [*a, [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0]])
+            .collect();
+        let mesh = PMesh::<u32>::loft(
+            &[
+                profile(&triangle, 0.0),
+                profile(&SQUARE, 1.0),
+                profile(&octagon, 2.0),
+            ],
+            true,
+        );
+        assert!(mesh.validate().is_ok());
+
+        // the union of {0, 1/3, 2/3, 1} and {0, 1/8, ..., 1} has 11 parameters
+        let uv = mesh.get_uv().unwrap();
+        let mut params: Vec<f32> = uv.iter().map(|uv| uv[0]).collect();
+        params.dedup();
+        params.truncate(params.iter().position(|u| *u == 1.0).unwrap() + 1);
+        assert_eq!(params.len(), 11);
+
+        // the corners of every profile are vertices of its row
+        for (z, corners) in [(0.0, &triangle[..]), (1.0, &SQUARE[..])] {
+            for corner in corners {
+                let corner = Vec3::new(corner[0], corner[1], z);
+                assert!(mesh
+                    .vertices
+                    .get_vertices()
+                    .iter()
+                    .any(|v| Vec3::from(*v).distance(corner) < 1e-5));
+            }
+        }
+        // the resampled square stays on the square
+        for v in mesh.vertices.get_vertices().iter().filter(|v| v[2] == 1.0) {
+            assert!((v[0].abs().max(v[1].abs()) - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn nan_profiles_dont_panic() {
+        let mut points = SQUARE;
+        points[1] = [f32::NAN, 1.0];
+        let mesh = PMesh::<u32>::loft(&[profile(&SQUARE, 0.0), profile(&points, 1.0)], true);
+        assert!(mesh
+            .vertices
+            .get_vertices()
+            .iter()
+            .any(|v| v.iter().any(|x| !x.is_finite())));
+        assert!(mesh.validate_structure().is_ok());
+
+        let mesh = PMesh::<u32>::loft(&[profile(&points, 0.0), profile(&points, 1.0)], false);
+        assert!(mesh.validate_structure().is_ok());
+    }
+}
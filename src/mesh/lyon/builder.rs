@@ -1,15 +1,22 @@
-use bevy::math::{Affine2, Vec2};
+use bevy::math::{Affine2, Rect, Vec2};
+use lyon::geom::{Angle, ArcFlags, SvgArc};
 pub use lyon::path::traits::PathBuilder;
 use lyon::path::{builder::NoAttributes, traits::Build, Winding};
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
 
 /// This structure wraps a `lyon::tesselation::PathBuilder` and adds functionality to apply transformations to the path being built.
 pub struct PBuilder<T>
 where
     T: PathBuilder,
 {
-    builder: NoAttributes<T>,
+    builder: T,
     transform: Affine2,
     stack: Vec<Affine2>,
+    /// The current point and the start of the current sub-path after the transformation.
+    current: Vec2,
+    start: Vec2,
+    /// The custom attributes of the following points. The first one is the width of variable-width strokes.
+    attributes: Vec<f32>,
 }
 
 #[inline]
@@ -22,19 +29,70 @@ fn vec2v(v: Vec2) -> lyon::math::Vector {
     lyon::math::Vector::new(v.x, v.y)
 }
 
+/// Approximates an elliptic arc with cubic bézier curves of at most 90° each.
+/// Calls `f` with the control points and end point of every curve.
+fn for_each_arc_cubic(
+    center: Vec2,
+    radii: Vec2,
+    x_rotation: f32,
+    start_angle: f32,
+    sweep_angle: f32,
+    mut f: impl FnMut(Vec2, Vec2, Vec2),
+) {
+    let rotation = Vec2::from_angle(x_rotation);
+    let point = |a: f32| center + rotation.rotate(radii * Vec2::new(a.cos(), a.sin()));
+    let tangent = |a: f32| rotation.rotate(radii * Vec2::new(-a.sin(), a.cos()));
+    let sweep = sweep_angle.clamp(-TAU, TAU);
+    let steps = (sweep.abs() / FRAC_PI_2).ceil().max(1.0);
+    let step = sweep / steps;
+    // the optimal length of the control vectors of a circular arc
+    let k = 4.0 / 3.0 * (step / 4.0).tan();
+    for i in 0..steps as usize {
+        let a1 = start_angle + step * i as f32;
+        let a2 = a1 + step;
+        f(
+            point(a1) + tangent(a1) * k,
+            point(a2) - tangent(a2) * k,
+            point(a2),
+        );
+    }
+}
+
 impl<T> PBuilder<T>
 where
     T: PathBuilder,
 {
     /// Creates a new builder with the given fill builder.
     pub fn new(builder: NoAttributes<T>) -> Self {
+        PBuilder::with_attributes(builder.into_inner())
+    }
+
+    /// Creates a new builder with the given path builder with custom attributes.
+    ///
+    /// All custom attributes start at 1. The first one is set by `set_width`.
+    pub fn with_attributes(builder: T) -> Self {
+        let attributes = vec![1.0; builder.num_attributes()];
         PBuilder {
             builder,
             transform: Affine2::IDENTITY,
             stack: Vec::new(),
+            current: Vec2::ZERO,
+            start: Vec2::ZERO,
+            attributes,
         }
     }
 
+    /// Sets the width of the stroke at the following points.
+    ///
+    /// The width isn't transformed. It is ignored if the underlying builder has no custom attributes,
+    /// e.g., when filling.
+    pub fn set_width(&mut self, width: f32) -> &mut Self {
+        if let Some(w) = self.attributes.first_mut() {
+            *w = width;
+        }
+        self
+    }
+
     /// Stores the current transformation on the stack.
     pub fn push(&mut self) -> &mut Self {
         self.stack.push(self.transform);
@@ -84,11 +142,17 @@ where
 
     /// Begins a new sub-path at the given point.
     pub fn begin(&mut self, point: Vec2) -> &mut Self {
-        self.builder
-            .begin(vec2p(self.transform.transform_point2(point)));
+        self.start = self.transform.transform_point2(point);
+        self.current = self.start;
+        self.builder.begin(vec2p(self.start), &self.attributes);
         self
     }
 
+    /// Begins a new sub-path at the given point where the stroke has the given width. See `set_width`.
+    pub fn begin_w(&mut self, point: Vec2, width: f32) -> &mut Self {
+        self.set_width(width).begin(point)
+    }
+
     /// Begins a new sub-path at the origin of the current transformation.
     pub fn begin_here(&mut self) -> &mut Self {
         self.begin(Vec2::ZERO)
@@ -107,6 +171,9 @@ where
     /// After this method is called, there is no sub-path in progress until begin is called again.
     pub fn end(&mut self, close: bool) -> &mut Self {
         self.builder.end(close);
+        if close {
+            self.current = self.start;
+        }
         self
     }
 
@@ -141,44 +208,248 @@ where
     ///
     /// A sub-path must be in progress when this method is called.
     pub fn line_to(&mut self, point: Vec2) -> &mut Self {
-        self.builder
-            .line_to(vec2p(self.transform.transform_point2(point)));
+        self.current = self.transform.transform_point2(point);
+        self.builder.line_to(vec2p(self.current), &self.attributes);
         self
     }
 
+    /// Adds a transformed line segment to the current sub-path. The stroke has the given width at its end.
+    /// See `set_width`.
+    ///
+    /// A sub-path must be in progress when this method is called.
+    pub fn line_to_w(&mut self, point: Vec2, width: f32) -> &mut Self {
+        self.set_width(width).line_to(point)
+    }
+
     /// Adds a transformed quadratic bézier curve to the current sub-path.
     ///
     /// A sub-path must be in progress when this method is called.
     pub fn quadratic_bezier_to(&mut self, ctrl: Vec2, to: Vec2) -> &mut Self {
+        self.current = self.transform.transform_point2(to);
         self.builder.quadratic_bezier_to(
             vec2p(self.transform.transform_point2(ctrl)),
-            vec2p(self.transform.transform_point2(to)),
+            vec2p(self.current),
+            &self.attributes,
         );
         self
     }
 
+    /// Adds a transformed quadratic bézier curve to the current sub-path. The stroke has the given width
+    /// at its end. See `set_width`.
+    ///
+    /// A sub-path must be in progress when this method is called.
+    pub fn quadratic_bezier_to_w(&mut self, ctrl: Vec2, to: Vec2, width: f32) -> &mut Self {
+        self.set_width(width).quadratic_bezier_to(ctrl, to)
+    }
+
     /// Adds a transformed cubic bézier curve to the current sub-path.
     ///
     /// A sub-path must be in progress when this method is called.
     pub fn cubic_bezier_to(&mut self, ctrl1: Vec2, ctrl2: Vec2, to: Vec2) -> &mut Self {
+        self.current = self.transform.transform_point2(to);
         self.builder.cubic_bezier_to(
             vec2p(self.transform.transform_point2(ctrl1)),
             vec2p(self.transform.transform_point2(ctrl2)),
-            vec2p(self.transform.transform_point2(to)),
+            vec2p(self.current),
+            &self.attributes,
         );
         self
     }
 
+    /// Adds a transformed cubic bézier curve to the current sub-path. The stroke has the given width
+    /// at its end. See `set_width`.
+    ///
+    /// A sub-path must be in progress when this method is called.
+    pub fn cubic_bezier_to_w(
+        &mut self,
+        ctrl1: Vec2,
+        ctrl2: Vec2,
+        to: Vec2,
+        width: f32,
+    ) -> &mut Self {
+        self.set_width(width).cubic_bezier_to(ctrl1, ctrl2, to)
+    }
+
+    /// Returns the current point in the coordinates of the current transformation.
+    pub fn current_point(&self) -> Vec2 {
+        self.transform.inverse().transform_point2(self.current)
+    }
+
+    /// Adds a transformed elliptic arc around the given center to the current sub-path.
+    ///
+    /// The arc starts at the angle of the current point and sweeps by `sweep_angle` radians,
+    /// where positive angles are counter-clockwise. `x_rotation` rotates the axes of the ellipse.
+    /// If the current point isn't on the ellipse, a line connects it to the start of the arc.
+    ///
+    /// Like in SVG, an ellipse with a zero or non-finite radius becomes a line to the end of the arc.
+    /// Nothing is added if the current point is the center, since the arc has no start angle then.
+    ///
+    /// A sub-path must be in progress when this method is called.
+    pub fn arc_to(
+        &mut self,
+        center: Vec2,
+        radii: Vec2,
+        sweep_angle: f32,
+        x_rotation: f32,
+    ) -> &mut Self {
+        let from = self.current_point();
+        let offset = Vec2::from_angle(-x_rotation).rotate(from - center);
+        if offset == Vec2::ZERO || !offset.is_finite() {
+            return self;
+        }
+        let radii = radii.abs();
+        if !(radii.min_element() > 0.0 && radii.is_finite()) {
+            let end_angle = offset.y.atan2(offset.x) + sweep_angle;
+            let end = center
+                + Vec2::from_angle(x_rotation)
+                    .rotate(radii * Vec2::new(end_angle.cos(), end_angle.sin()));
+            if end.is_finite() {
+                self.line_to(end);
+            }
+            return self;
+        }
+        let local = offset / radii;
+        let start_angle = local.y.atan2(local.x);
+        let start = center
+            + Vec2::from_angle(x_rotation)
+                .rotate(radii * Vec2::new(start_angle.cos(), start_angle.sin()));
+        if start.distance(from) > radii.max_element() * 1e-5 {
+            self.line_to(start);
+        }
+        for_each_arc_cubic(
+            center,
+            radii,
+            x_rotation,
+            start_angle,
+            sweep_angle,
+            |ctrl1, ctrl2, to| {
+                self.cubic_bezier_to(ctrl1, ctrl2, to);
+            },
+        );
+        self
+    }
+
+    /// Adds a transformed elliptic arc from the current point to `to` like the arc command of SVG paths.
+    ///
+    /// Of the up to four arcs with the given radii and rotation (in radians) that connect the points,
+    /// `large_arc` selects one that sweeps more than 180° and `sweep` selects one that is
+    /// counter-clockwise in y-up coordinates. The radii are scaled up if they are too small.
+    ///
+    /// A sub-path must be in progress when this method is called.
+    pub fn svg_arc_to(
+        &mut self,
+        radii: Vec2,
+        x_rotation: f32,
+        large_arc: bool,
+        sweep: bool,
+        to: Vec2,
+    ) -> &mut Self {
+        let from = self.current_point();
+        self.endpoint_arc(from, radii, x_rotation, large_arc, sweep, to);
+        self
+    }
+
+    /// Adds the arc of `svg_arc_to` starting at the given point.
+    pub(super) fn endpoint_arc(
+        &mut self,
+        from: Vec2,
+        radii: Vec2,
+        x_rotation: f32,
+        large_arc: bool,
+        sweep: bool,
+        to: Vec2,
+    ) {
+        let arc = SvgArc {
+            from: vec2p(from),
+            to: vec2p(to),
+            radii: vec2v(radii.abs()),
+            x_rotation: Angle::radians(x_rotation),
+            flags: ArcFlags { large_arc, sweep },
+        };
+        if arc.is_straight_line() {
+            if from != to {
+                self.line_to(to);
+            }
+            return;
+        }
+        let arc = arc.to_arc();
+        for_each_arc_cubic(
+            Vec2::new(arc.center.x, arc.center.y),
+            Vec2::new(arc.radii.x, arc.radii.y),
+            arc.x_rotation.radians,
+            arc.start_angle.radians,
+            arc.sweep_angle.radians,
+            |ctrl1, ctrl2, to| {
+                self.cubic_bezier_to(ctrl1, ctrl2, to);
+            },
+        );
+    }
+
     /// Adds a transformed sub-path containing a rectangle.
     ///
     /// There must be no sub-path in progress when this method is called.
     /// No sub-path is in progress after the method is called.
-    pub fn add_rectangle(&mut self, rect: bevy::math::Rect, winding: Winding) -> &mut Self {
-        let p0 = vec2p(self.transform.transform_point2(rect.min));
-        let p1 = vec2p(self.transform.transform_point2(rect.max));
-        self.builder
-            .add_rectangle(&lyon::math::Box2D::new(p0, p1), winding);
-        self
+    pub fn add_rectangle(&mut self, rect: Rect, winding: Winding) -> &mut Self {
+        self.add_rounded_rectangle(rect, [0.0; 4], winding)
+    }
+
+    /// Adds a transformed sub-path containing a rectangle with rounded corners.
+    ///
+    /// The radii are given counter-clockwise starting at `rect.min`, i.e., for the bottom left,
+    /// bottom right, top right, and top left corner in y-up coordinates.
+    /// Like in CSS, all radii are scaled down if the radii at one side don't fit.
+    ///
+    /// There must be no sub-path in progress when this method is called.
+    /// No sub-path is in progress after the method is called.
+    pub fn add_rounded_rectangle(
+        &mut self,
+        rect: Rect,
+        radii: [f32; 4],
+        winding: Winding,
+    ) -> &mut Self {
+        let size = rect.size();
+        let radii = radii.map(|r| r.max(0.0));
+        let scale = (0..4)
+            .map(|i| {
+                let side = if i % 2 == 0 { size.x } else { size.y };
+                let sum = radii[i] + radii[(i + 1) % 4];
+                if sum > 0.0 {
+                    side / sum
+                } else {
+                    1.0
+                }
+            })
+            .fold(1.0f32, f32::min);
+        let mut corners = [
+            (rect.min, radii[0]),
+            (Vec2::new(rect.max.x, rect.min.y), radii[1]),
+            (rect.max, radii[2]),
+            (Vec2::new(rect.min.x, rect.max.y), radii[3]),
+        ];
+        if winding == Winding::Negative {
+            corners[1..].reverse();
+        }
+
+        // the length of the control vectors of a quarter circle
+        let k = 4.0 / 3.0 * (PI / 8.0).tan();
+        let direction =
+            |from: usize, to: usize| (corners[to % 4].0 - corners[from % 4].0).normalize_or_zero();
+        let (first, r) = corners[0];
+        self.begin(first + direction(0, 1) * r * scale);
+        for i in 1..=4 {
+            let (corner, r) = corners[i % 4];
+            let r = r * scale;
+            let (d_in, d_out) = (direction(i - 1, i), direction(i, i + 1));
+            self.line_to(corner - d_in * r);
+            if r > 0.0 {
+                self.cubic_bezier_to(
+                    corner - d_in * r * (1.0 - k),
+                    corner + d_out * r * (1.0 - k),
+                    corner + d_out * r,
+                );
+            }
+        }
+        self.close()
     }
 
     /// Adds a transformed sub-path containing a circle.
@@ -186,12 +457,7 @@ where
     /// There must be no sub-path in progress when this method is called.
     /// No sub-path is in progress after the method is called.
     pub fn add_circle(&mut self, center: Vec2, radius: f32, winding: Winding) -> &mut Self {
-        self.builder.add_circle(
-            vec2p(self.transform.transform_point2(center)),
-            radius,
-            winding,
-        );
-        self
+        self.add_ellipse(center, Vec2::splat(radius), 0.0, winding)
     }
 
     /// Adds a transformed sub-path containing an ellipse.
@@ -205,12 +471,232 @@ where
         x_rotation: f32,
         winding: Winding,
     ) -> &mut Self {
-        self.builder.add_ellipse(
-            vec2p(self.transform.transform_point2(center)),
-            vec2v(self.transform.transform_vector2(radii)),
-            lyon::math::Angle::radians(x_rotation),
-            winding,
+        let sweep = match winding {
+            Winding::Positive => TAU,
+            Winding::Negative => -TAU,
+        };
+        self.begin(center + Vec2::from_angle(x_rotation).rotate(Vec2::new(radii.x, 0.0)));
+        for_each_arc_cubic(center, radii, x_rotation, 0.0, sweep, |ctrl1, ctrl2, to| {
+            self.cubic_bezier_to(ctrl1, ctrl2, to);
+        });
+        self.close()
+    }
+
+    /// Adds a transformed closed sub-path through the given points.
+    ///
+    /// There must be no sub-path in progress when this method is called.
+    /// No sub-path is in progress after the method is called.
+    pub fn add_polygon(&mut self, points: &[Vec2]) -> &mut Self {
+        self.add_points(points, true)
+    }
+
+    /// Adds a transformed open sub-path through the given points.
+    ///
+    /// There must be no sub-path in progress when this method is called.
+    /// No sub-path is in progress after the method is called.
+    pub fn add_polyline(&mut self, points: &[Vec2]) -> &mut Self {
+        self.add_points(points, false)
+    }
+
+    fn add_points(&mut self, points: &[Vec2], close: bool) -> &mut Self {
+        let Some((first, rest)) = points.split_first() else {
+            return self;
+        };
+        self.begin(*first);
+        for p in rest {
+            self.line_to(*p);
+        }
+        self.end(close)
+    }
+
+    /// Adds a transformed sub-path containing a regular star with the given number of tips.
+    ///
+    /// The first tip points along the positive y-axis. The tips are at `outer_radius` and
+    /// the corners between them at `inner_radius`, so equal radii give a regular polygon.
+    /// Nothing is added if there are less than 2 tips.
+    ///
+    /// There must be no sub-path in progress when this method is called.
+    /// No sub-path is in progress after the method is called.
+    pub fn add_star(
+        &mut self,
+        center: Vec2,
+        tips: usize,
+        outer_radius: f32,
+        inner_radius: f32,
+        winding: Winding,
+    ) -> &mut Self {
+        if tips < 2 {
+            return self;
+        }
+        let step = match winding {
+            Winding::Positive => PI / tips as f32,
+            Winding::Negative => -PI / tips as f32,
+        };
+        let points: Vec<Vec2> = (0..tips * 2)
+            .map(|i| {
+                let radius = if i % 2 == 0 {
+                    outer_radius
+                } else {
+                    inner_radius
+                };
+                center + Vec2::from_angle(FRAC_PI_2 + step * i as f32) * radius
+            })
+            .collect();
+        self.add_polygon(&points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{flatten_path, PathBuilderImpl};
+    use super::*;
+    use lyon::math::{point, Box2D};
+    use lyon::path::{Event, Path};
+
+    const TOL: f32 = 0.001;
+
+    fn path(draw_commands: impl FnOnce(&mut PBuilder<PathBuilderImpl>)) -> Path {
+        let mut builder = PBuilder::new(Path::builder());
+        draw_commands(&mut builder);
+        builder.build()
+    }
+
+    fn lyon_path(draw_commands: impl FnOnce(&mut NoAttributes<PathBuilderImpl>)) -> Path {
+        let mut builder = Path::builder();
+        draw_commands(&mut builder);
+        builder.build()
+    }
+
+    fn distance_to_polygon(p: Vec2, polygon: &[Vec2]) -> f32 {
+        (0..polygon.len())
+            .map(|i| {
+                let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+                let t = ((p - a).dot(b - a) / (b - a).length_squared()).clamp(0.0, 1.0);
+                if t.is_finite() {
+                    p.distance(a + (b - a) * t)
+                } else {
+                    p.distance(a)
+                }
+            })
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    fn signed_area(polygon: &[Vec2]) -> f32 {
+        (0..polygon.len())
+            .map(|i| polygon[i].perp_dot(polygon[(i + 1) % polygon.len()]))
+            .sum::<f32>()
+            / 2.0
+    }
+
+    /// Checks that the flattened paths have the same single closed contour with the same orientation.
+    fn assert_same_shape(a: &Path, b: &Path) {
+        let (a, b) = (flatten_path(a, TOL), flatten_path(b, TOL));
+        assert_eq!((a.len(), b.len()), (1, 1));
+        let ((a, a_closed), (b, b_closed)) = (&a[0], &b[0]);
+        assert!(*a_closed && *b_closed);
+        for p in a {
+            assert!(distance_to_polygon(*p, b) < 2.0 * TOL);
+        }
+        for p in b {
+            assert!(distance_to_polygon(*p, a) < 2.0 * TOL);
+        }
+        let (area_a, area_b) = (signed_area(a), signed_area(b));
+        assert!((area_a - area_b).abs() < 0.01 * area_b.abs());
+    }
+
+    fn events(path: &Path) -> Vec<Event<Vec2, Vec2>> {
+        let v = |p: lyon::math::Point| Vec2::new(p.x, p.y);
+        path.iter()
+            .map(|e| match e {
+                Event::Begin { at } => Event::Begin { at: v(at) },
+                Event::Line { from, to } => Event::Line {
+                    from: v(from),
+                    to: v(to),
+                },
+                Event::End { last, first, close } => Event::End {
+                    last: v(last),
+                    first: v(first),
+                    close,
+                },
+                _ => panic!("unexpected curve"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn circles_match_lyon() {
+        for winding in [Winding::Positive, Winding::Negative] {
+            assert_same_shape(
+                &path(|b| {
+                    b.translate(Vec2::new(1.0, 0.0))
+                        .add_circle(Vec2::new(1.0, 2.0), 3.0, winding);
+                }),
+                &lyon_path(|b| b.add_circle(point(2.0, 2.0), 3.0, winding)),
+            );
+        }
+    }
+
+    #[test]
+    fn rectangles_match_lyon() {
+        for winding in [Winding::Positive, Winding::Negative] {
+            assert_same_shape(
+                &path(|b| {
+                    b.add_rectangle(Rect::new(-1.0, 0.5, 2.0, 1.5), winding);
+                }),
+                &lyon_path(|b| {
+                    b.add_rectangle(&Box2D::new(point(-1.0, 0.5), point(2.0, 1.5)), winding);
+                }),
+            );
+        }
+    }
+
+    #[test]
+    fn arcs_with_zero_radii_are_lines() {
+        let path = path(|b| {
+            b.begin(Vec2::new(1.0, 0.0))
+                .arc_to(Vec2::ZERO, Vec2::new(2.0, 0.0), PI, 0.0)
+                .end(false);
+        });
+        assert_eq!(
+            events(&path),
+            vec![
+                Event::Begin {
+                    at: Vec2::new(1.0, 0.0)
+                },
+                Event::Line {
+                    from: Vec2::new(1.0, 0.0),
+                    to: Vec2::new(-2.0, 0.0)
+                },
+                Event::End {
+                    last: Vec2::new(-2.0, 0.0),
+                    first: Vec2::new(1.0, 0.0),
+                    close: false
+                },
+            ]
         );
-        self
+    }
+
+    #[test]
+    fn arcs_starting_at_the_center_are_skipped() {
+        let path = path(|b| {
+            b.begin(Vec2::ZERO)
+                .arc_to(Vec2::ZERO, Vec2::ONE, PI, 0.0)
+                .end(false);
+        });
+        assert_eq!(events(&path).len(), 2);
+    }
+
+    #[test]
+    fn stars_need_two_tips() {
+        for tips in [0, 1] {
+            let path = path(|b| {
+                b.add_star(Vec2::ZERO, tips, 2.0, 1.0, Winding::Positive);
+            });
+            assert!(events(&path).is_empty());
+        }
+        let path = path(|b| {
+            b.add_star(Vec2::ZERO, 2, 2.0, 1.0, Winding::Positive);
+        });
+        assert_eq!(events(&path).len(), 5);
     }
 }
@@ -0,0 +1,330 @@
+use super::super::{OrPanic, PMesh, PMeshError};
+use super::{for_each_flattened, PBuilder, PTessellator, PathBufferBuilder};
+use crate::IndexType;
+use bevy::{
+    math::{Vec2, Vec3},
+    prelude::Mesh,
+};
+use lyon::{
+    math::Point,
+    path::{Path, PathSlice},
+    tessellation::{
+        geometry_builder::Positions, BuffersBuilder, FillOptions, FillTessellator, VertexBuffers,
+    },
+};
+use std::ops::Range;
+
+impl<T> PMesh<T>
+where
+    T: IndexType,
+{
+    /// Fills the path built in the closure, extrudes it by `depth` along the z-axis, and appends it to the mesh.
+    ///
+    /// The back cap lies at z=0 and the front cap at z=`depth`. Every contour of the path gets a side wall,
+    /// so holes are extruded as well. The shape is filled using the even-odd rule. The walls of each contour
+    /// are oriented as a whole, so the contours shouldn't intersect themselves or each other.
+    /// All faces point outwards and the uv coordinates are in world units:
+    /// the caps use the x/y coordinates and the walls use the arc length along the contour and the height.
+    ///
+    /// The extruded shape has normals, so the mesh must be empty or have normals as well. To append it
+    /// to a mesh without normals, use `extrude_path` and `extend_with`.
+    ///
+    /// Panics if the mesh has vertices but no normals or if the tessellation fails. See `try_fill_extruded`.
+    pub fn fill_extruded<F>(&mut self, tol: f32, depth: f32, draw_commands: F) -> &mut PMesh<T>
+    where
+        F: FnOnce(&mut PBuilder<PathBufferBuilder<'_>>),
+    {
+        self.try_fill_extruded(tol, depth, draw_commands).or_panic();
+        self
+    }
+
+    /// Fills the path built in the closure, extrudes it by `depth` along the z-axis, and appends it to the mesh.
+    /// Returns `PMeshError::MissingAttribute` if the mesh has vertices but no normals and
+    /// `PMeshError::Tessellation` if lyon can't tessellate the caps.
+    pub fn try_fill_extruded<F>(
+        &mut self,
+        tol: f32,
+        depth: f32,
+        draw_commands: F,
+    ) -> Result<&mut PMesh<T>, PMeshError>
+    where
+        F: FnOnce(&mut PBuilder<PathBufferBuilder<'_>>),
+    {
+        PTessellator::with_thread_local(|tessellator| {
+            tessellator
+                .try_fill_extruded_into(self, tol, depth, draw_commands)
+                .map(|_| ())
+        })?;
+        Ok(self)
+    }
+
+    /// Extrudes a closed lyon path by `depth` along the z-axis. See `fill_extruded`.
+    ///
+    /// Panics if the tessellation fails. See `try_extrude_path`.
+    pub fn extrude_path(path: &Path, tol: f32, depth: f32) -> PMesh<T> {
+        PMesh::try_extrude_path(path, tol, depth).or_panic()
+    }
+
+    /// Extrudes a closed lyon path by `depth` along the z-axis. Returns an error if the tessellation fails.
+    pub fn try_extrude_path(path: &Path, tol: f32, depth: f32) -> Result<PMesh<T>, PMeshError> {
+        let mut mesh = PMesh::new();
+        PTessellator::with_thread_local(|tessellator| {
+            tessellator
+                .try_extrude_into(&mut mesh, path.as_slice(), tol, depth)
+                .map(|_| ())
+        })?;
+        Ok(mesh)
+    }
+}
+
+/// Reusable buffers for extruding paths.
+#[derive(Default)]
+pub(super) struct ExtrudeBuffers {
+    /// The tessellated caps.
+    geometry: VertexBuffers<Point, u32>,
+    /// The flattened points of the current sub-path.
+    points: Vec<(Vec2, f32)>,
+    /// The points of all flattened contours and the range of every contour.
+    contours: Vec<Vec2>,
+    ranges: Vec<Range<usize>>,
+}
+
+/// Extrudes a closed lyon path by `depth` along the z-axis and appends it to the mesh. See `PMesh::fill_extruded`.
+///
+/// The caps and the contours are computed in `buffers`, so their memory can be reused.
+///
+/// The extruded shape always has normals, so the mesh must be empty or already have normals.
+/// Returns an error without changing the mesh if it has vertices but no normals, if its indices or
+/// attribute lengths are invalid, or if the tessellation fails.
+pub(super) fn extrude_into<T>(
+    tessellator: &mut FillTessellator,
+    buffers: &mut ExtrudeBuffers,
+    path: PathSlice,
+    tol: f32,
+    depth: f32,
+    mesh: &mut PMesh<T>,
+) -> Result<(), PMeshError>
+where
+    T: IndexType,
+{
+    mesh.validate_structure()?;
+    if mesh.normals.is_none() && mesh.vertices.len() > 0 {
+        return Err(PMeshError::MissingAttribute(Mesh::ATTRIBUTE_NORMAL.name));
+    }
+
+    let ExtrudeBuffers {
+        geometry,
+        points,
+        contours,
+        ranges,
+    } = buffers;
+    geometry.vertices.clear();
+    geometry.indices.clear();
+    tessellator.tessellate_path(
+        path,
+        &FillOptions::tolerance(tol),
+        &mut BuffersBuilder::new(geometry, Positions),
+    )?;
+
+    let vertices = mesh.vertices.get_vertices_mut();
+    let mut uv = mesh.uv.as_mut();
+    let normals = mesh.normals.get_or_insert_with(Vec::new);
+    let indices = mesh.indices.get_indices_mut();
+
+    // when extruding in the negative direction, every face has to be flipped
+    let flip = depth < 0.0;
+
+    // caps
+    for (z, front) in [(0.0, false), (depth, true)] {
+        let offset = vertices.len();
+        for p in &geometry.vertices {
+            vertices.push([p.x, p.y, z]);
+            if let Some(uv) = uv.as_mut() {
+                uv.push([p.x, p.y]);
+            }
+            normals.push([0.0, 0.0, if front != flip { 1.0 } else { -1.0 }]);
+        }
+        for tri in geometry.indices.chunks_exact(3) {
+            let [a, b, c] = [tri[0], tri[1], tri[2]].map(|i| geometry.vertices[i as usize]);
+            let ccw = (b - a).cross(c - a) > 0.0;
+            let (i1, i2) = if ccw == front { (1, 2) } else { (2, 1) };
+            let (i1, i2) = if flip { (i2, i1) } else { (i1, i2) };
+            indices.push(T::new(offset + tri[0] as usize));
+            indices.push(T::new(offset + tri[i1] as usize));
+            indices.push(T::new(offset + tri[i2] as usize));
+        }
+    }
+
+    // side walls
+    contours.clear();
+    ranges.clear();
+    for_each_flattened(path, tol, points, |points, _| {
+        if points.len() >= 3 {
+            let start = contours.len();
+            contours.extend(points.iter().map(|(p, _)| *p));
+            ranges.push(start..contours.len());
+        }
+    });
+    for range in ranges.iter() {
+        let contour = &contours[range.clone()];
+
+        // The outside has to be on the right side of the edges. Probe the right side of the longest edge
+        // using the even-odd rule, so this also works for holes.
+        let (a, b) = (0..contour.len())
+            .map(|k| (contour[k], contour[(k + 1) % contour.len()]))
+            .max_by(|(a0, b0), (a1, b1)| a0.distance(*b0).total_cmp(&a1.distance(*b1)))
+            .unwrap();
+        let len = a.distance(b);
+        if len <= 0.0 {
+            continue;
+        }
+        let right = Vec2::new(b.y - a.y, a.x - b.x) / len;
+        let probe = (a + b) * 0.5 + right * (len * 1e-3).min(tol * 0.1);
+        let inside = ranges
+            .iter()
+            .filter(|r| contains_point(&contours[(*r).clone()], probe))
+            .count();
+        let reverse = inside % 2 == 1;
+
+        let mut arc_len = 0.0;
+        for k in 0..contour.len() {
+            let mut a = contour[k];
+            let mut b = contour[(k + 1) % contour.len()];
+            let len = a.distance(b);
+            if len <= 0.0 {
+                continue;
+            }
+            let mut u = [arc_len, arc_len + len];
+            arc_len += len;
+            if reverse {
+                std::mem::swap(&mut a, &mut b);
+                u.swap(0, 1);
+            }
+
+            let n = Vec3::new(b.y - a.y, a.x - b.x, 0.0).normalize();
+            let offset = vertices.len();
+            vertices.extend([
+                [a.x, a.y, 0.0],
+                [b.x, b.y, 0.0],
+                [b.x, b.y, depth],
+                [a.x, a.y, depth],
+            ]);
+            if let Some(uv) = uv.as_mut() {
+                uv.extend([[u[0], 0.0], [u[1], 0.0], [u[1], depth], [u[0], depth]]);
+            }
+            normals.extend([n.to_array(); 4]);
+            let quad = if flip {
+                [0, 2, 1, 0, 3, 2]
+            } else {
+                [0, 1, 2, 0, 2, 3]
+            };
+            indices.extend(quad.iter().map(|j| T::new(offset + j)));
+        }
+    }
+
+    let n = vertices.len();
+    mesh.attributes.resize(n);
+    Ok(())
+}
+
+/// Even-odd point-in-polygon test.
+fn contains_point(polygon: &[Vec2], p: Vec2) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let a = polygon[i];
+        let b = polygon[j];
+        if (a.y > p.y) != (b.y > p.y) && p.x < (b.x - a.x) * (p.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A square with a square hole. The hole is drawn in the same direction as the outline.
+    fn frame(depth: f32) -> PMesh<u32> {
+        let mut mesh = PMesh::new();
+        mesh.fill_extruded(0.01, depth, |builder| {
+            for size in [2.0, 1.0] {
+                builder
+                    .begin(Vec2::new(-size, -size))
+                    .line_to(Vec2::new(size, -size))
+                    .line_to(Vec2::new(size, size))
+                    .line_to(Vec2::new(-size, size))
+                    .close();
+            }
+        });
+        mesh
+    }
+
+    fn inside_frame(p: Vec3, depth: f32) -> bool {
+        let in_square = |size: f32| p.x.abs() < size && p.y.abs() < size;
+        in_square(2.0) && !in_square(1.0) && p.z > depth.min(0.0) && p.z < depth.max(0.0)
+    }
+
+    #[test]
+    fn faces_point_outwards() {
+        for depth in [1.0, -1.0] {
+            let mesh = frame(depth);
+            assert!(mesh.validate().is_ok());
+            let normals = mesh.normals.as_ref().unwrap();
+            for [a, b, c] in mesh.iter_faces() {
+                let (pa, pb, pc) = (mesh.vec3_at(a), mesh.vec3_at(b), mesh.vec3_at(c));
+                let n = Vec3::from(normals[a]);
+                assert!((n.length() - 1.0).abs() < 1e-5);
+                // the winding agrees with the normal
+                assert!((pb - pa).cross(pc - pa).dot(n) > 0.0);
+                // a step along the normal leaves the solid and a step against it enters it
+                let center = (pa + pb + pc) / 3.0;
+                assert!(!inside_frame(center + n * 0.01, depth));
+                assert!(inside_frame(center - n * 0.01, depth));
+            }
+        }
+    }
+
+    #[test]
+    fn holes_get_walls_facing_the_hole() {
+        let mesh = frame(1.0);
+        let normals = mesh.normals.as_ref().unwrap();
+        let inner_walls = (0..mesh.vertices.len())
+            .filter(|i| {
+                let p = mesh.vec3_at(*i);
+                let n = Vec3::from(normals[*i]);
+                n.z == 0.0 && p.x.abs() <= 1.0 && p.y.abs() <= 1.0
+            })
+            .inspect(|i| {
+                let n = Vec3::from(normals[*i]);
+                assert!(mesh.vec3_at(*i).dot(n) < 0.0);
+            })
+            .count();
+        // four quads
+        assert_eq!(inner_walls, 16);
+    }
+
+    #[test]
+    fn meshes_without_normals_are_an_error() {
+        let mut mesh = PMesh::<u32>::build(
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            vec![0, 1, 2],
+            None,
+        );
+        let result = mesh.try_fill_extruded(0.01, 1.0, |builder| {
+            builder
+                .begin(Vec2::ZERO)
+                .line_to(Vec2::X)
+                .line_to(Vec2::Y)
+                .close();
+        });
+        assert_eq!(
+            result.err(),
+            Some(PMeshError::MissingAttribute(Mesh::ATTRIBUTE_NORMAL.name))
+        );
+        assert_eq!(mesh.vertices.len(), 3);
+        assert_eq!(mesh.normals, None);
+    }
+}
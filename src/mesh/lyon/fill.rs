@@ -1,25 +1,113 @@
-use super::super::PMesh;
+use super::super::{OrPanic, PMesh, PMeshError};
 use super::builder::PBuilder;
+use super::{PGeometryBuilder, PTessellator, UvMapping};
 use crate::IndexType;
-pub use lyon::tessellation::FillBuilder;
-use lyon::{
-    math::Point,
-    tessellation::{
-        geometry_builder::{MaxIndex, Positions},
-        BuffersBuilder, FillOptions, FillTessellator, VertexBuffers, VertexId,
-    },
-};
+use lyon::tessellation::{geometry_builder::MaxIndex, FillOptions, FillTessellator, VertexId};
+pub use lyon::tessellation::{FillBuilder, FillRule, Orientation};
 use std::ops::Add;
 
+/// Options for filling a path with `PFill` or `PMesh::fill_with`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PFillOptions {
+    /// The maximum distance between the curves and their approximation.
+    pub tolerance: f32,
+
+    /// Decides which parts of overlapping or self-intersecting sub-paths are inside.
+    /// With `EvenOdd`, overlapping shapes produce holes. With `NonZero`, shapes with the same winding are united.
+    pub fill_rule: FillRule,
+
+    /// Whether the tessellator sweeps the geometry vertically or horizontally.
+    pub sweep_orientation: Orientation,
+
+    /// Whether intersecting edges are handled. Only disable this if the path has no self-intersections,
+    /// otherwise the tessellation may fail or produce incorrect results.
+    pub handle_intersections: bool,
+
+    /// How the uv coordinates are computed from the positions. `UvMapping::AlongPath` is the same as `Raw`.
+    pub uv: UvMapping,
+}
+
+impl Default for PFillOptions {
+    fn default() -> Self {
+        PFillOptions {
+            tolerance: FillOptions::DEFAULT_TOLERANCE,
+            fill_rule: FillOptions::DEFAULT_FILL_RULE,
+            sweep_orientation: FillOptions::DEFAULT_SWEEP_ORIENTATION,
+            handle_intersections: true,
+            uv: UvMapping::Raw,
+        }
+    }
+}
+
+impl PFillOptions {
+    /// Creates fill options with the given tolerance and the even-odd fill rule.
+    pub fn new(tol: f32) -> Self {
+        PFillOptions {
+            tolerance: tol,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the fill rule.
+    pub fn with_fill_rule(mut self, fill_rule: FillRule) -> Self {
+        self.fill_rule = fill_rule;
+        self
+    }
+
+    /// Sets the sweep orientation.
+    pub fn with_sweep_orientation(mut self, orientation: Orientation) -> Self {
+        self.sweep_orientation = orientation;
+        self
+    }
+
+    /// Sets whether intersecting edges are handled.
+    pub fn with_intersections(mut self, handle_intersections: bool) -> Self {
+        self.handle_intersections = handle_intersections;
+        self
+    }
+
+    /// Sets how the uv coordinates are computed.
+    pub fn with_uv(mut self, mapping: UvMapping) -> Self {
+        self.uv = mapping;
+        self
+    }
+
+    fn to_lyon(self) -> FillOptions {
+        FillOptions::tolerance(self.tolerance)
+            .with_fill_rule(self.fill_rule)
+            .with_sweep_orientation(self.sweep_orientation)
+            .with_intersections(self.handle_intersections)
+    }
+}
+
+/// Fills the path built in the closure and appends it to the mesh.
+pub(super) fn fill_into<T, F>(
+    tessellator: &mut FillTessellator,
+    options: &PFillOptions,
+    mesh: &mut PMesh<T>,
+    draw_commands: F,
+) -> Result<(), PMeshError>
+where
+    T: Add + IndexType + From<VertexId> + MaxIndex,
+    F: FnOnce(&mut PBuilder<FillBuilder>),
+{
+    let lyon_options = options.to_lyon();
+    let mut output = PGeometryBuilder::new(mesh).with_uv_mapping(options.uv);
+    let builder = tessellator.builder(&lyon_options, &mut output);
+    let mut my_builder = PBuilder::new(builder);
+    draw_commands(&mut my_builder);
+    my_builder.build()?;
+    Ok(())
+}
+
 /// This structure wraps a `lyon::tesselation::FillTessellator` and adds functionality to apply transformations to the path being built.
 pub struct PFill<T>
 where
     T: IndexType,
 {
-    // TODO: reuse the Tesselator!
     tessellator: FillTessellator,
-    options: FillOptions,
-    geometry: VertexBuffers<Point, T>,
+    options: PFillOptions,
+    mesh: PMesh<T>,
 }
 
 impl<T> PFill<T>
@@ -28,35 +116,47 @@ where
 {
     /// Creates a new fill tessellator with the given tolerance.
     pub fn new(tol: f32) -> Self {
-        // Will contain the result of the tessellation.
-        let geometry: VertexBuffers<Point, T> = VertexBuffers::new();
-        let tessellator = FillTessellator::new();
-        let options = FillOptions::tolerance(tol);
+        PFill::with_options(PFillOptions::new(tol))
+    }
 
+    /// Creates a new fill tessellator with the given options.
+    pub fn with_options(options: PFillOptions) -> Self {
         PFill::<T> {
-            tessellator,
+            tessellator: FillTessellator::new(),
             options,
-            geometry,
+            // Will contain the result of the tessellation.
+            mesh: PMesh::new(),
         }
     }
 
     /// Draws the path using the given closure.
+    ///
+    /// Panics if the tessellation fails. See `try_draw`.
     pub fn draw<F>(&mut self, draw_commands: F) -> &mut Self
     where
         F: FnOnce(&mut PBuilder<FillBuilder>),
     {
-        // TODO: Improve performance: Can I use the BuffersBuilder to directly write to the mesh?
-        let geometry_builder = &mut BuffersBuilder::new(&mut self.geometry, Positions);
-        let builder = self.tessellator.builder(&self.options, geometry_builder);
-        let mut my_builder = PBuilder::new(builder);
-        draw_commands(&mut my_builder);
-        my_builder.build().unwrap();
+        self.try_draw(draw_commands).or_panic();
         self
     }
 
+    /// Draws the path using the given closure. Returns an error if the tessellation fails.
+    pub fn try_draw<F>(&mut self, draw_commands: F) -> Result<&mut Self, PMeshError>
+    where
+        F: FnOnce(&mut PBuilder<FillBuilder>),
+    {
+        fill_into(
+            &mut self.tessellator,
+            &self.options,
+            &mut self.mesh,
+            draw_commands,
+        )?;
+        Ok(self)
+    }
+
     /// Builds a PMesh object, consuming the tessellator.
     pub fn build(self) -> PMesh<T> {
-        PMesh::import_geometry(&self.geometry, false)
+        self.mesh
     }
 }
 
@@ -69,9 +169,90 @@ where
     where
         F: FnOnce(&mut PBuilder<FillBuilder>),
     {
-        let mut tessellator = PFill::<T>::new(tol);
-        tessellator.draw(draw_commands);
-        self.extend(&tessellator.build());
+        self.fill_with(&PFillOptions::new(tol), draw_commands)
+    }
+
+    /// Fills the path built in the closure and appends it to the mesh. Returns `PMeshError::Tessellation`
+    /// if lyon can't tessellate the path or the vertices don't fit into the index type.
+    pub fn try_fill<F>(&mut self, tol: f32, draw_commands: F) -> Result<&mut PMesh<T>, PMeshError>
+    where
+        F: FnOnce(&mut PBuilder<FillBuilder>),
+    {
+        self.try_fill_with(&PFillOptions::new(tol), draw_commands)
+    }
+
+    /// Fills the path built in the closure with the given options and appends it to the mesh.
+    ///
+    /// Panics if the tessellation fails. See `try_fill_with`.
+    pub fn fill_with<F>(&mut self, options: &PFillOptions, draw_commands: F) -> &mut PMesh<T>
+    where
+        F: FnOnce(&mut PBuilder<FillBuilder>),
+    {
+        self.try_fill_with(options, draw_commands).or_panic();
         self
     }
+
+    /// Fills the path built in the closure with the given options and appends it to the mesh.
+    /// Returns `PMeshError::Tessellation` if lyon can't tessellate the path or the vertices don't fit into
+    /// the index type.
+    pub fn try_fill_with<F>(
+        &mut self,
+        options: &PFillOptions,
+        draw_commands: F,
+    ) -> Result<&mut PMesh<T>, PMeshError>
+    where
+        F: FnOnce(&mut PBuilder<FillBuilder>),
+    {
+        PTessellator::with_thread_local(|tessellator| {
+            tessellator
+                .try_fill_into(self, options, draw_commands)
+                .map(|_| ())
+        })?;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::{Vec2, Vec3};
+
+    /// Fills two overlapping squares of size 2 drawn in the same direction as a single path.
+    fn overlapping_squares(fill_rule: FillRule) -> PMesh<u32> {
+        let mut mesh = PMesh::<u32>::new();
+        mesh.fill_with(
+            &PFillOptions::new(0.01).with_fill_rule(fill_rule),
+            |builder| {
+                for offset in [Vec2::ZERO, Vec2::ONE] {
+                    builder
+                        .begin(offset)
+                        .line_to(offset + Vec2::new(2.0, 0.0))
+                        .line_to(offset + Vec2::new(2.0, 2.0))
+                        .line_to(offset + Vec2::new(0.0, 2.0))
+                        .close();
+                }
+            },
+        );
+        mesh
+    }
+
+    fn area(mesh: &PMesh<u32>) -> f32 {
+        mesh.iter_faces()
+            .map(|[a, b, c]| {
+                let (a, b, c): (Vec3, Vec3, Vec3) =
+                    (mesh.vec3_at(a), mesh.vec3_at(b), mesh.vec3_at(c));
+                (b - a).cross(c - a).length() * 0.5
+            })
+            .sum()
+    }
+
+    #[test]
+    fn fill_rules_differ_for_overlapping_paths() {
+        let non_zero = overlapping_squares(FillRule::NonZero);
+        let even_odd = overlapping_squares(FillRule::EvenOdd);
+        assert_ne!(non_zero.indices.len(), even_odd.indices.len());
+        // the 1x1 overlap has a winding number of 2, so only NonZero fills it
+        assert!((area(&non_zero) - 7.0).abs() < 1e-4);
+        assert!((area(&even_odd) - 6.0).abs() < 1e-4);
+    }
 }
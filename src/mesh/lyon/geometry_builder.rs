@@ -0,0 +1,383 @@
+use super::super::PMesh;
+use crate::IndexType;
+use bevy::{
+    math::{Rect, Vec2},
+    mesh::{MeshVertexAttribute, VertexAttributeValues},
+    render::render_resource::VertexFormat,
+};
+use lyon::path::EndpointId;
+use lyon::tessellation::{
+    FillGeometryBuilder, FillVertex, GeometryBuilder, GeometryBuilderError, Side,
+    StrokeGeometryBuilder, StrokeVertex, VertexId, VertexSource,
+};
+
+/// How far along the stroked path the vertex is, i.e., the length of the path up to the vertex
+/// including all previous sub-paths.
+pub const ATTRIBUTE_STROKE_ADVANCEMENT: MeshVertexAttribute = MeshVertexAttribute::new(
+    "Vertex_StrokeAdvancement",
+    0x6c79_6f6e_0001,
+    VertexFormat::Float32,
+);
+
+/// The side of the stroke the vertex is on: 1 on the left and -1 on the right of the path.
+pub const ATTRIBUTE_STROKE_SIDE: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_StrokeSide", 0x6c79_6f6e_0002, VertexFormat::Float32);
+
+/// The direction in which the vertex was moved away from the path. Moving the vertex by the normal
+/// widens the stroke by one unit on each side.
+pub const ATTRIBUTE_STROKE_NORMAL: MeshVertexAttribute = MeshVertexAttribute::new(
+    "Vertex_StrokeNormal",
+    0x6c79_6f6e_0003,
+    VertexFormat::Float32x2,
+);
+
+const STROKE_ATTRIBUTES: [MeshVertexAttribute; 3] = [
+    ATTRIBUTE_STROKE_ADVANCEMENT,
+    ATTRIBUTE_STROKE_SIDE,
+    ATTRIBUTE_STROKE_NORMAL,
+];
+
+/// How the uv coordinates of filled and stroked shapes are computed from their positions.
+///
+/// Like the positions, `v` grows along the y-axis. Use `World` with a negative y-scale to flip it.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum UvMapping {
+    /// The uv coordinates of strokes run along the path: `u` is the advancement along the path and `v` is
+    /// 0 on the right and 1 on the left side. Fills use `Raw` instead.
+    AlongPath,
+
+    /// The uv coordinates are the positions in world units.
+    #[default]
+    Raw,
+
+    /// The bounding box of the shape is mapped to [0, 1].
+    Bounds,
+
+    /// The given rectangle is mapped to [0, 1], so the texture lines up across separately generated shapes.
+    Rect(Rect),
+
+    /// The uv coordinates are the positions multiplied by `scale` plus `offset`.
+    World {
+        /// The number of uv units per world unit.
+        scale: Vec2,
+
+        /// The uv coordinates at the origin.
+        offset: Vec2,
+    },
+}
+
+impl UvMapping {
+    /// Returns the scale and offset that map the rectangle to [0, 1]. Collapsed axes are mapped to 0.
+    fn fit(rect: Rect) -> (Vec2, Vec2) {
+        let size = rect.size();
+        let scale = Vec2::new(
+            if size.x > 0.0 { 1.0 / size.x } else { 0.0 },
+            if size.y > 0.0 { 1.0 / size.y } else { 0.0 },
+        );
+        (scale, -rect.min * scale)
+    }
+}
+
+/// A lyon geometry builder that writes the tessellated vertices and triangles directly into a `PMesh`.
+///
+/// The uv coordinates are computed using the `UvMapping` when the geometry is finished. By default, filled
+/// vertices get their positions and stroked vertices get uv coordinates that run along the stroke. Strokes additionally write `ATTRIBUTE_STROKE_ADVANCEMENT`,
+/// `ATTRIBUTE_STROKE_SIDE`, and `ATTRIBUTE_STROKE_NORMAL`; other vertices get zeros for them.
+pub struct PGeometryBuilder<'a, T>
+where
+    T: IndexType,
+{
+    mesh: &'a mut PMesh<T>,
+    first_vertex: usize,
+    first_index: usize,
+    /// Whether the stroke attributes were added to the mesh.
+    stroke: bool,
+    /// Which of the stroke attributes and whether the uv coordinates were added by this builder,
+    /// so aborting can remove them.
+    added_stroke_attributes: [bool; 3],
+    added_uv: bool,
+    uv_mapping: UvMapping,
+    /// The first endpoint of every sub-path and its distance from the start of the original path.
+    advancement_offsets: &'a [(EndpointId, f32)],
+}
+
+impl<'a, T> PGeometryBuilder<'a, T>
+where
+    T: IndexType,
+{
+    /// Creates a geometry builder that appends to the given mesh.
+    pub fn new(mesh: &'a mut PMesh<T>) -> Self {
+        let n = mesh.vertices.len();
+        PGeometryBuilder {
+            mesh,
+            first_vertex: n,
+            first_index: 0,
+            stroke: false,
+            added_stroke_attributes: [false; 3],
+            added_uv: false,
+            uv_mapping: UvMapping::AlongPath,
+            advancement_offsets: &[],
+        }
+    }
+
+    /// Adds the given distances to the advancement of the sub-paths starting at the given endpoints,
+    /// so the advancement continues across the dashes of a dashed path. Must be sorted by the endpoints.
+    pub(super) fn with_advancement_offsets(mut self, offsets: &'a [(EndpointId, f32)]) -> Self {
+        self.advancement_offsets = offsets;
+        self
+    }
+
+    /// Sets how the uv coordinates are computed.
+    pub fn with_uv_mapping(mut self, mapping: UvMapping) -> Self {
+        self.uv_mapping = mapping;
+        self
+    }
+
+    fn advancement_offset(&self, source: VertexSource) -> f32 {
+        let id = match source {
+            VertexSource::Endpoint { id } => id,
+            VertexSource::Edge { from, .. } => from,
+        };
+        let i = self
+            .advancement_offsets
+            .partition_point(|(start, _)| start.0 <= id.0);
+        i.checked_sub(1)
+            .map_or(0.0, |i| self.advancement_offsets[i].1)
+    }
+
+    fn push_vertex(
+        &mut self,
+        position: [f32; 2],
+        uv: [f32; 2],
+    ) -> Result<VertexId, GeometryBuilderError> {
+        let id = self.mesh.vertices.len() - self.first_vertex;
+        if self.mesh.vertices.len() >= <T as IndexType>::max().index() || id >= u32::MAX as usize {
+            return Err(GeometryBuilderError::TooManyVertices);
+        }
+        self.mesh
+            .vertices
+            .get_vertices_mut()
+            .push([position[0], position[1], 0.0]);
+        if let Some(uvs) = self.mesh.uv.as_mut() {
+            uvs.push(uv);
+        }
+        Ok(VertexId(id as u32))
+    }
+
+    /// Appends the stroke attributes of the last vertex to the attributes of the mesh.
+    /// If the mesh doesn't have them yet, they are added and the previous vertices get zeros.
+    fn push_stroke_attributes(&mut self, advancement: f32, side: f32, normal: [f32; 2]) {
+        let n = self.mesh.vertices.len() - 1;
+        let attributes = &mut self.mesh.attributes;
+        if !self.stroke {
+            self.stroke = true;
+            self.added_stroke_attributes = STROKE_ATTRIBUTES.map(|a| !attributes.contains(a));
+            if !attributes.contains(ATTRIBUTE_STROKE_ADVANCEMENT) {
+                attributes.insert(ATTRIBUTE_STROKE_ADVANCEMENT, vec![0.0f32; n].into());
+            }
+            if !attributes.contains(ATTRIBUTE_STROKE_SIDE) {
+                attributes.insert(ATTRIBUTE_STROKE_SIDE, vec![0.0f32; n].into());
+            }
+            if !attributes.contains(ATTRIBUTE_STROKE_NORMAL) {
+                attributes.insert(ATTRIBUTE_STROKE_NORMAL, vec![[0.0f32; 2]; n].into());
+            }
+        }
+        if let Some(VertexAttributeValues::Float32(v)) =
+            attributes.get_mut(ATTRIBUTE_STROKE_ADVANCEMENT)
+        {
+            v.push(advancement);
+        }
+        if let Some(VertexAttributeValues::Float32(v)) = attributes.get_mut(ATTRIBUTE_STROKE_SIDE) {
+            v.push(side);
+        }
+        if let Some(VertexAttributeValues::Float32x2(v)) =
+            attributes.get_mut(ATTRIBUTE_STROKE_NORMAL)
+        {
+            v.push(normal);
+        }
+    }
+
+    /// Replaces the uv coordinates of the new vertices according to the uv mapping.
+    fn map_uv(&mut self) {
+        let vertices = &self.mesh.vertices.get_vertices()[self.first_vertex..];
+        let Some(uv) = self.mesh.uv.as_mut() else {
+            return;
+        };
+        let (scale, offset) = match self.uv_mapping {
+            // fills already use their positions and strokes the advancement
+            UvMapping::AlongPath => return,
+            UvMapping::Raw => (Vec2::ONE, Vec2::ZERO),
+            UvMapping::Bounds => {
+                let (min, max) = vertices
+                    .iter()
+                    .map(|v| Vec2::new(v[0], v[1]))
+                    .fold((Vec2::INFINITY, Vec2::NEG_INFINITY), |(min, max), p| {
+                        (min.min(p), max.max(p))
+                    });
+                UvMapping::fit(Rect { min, max })
+            }
+            UvMapping::Rect(rect) => UvMapping::fit(rect),
+            UvMapping::World { scale, offset } => (scale, offset),
+        };
+        for (uv, v) in uv[self.first_vertex..].iter_mut().zip(vertices) {
+            *uv = (Vec2::new(v[0], v[1]) * scale + offset).to_array();
+        }
+    }
+
+    /// Makes all attributes as long as the vertices.
+    fn finish(&mut self) {
+        let n = self.mesh.vertices.len();
+        self.mesh.attributes.resize(n);
+        if let Some(normals) = self.mesh.normals.as_mut() {
+            normals.resize(n, [0.0, 0.0, 1.0]);
+        }
+    }
+}
+
+impl<T> GeometryBuilder for PGeometryBuilder<'_, T>
+where
+    T: IndexType,
+{
+    fn begin_geometry(&mut self) {
+        let n = self.mesh.vertices.len();
+        self.first_vertex = n;
+        self.first_index = self.mesh.indices.len();
+        self.added_uv = self.mesh.uv.is_none() && n == 0;
+        if self.added_uv {
+            self.mesh.uv = Some(Vec::new());
+        }
+    }
+
+    fn end_geometry(&mut self) {
+        self.map_uv();
+        self.finish();
+    }
+
+    fn add_triangle(&mut self, a: VertexId, b: VertexId, c: VertexId) {
+        let index = |v: VertexId| T::new(self.first_vertex + v.0 as usize);
+        let (a, b, c) = (index(a), index(b), index(c));
+        self.mesh.indices.push(a, b, c);
+    }
+
+    fn abort_geometry(&mut self) {
+        // removes everything that was added since `begin_geometry`
+        let n = self.first_vertex;
+        self.mesh.vertices.get_vertices_mut().truncate(n);
+        self.mesh
+            .indices
+            .get_indices_mut()
+            .truncate(self.first_index);
+        if self.added_uv {
+            self.mesh.uv = None;
+        } else if let Some(uvs) = self.mesh.uv.as_mut() {
+            uvs.truncate(n);
+        }
+        if let Some(normals) = self.mesh.normals.as_mut() {
+            normals.truncate(n);
+        }
+        for (attribute, added) in STROKE_ATTRIBUTES
+            .into_iter()
+            .zip(self.added_stroke_attributes)
+        {
+            if added {
+                self.mesh.attributes.remove(attribute);
+            }
+        }
+        self.mesh.attributes.resize(n);
+        self.stroke = false;
+        self.added_stroke_attributes = [false; 3];
+    }
+}
+
+impl<T> FillGeometryBuilder for PGeometryBuilder<'_, T>
+where
+    T: IndexType,
+{
+    fn add_fill_vertex(&mut self, vertex: FillVertex) -> Result<VertexId, GeometryBuilderError> {
+        let p = vertex.position();
+        self.push_vertex([p.x, p.y], [p.x, p.y])
+    }
+}
+
+impl<T> StrokeGeometryBuilder for PGeometryBuilder<'_, T>
+where
+    T: IndexType,
+{
+    fn add_stroke_vertex(
+        &mut self,
+        vertex: StrokeVertex,
+    ) -> Result<VertexId, GeometryBuilderError> {
+        let p = vertex.position();
+        let advancement = vertex.advancement() + self.advancement_offset(vertex.source());
+        // lyon's positive side is on the left in y-up coordinates
+        let left = vertex.side() == Side::Positive;
+        let normal = vertex.normal();
+        let id = self.push_vertex([p.x, p.y], [advancement, if left { 1.0 } else { 0.0 }])?;
+        self.push_stroke_attributes(
+            advancement,
+            if left { 1.0 } else { -1.0 },
+            [normal.x, normal.y],
+        );
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::Vec2;
+
+    /// A mesh that is a few vertices short of the maximum of u16 indices.
+    fn almost_full(normals: bool) -> PMesh<u16> {
+        let n = u16::MAX as usize - 20;
+        let mut vertices = vec![[0.0; 3]; n];
+        vertices[1] = [1.0, 0.0, 0.0];
+        vertices[2] = [0.0, 1.0, 0.0];
+        let mut mesh = PMesh::build(vertices, vec![0, 1, 2], Some(vec![[0.0; 2]; n]));
+        if normals {
+            mesh.normals = Some(vec![[0.0, 0.0, 1.0]; n]);
+        }
+        mesh.set_colors(vec![[1.0; 4]; n]);
+        mesh
+    }
+
+    fn zigzag<B: lyon::path::traits::PathBuilder>(
+        builder: &mut super::super::PBuilder<B>,
+    ) -> &mut super::super::PBuilder<B> {
+        builder.begin(Vec2::ZERO);
+        for i in 1..100 {
+            builder.line_to(Vec2::new(i as f32, (i % 2) as f32));
+        }
+        builder.end(false)
+    }
+
+    #[test]
+    fn aborting_restores_the_mesh() {
+        for normals in [false, true] {
+            let mut mesh = almost_full(normals);
+            let expected = mesh.clone();
+            assert!(mesh
+                .try_stroke(0.1, 0.01, |b| {
+                    zigzag(b);
+                })
+                .is_err());
+            assert!(mesh.validate().is_ok());
+            assert_eq!(mesh.vertices.len(), expected.vertices.len());
+            assert_eq!(mesh.indices.get_indices(), expected.indices.get_indices());
+            assert_eq!(mesh.uv, expected.uv);
+            assert_eq!(mesh.normals, expected.normals);
+            assert!(!mesh.attributes.contains(ATTRIBUTE_STROKE_ADVANCEMENT));
+            assert!(!mesh.attributes.contains(ATTRIBUTE_STROKE_SIDE));
+            assert!(!mesh.attributes.contains(ATTRIBUTE_STROKE_NORMAL));
+            assert_eq!(mesh.get_colors(), expected.get_colors());
+
+            assert!(mesh
+                .try_fill(0.01, |b| {
+                    zigzag(b);
+                })
+                .is_err());
+            assert!(mesh.validate().is_ok());
+            assert_eq!(mesh.vertices.len(), expected.vertices.len());
+        }
+    }
+}
@@ -0,0 +1,386 @@
+use super::super::{profile::profile_samples, MissingAttributes, OrPanic, PMesh, PMeshError};
+use super::{flatten_path, PBuilder, PTessellator, PathBuilderImpl};
+use crate::IndexType;
+use bevy::math::{Affine3A, Quat, Vec3};
+use lyon::{
+    math::{point, Point},
+    path::Path,
+    tessellation::{geometry_builder::Positions, BuffersBuilder, FillOptions, VertexBuffers},
+};
+use std::f32::consts::PI;
+
+/// Options for revolving a profile with `PMesh::lathe_with`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PLatheOptions {
+    /// The maximum distance between the curves and their approximation.
+    pub tolerance: f32,
+
+    /// The number of steps around the axis.
+    pub segments: usize,
+
+    /// The angle of the revolution in radians. Negative angles revolve in the opposite direction.
+    pub angle: f32,
+
+    /// Whether both ends of a partial revolution are closed.
+    pub caps: bool,
+
+    /// The profile is drawn in the xy-plane of this frame and revolved around its y-axis.
+    pub frame: Affine3A,
+}
+
+impl Default for PLatheOptions {
+    fn default() -> Self {
+        PLatheOptions {
+            tolerance: FillOptions::DEFAULT_TOLERANCE,
+            segments: 32,
+            angle: 2.0 * PI,
+            caps: true,
+            frame: Affine3A::IDENTITY,
+        }
+    }
+}
+
+impl PLatheOptions {
+    /// Creates options for a full revolution around the y-axis with the given tolerance and number of segments.
+    pub fn new(tol: f32, segments: usize) -> Self {
+        PLatheOptions {
+            tolerance: tol,
+            segments,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the angle of the revolution.
+    pub fn with_angle(mut self, angle: f32) -> Self {
+        self.angle = angle;
+        self
+    }
+
+    /// Sets whether both ends of a partial revolution are closed.
+    pub fn with_caps(mut self, caps: bool) -> Self {
+        self.caps = caps;
+        self
+    }
+
+    /// Sets the frame of the profile.
+    pub fn with_frame(mut self, frame: Affine3A) -> Self {
+        self.frame = frame;
+        self
+    }
+
+    /// Revolves around the axis through `origin` in the given direction. The y-axis of the profile is
+    /// rotated onto the axis along the shortest arc. A zero direction keeps the y-axis.
+    pub fn with_axis(self, origin: Vec3, direction: Vec3) -> Self {
+        let rotation = direction
+            .try_normalize()
+            .map_or(Quat::IDENTITY, |d| Quat::from_rotation_arc(Vec3::Y, d));
+        self.with_frame(Affine3A::from_rotation_translation(rotation, origin))
+    }
+}
+
+impl<T> PMesh<T>
+where
+    T: IndexType,
+{
+    /// Revolves the path built in the closure around the y-axis and appends it to the mesh.
+    ///
+    /// The profile is drawn in the xy-plane and should stay at x >= 0. The side to the right of the
+    /// drawing direction becomes the front, i.e., profiles drawn upwards or counter-clockwise face outwards.
+    /// `angle` is the angle of the revolution in radians and `segments` the number of steps around the axis.
+    /// If the revolution is partial and `caps` is set, both ends are closed.
+    /// The uv coordinates go along the angle (u) and the arc length of the profile (v), both normalized to [0, 1].
+    ///
+    /// Use `lathe_with` to revolve around another axis.
+    pub fn lathe<F>(
+        &mut self,
+        tol: f32,
+        segments: usize,
+        angle: f32,
+        caps: bool,
+        draw_commands: F,
+    ) -> &mut PMesh<T>
+    where
+        F: FnOnce(&mut PBuilder<PathBuilderImpl>),
+    {
+        let options = PLatheOptions::new(tol, segments)
+            .with_angle(angle)
+            .with_caps(caps);
+        self.lathe_with(&options, draw_commands)
+    }
+
+    /// Revolves the path built in the closure around the y-axis and appends it to the mesh. See `try_lathe_with`.
+    pub fn try_lathe<F>(
+        &mut self,
+        tol: f32,
+        segments: usize,
+        angle: f32,
+        caps: bool,
+        draw_commands: F,
+    ) -> Result<&mut PMesh<T>, PMeshError>
+    where
+        F: FnOnce(&mut PBuilder<PathBuilderImpl>),
+    {
+        let options = PLatheOptions::new(tol, segments)
+            .with_angle(angle)
+            .with_caps(caps);
+        self.try_lathe_with(&options, draw_commands)
+    }
+
+    /// Revolves the path built in the closure with the given options and appends it to the mesh. See `lathe`.
+    ///
+    /// Panics if the tessellation of the caps fails. See `try_lathe_with`.
+    pub fn lathe_with<F>(&mut self, options: &PLatheOptions, draw_commands: F) -> &mut PMesh<T>
+    where
+        F: FnOnce(&mut PBuilder<PathBuilderImpl>),
+    {
+        self.try_lathe_with(options, draw_commands).or_panic();
+        self
+    }
+
+    /// Revolves the path built in the closure with the given options and appends it to the mesh.
+    /// Returns `PMeshError::Tessellation` if lyon can't tessellate the caps, and the errors of `extend_with`
+    /// if the indices or attributes of the mesh are invalid.
+    pub fn try_lathe_with<F>(
+        &mut self,
+        options: &PLatheOptions,
+        draw_commands: F,
+    ) -> Result<&mut PMesh<T>, PMeshError>
+    where
+        F: FnOnce(&mut PBuilder<PathBuilderImpl>),
+    {
+        let mut builder = PBuilder::new(Path::builder());
+        draw_commands(&mut builder);
+        let path: Path = builder.build();
+        let mesh = PMesh::try_lathe_path(&path, options)?;
+        self.extend_with(&mesh, MissingAttributes::Compute)
+    }
+
+    /// Revolves a lyon path with the given options. See `lathe`.
+    ///
+    /// Panics if the tessellation of the caps fails. See `try_lathe_path`.
+    pub fn lathe_path(path: &Path, options: &PLatheOptions) -> PMesh<T> {
+        PMesh::try_lathe_path(path, options).or_panic()
+    }
+
+    /// Revolves a lyon path with the given options. Returns an error if the tessellation of the caps fails.
+    pub fn try_lathe_path(path: &Path, options: &PLatheOptions) -> Result<PMesh<T>, PMeshError> {
+        let PLatheOptions {
+            tolerance: tol,
+            segments,
+            angle,
+            caps,
+            frame,
+        } = *options;
+        let segments = segments.max(1);
+        let mut vertices: Vec<[f32; 3]> = Vec::new();
+        let mut uv: Vec<[f32; 2]> = Vec::new();
+        let mut normals: Vec<[f32; 3]> = Vec::new();
+        let mut indices: Vec<T> = Vec::new();
+
+        // revolving in the negative direction mirrors the surface
+        let flip = angle < 0.0;
+        let contours = flatten_path(path, tol);
+
+        for (points, closed) in &contours {
+            let samples = profile_samples(points, *closed);
+            let total = samples.last().map_or(0.0, |s| s.arc_len);
+            let base = vertices.len();
+            for sample in &samples {
+                for j in 0..=segments {
+                    let u = j as f32 / segments as f32;
+                    let rot = Quat::from_rotation_y(angle * u);
+                    vertices.push((rot * sample.position.extend(0.0)).to_array());
+                    normals.push((rot * sample.normal.extend(0.0)).to_array());
+                    uv.push([
+                        u,
+                        if total > 0.0 {
+                            sample.arc_len / total
+                        } else {
+                            0.0
+                        },
+                    ]);
+                }
+            }
+
+            let stride = segments + 1;
+            for i in 0..samples.len().saturating_sub(1) {
+                let (s0, s1) = (samples[i], samples[i + 1]);
+                if s0.position.distance(s1.position) <= f32::EPSILON {
+                    continue;
+                }
+                for j in 0..segments {
+                    let a = base + i * stride + j;
+                    let d = a + 1;
+                    let b = a + stride;
+                    let c = b + 1;
+                    let mut push = |x: usize, y: usize, z: usize| {
+                        let (y, z) = if flip { (z, y) } else { (y, z) };
+                        indices.extend([T::new(x), T::new(y), T::new(z)]);
+                    };
+                    // skip the degenerate triangles at the axis
+                    if s1.position.x.abs() > f32::EPSILON {
+                        push(a, c, b);
+                    }
+                    if s0.position.x.abs() > f32::EPSILON {
+                        push(a, d, c);
+                    }
+                }
+            }
+        }
+
+        if caps && angle.abs() < 2.0 * PI - 1e-4 {
+            // open profiles are closed along the axis
+            let mut builder = Path::builder();
+            for (points, closed) in &contours {
+                if points.len() < 2 {
+                    continue;
+                }
+                builder.begin(point(points[0].x, points[0].y));
+                for p in &points[1..] {
+                    builder.line_to(point(p.x, p.y));
+                }
+                if !*closed {
+                    let (first, last) = (points[0], points[points.len() - 1]);
+                    builder.line_to(point(0.0, last.y));
+                    builder.line_to(point(0.0, first.y));
+                }
+                builder.close();
+            }
+            let mut geometry: VertexBuffers<Point, u32> = VertexBuffers::new();
+            PTessellator::with_thread_local(|tessellator| {
+                tessellator.fill_tessellator().tessellate_path(
+                    &builder.build(),
+                    &FillOptions::tolerance(tol),
+                    &mut BuffersBuilder::new(&mut geometry, Positions),
+                )
+            })?;
+
+            // The start cap faces away from the direction of the revolution and the end cap towards it.
+            let sign = angle.signum();
+            for (rot, ccw) in [
+                (Quat::IDENTITY, sign > 0.0),
+                (Quat::from_rotation_y(angle), sign < 0.0),
+            ] {
+                let offset = vertices.len();
+                let normal = rot * Vec3::Z * if ccw { 1.0 } else { -1.0 };
+                for p in &geometry.vertices {
+                    vertices.push((rot * Vec3::new(p.x, p.y, 0.0)).to_array());
+                    normals.push(normal.to_array());
+                    uv.push([p.x, p.y]);
+                }
+                for tri in geometry.indices.chunks_exact(3) {
+                    let [a, b, c] = [tri[0], tri[1], tri[2]].map(|i| geometry.vertices[i as usize]);
+                    let (i1, i2) = if ((b - a).cross(c - a) > 0.0) == ccw {
+                        (1, 2)
+                    } else {
+                        (2, 1)
+                    };
+                    indices.extend([tri[0], tri[i1], tri[i2]].map(|i| T::new(offset + i as usize)));
+                }
+            }
+        }
+
+        let mut mesh = PMesh::try_build_ex(vertices, indices, Some(uv), Some(normals))?;
+        if frame != Affine3A::IDENTITY {
+            mesh.transform(frame);
+        }
+        Ok(mesh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::Vec2;
+
+    /// The wall of a cylinder with radius 1 and height 1.
+    fn wall(builder: &mut PBuilder<PathBuilderImpl>) {
+        builder
+            .begin(Vec2::new(1.0, 0.0))
+            .line_to(Vec2::new(1.0, 1.0))
+            .end(false);
+    }
+
+    /// A closed cylinder whose profile starts and ends on the axis.
+    fn cylinder(builder: &mut PBuilder<PathBuilderImpl>) {
+        builder
+            .begin(Vec2::new(0.0, 0.0))
+            .line_to(Vec2::new(1.0, 0.0))
+            .line_to(Vec2::new(1.0, 1.0))
+            .line_to(Vec2::new(0.0, 1.0))
+            .end(false);
+    }
+
+    #[test]
+    fn full_and_partial_revolutions() {
+        let mut full = PMesh::<u32>::new();
+        full.lathe(0.01, 8, 2.0 * PI, true, wall);
+        assert_eq!(full.vertices.len(), 2 * 9);
+        assert_eq!(full.indices.len(), 3 * 2 * 8);
+
+        let mut open = PMesh::<u32>::new();
+        open.lathe(0.01, 8, PI, false, wall);
+        assert_eq!(open.vertices.len(), 2 * 9);
+        assert_eq!(open.indices.len(), 3 * 2 * 8);
+
+        // both caps are the rectangle between the wall and the axis
+        let mut capped = PMesh::<u32>::new();
+        capped.lathe(0.01, 8, PI, true, wall);
+        assert_eq!(capped.vertices.len(), 2 * 9 + 2 * 4);
+        assert_eq!(capped.indices.len(), 3 * (2 * 8 + 2 * 2));
+        assert!(capped.validate().is_ok());
+    }
+
+    #[test]
+    fn points_on_the_axis_have_no_degenerate_triangles() {
+        for angle in [2.0 * PI, PI, -PI] {
+            let mut mesh = PMesh::<u32>::new();
+            mesh.lathe(0.01, 16, angle, true, cylinder);
+            assert!(mesh.validate().is_ok());
+        }
+    }
+
+    #[test]
+    fn uv_coordinates_are_monotone() {
+        let mut mesh = PMesh::<u32>::new();
+        mesh.lathe(0.01, 8, PI, false, |builder| {
+            builder
+                .begin(Vec2::new(1.0, 0.0))
+                .quadratic_bezier_to(Vec2::new(2.0, 1.0), Vec2::new(1.0, 2.0))
+                .end(false);
+        });
+        let uv = mesh.uv.as_ref().unwrap();
+        let stride = 9;
+        let rows = uv.len() / stride;
+        for i in 0..rows {
+            for j in 0..stride {
+                let [u, v] = uv[i * stride + j];
+                assert!((0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v));
+                if j > 0 {
+                    assert!(u > uv[i * stride + j - 1][0]);
+                }
+                if i > 0 {
+                    assert!(v > uv[(i - 1) * stride + j][1]);
+                }
+            }
+        }
+        assert_eq!(uv[0][1], 0.0);
+        assert_eq!(uv[uv.len() - 1][1], 1.0);
+    }
+
+    #[test]
+    fn revolves_around_the_axis() {
+        let origin = Vec3::new(0.0, 2.0, 0.0);
+        let options = PLatheOptions::new(0.01, 16).with_axis(origin, Vec3::X);
+        let mut mesh = PMesh::<u32>::new();
+        mesh.lathe_with(&options, wall);
+        assert!(mesh.validate().is_ok());
+        for (i, normal) in mesh.normals.as_ref().unwrap().iter().enumerate() {
+            let p = mesh.vec3_at(i) - origin;
+            let radial = Vec3::new(0.0, p.y, p.z);
+            assert!((radial.length() - 1.0).abs() < 1e-5);
+            assert!(p.x > -1e-5 && p.x < 1.0 + 1e-5);
+            assert!(Vec3::from(*normal).dot(radial) > 0.99);
+        }
+    }
+}
@@ -1,20 +1,53 @@
 //! This module contains the implementation of the lyon mesh import functions.
 
 use super::{IndexType, PMesh};
-use lyon::{lyon_tessellation::VertexBuffers, math::Point};
+use bevy::math::Vec2;
+use lyon::{
+    geom::{CubicBezierSegment, QuadraticBezierSegment},
+    lyon_tessellation::VertexBuffers,
+    math::Point,
+    path::{Attributes, Event, Path, PathSlice},
+};
 mod builder;
+mod extrude;
 mod fill;
+mod geometry_builder;
+mod lathe;
 mod stroke;
+#[cfg(feature = "svg")]
+mod svg;
+mod svg_path;
+mod tessellator;
+#[cfg(feature = "text")]
+mod text;
 pub use builder::{PBuilder, PathBuilder};
-pub use fill::{FillBuilder, PFill};
-pub use lyon::path::Winding;
-pub use stroke::{PStroke, StrokeBuilder};
+pub use fill::{FillBuilder, FillRule, Orientation, PFill, PFillOptions};
+pub use geometry_builder::{
+    PGeometryBuilder, UvMapping, ATTRIBUTE_STROKE_ADVANCEMENT, ATTRIBUTE_STROKE_NORMAL,
+    ATTRIBUTE_STROKE_SIDE,
+};
+pub use lathe::PLatheOptions;
+pub use lyon::path::{
+    path::BuilderImpl as PathBuilderImpl,
+    path_buffer::{
+        Builder as PathBufferBuilder, BuilderWithAttributes as PathBufferBuilderWithAttributes,
+    },
+    Winding,
+};
+pub use stroke::{LineCap, LineJoin, PStroke, PStrokeOptions, StrokeBuilder};
+#[cfg(feature = "svg")]
+pub use svg::{SvgOptions, SvgPaint, SvgShape};
+pub use tessellator::PTessellator;
+#[cfg(feature = "text")]
+pub use text::{PFont, PGlyph, PTextOptions, TextAlign};
 
 impl<T> PMesh<T>
 where
     T: IndexType,
 {
     /// Imports a mesh from a lyon VertexBuffers.
+    ///
+    /// The uv coordinates are the positions or, if `normalize_uv` is set, the bounding box is mapped to [0, 1].
     pub fn import_geometry(geometry: &VertexBuffers<Point, T>, normalize_uv: bool) -> PMesh<T>
     where
         T: IndexType,
@@ -22,20 +55,20 @@ where
         let vertices: Vec<[f32; 3]> = geometry.vertices.iter().map(|v| [v.x, v.y, 0.0]).collect();
         let indices = geometry.indices.clone().iter().cloned().collect();
 
-        let mut uv_x_scale = 1.0;
-        let mut uv_y_scale = 1.0;
+        let mut uv_min = (0.0, 0.0);
+        let mut uv_scale = (1.0, 1.0);
 
-        if normalize_uv {
-            //println!("x_min: {}, x_max: {}, y_min: {}, y_max: {}", x_min, x_max, y_min, y_max);
+        if normalize_uv && !geometry.vertices.is_empty() {
             let (x_min, x_max, y_min, y_max) = get_bounding_rect(geometry);
-            uv_x_scale = x_max - x_min;
-            uv_y_scale = y_max - y_min;
+            let scale = |size: f32| if size > 0.0 { 1.0 / size } else { 0.0 };
+            uv_min = (x_min, y_min);
+            uv_scale = (scale(x_max - x_min), scale(y_max - y_min));
         }
         let uv: Option<Vec<[f32; 2]>> = Some(
             geometry
                 .vertices
                 .iter()
-                .map(|v| [v.x / uv_x_scale, v.y / uv_y_scale])
+                .map(|v| [(v.x - uv_min.0) * uv_scale.0, (v.y - uv_min.1) * uv_scale.1])
                 .collect(),
         );
         PMesh::build_ex(vertices, indices, uv, None)
@@ -64,3 +97,110 @@ fn get_bounding_rect<T>(buf: &VertexBuffers<Point, T>) -> (f32, f32, f32, f32) {
 
     (x_min, x_max, y_min, y_max)
 }
+
+/// Flattens the path into polylines. Returns the points of each sub-path and whether it is closed.
+///
+/// Closed sub-paths don't repeat their first point at the end.
+pub(crate) fn flatten_path(path: &Path, tol: f32) -> Vec<(Vec<Vec2>, bool)> {
+    let mut contours = Vec::new();
+    for_each_flattened(path.as_slice(), tol, &mut Vec::new(), |points, closed| {
+        contours.push((points.iter().map(|(p, _)| *p).collect(), closed));
+    });
+    contours
+}
+
+/// Flattens every sub-path and calls `f` with its points and whether it is closed, like `flatten_path`.
+///
+/// Every point also has the first custom attribute of the path, i.e., the width of variable-width strokes.
+/// The width is zero if the path has no custom attributes. The points are collected in `points`, so its
+/// memory can be reused.
+pub(crate) fn for_each_flattened(
+    path: PathSlice,
+    tol: f32,
+    points: &mut Vec<(Vec2, f32)>,
+    mut f: impl FnMut(&mut Vec<(Vec2, f32)>, bool),
+) {
+    fn push(points: &mut Vec<(Vec2, f32)>, to: Point, width: f32) {
+        let to = Vec2::new(to.x, to.y);
+        if points
+            .last()
+            .is_none_or(|(last, _)| last.distance(to) > f32::EPSILON)
+        {
+            points.push((to, width));
+        }
+    }
+    let width = |attributes: Attributes| attributes.first().copied().unwrap_or(0.0);
+
+    for event in path.iter_with_attributes() {
+        match event {
+            Event::Begin { at: (at, a) } => {
+                points.clear();
+                points.push((Vec2::new(at.x, at.y), width(a)));
+            }
+            Event::Line { to: (to, a), .. } => push(points, to, width(a)),
+            Event::Quadratic {
+                from: (from, a0),
+                ctrl,
+                to: (to, a1),
+            } => {
+                let (w0, w1) = (width(a0), width(a1));
+                QuadraticBezierSegment { from, ctrl, to }
+                    .for_each_flattened_with_t(tol, &mut |line, t| {
+                        push(points, line.to, w0 + (w1 - w0) * t.end);
+                    });
+            }
+            Event::Cubic {
+                from: (from, a0),
+                ctrl1,
+                ctrl2,
+                to: (to, a1),
+            } => {
+                let (w0, w1) = (width(a0), width(a1));
+                CubicBezierSegment {
+                    from,
+                    ctrl1,
+                    ctrl2,
+                    to,
+                }
+                .for_each_flattened_with_t(tol, &mut |line, t| {
+                    push(points, line.to, w0 + (w1 - w0) * t.end);
+                });
+            }
+            Event::End { close, .. } => {
+                let first = points.first().map(|(p, _)| *p);
+                if points.len() > 1 && points.last().map(|(p, _)| *p) == first {
+                    points.pop();
+                }
+                f(points, close);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometry(points: &[[f32; 2]]) -> VertexBuffers<Point, u32> {
+        let mut geometry = VertexBuffers::new();
+        geometry
+            .vertices
+            .extend(points.iter().map(|p| Point::new(p[0], p[1])));
+        geometry.indices.extend(0..points.len() as u32);
+        geometry
+    }
+
+    #[test]
+    fn normalized_uv_start_at_zero() {
+        let mesh = PMesh::import_geometry(&geometry(&[[2.0, -1.0], [6.0, -1.0], [4.0, 3.0]]), true);
+        assert_eq!(mesh.uv, Some(vec![[0.0, 0.0], [1.0, 0.0], [0.5, 1.0]]));
+
+        // without an extent, the uv coordinates don't grow along that axis
+        let mesh = PMesh::import_geometry(&geometry(&[[2.0, 5.0], [6.0, 5.0], [4.0, 5.0]]), true);
+        assert_eq!(mesh.uv, Some(vec![[0.0, 0.0], [1.0, 0.0], [0.5, 0.0]]));
+
+        let mesh =
+            PMesh::import_geometry(&geometry(&[[2.0, -1.0], [6.0, -1.0], [4.0, 3.0]]), false);
+        assert_eq!(mesh.uv, Some(vec![[2.0, -1.0], [6.0, -1.0], [4.0, 3.0]]));
+    }
+}
@@ -1,24 +1,480 @@
-use super::super::PMesh;
-use super::PBuilder;
+use super::super::{OrPanic, PMesh, PMeshError};
+use super::{
+    for_each_flattened, PBuilder, PGeometryBuilder, PTessellator, PathBufferBuilderWithAttributes,
+    UvMapping,
+};
 use crate::IndexType;
+use bevy::math::Vec2;
 use lyon::math::Point;
-use lyon::tessellation::geometry_builder::{MaxIndex, Positions};
-pub use lyon::tessellation::StrokeBuilder;
-use lyon::tessellation::{
-    BuffersBuilder, StrokeOptions, StrokeTessellator, VertexBuffers, VertexId,
+use lyon::path::{
+    traits::PathBuilder, AttributeStore, Attributes, EndpointId, IdEvent, Path, PathBuffer,
+    PathSlice,
 };
+use lyon::tessellation::geometry_builder::MaxIndex;
+pub use lyon::tessellation::{LineCap, LineJoin, StrokeBuilder};
+use lyon::tessellation::{StrokeOptions, StrokeTessellator, VertexId};
 use std::ops::Add;
 
 // TODO: allow other index sizes!
 
+/// Options for stroking a path with `PStroke` or `PMesh::stroke_with`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PStrokeOptions {
+    /// The width of the stroke.
+    pub width: f32,
+
+    /// The maximum distance between the curves and their approximation.
+    pub tolerance: f32,
+
+    /// The cap at the start of every sub-path and dash.
+    pub start_cap: LineCap,
+
+    /// The cap at the end of every sub-path and dash.
+    pub end_cap: LineCap,
+
+    /// How the segments of a sub-path are joined.
+    pub join: LineJoin,
+
+    /// The maximum ratio between the length of a miter and the width of the stroke. Must be at least 1.
+    pub miter_limit: f32,
+
+    /// The lengths of alternating dashes and gaps. The stroke is solid if this is empty.
+    ///
+    /// Like in SVG, a list with an odd number of entries is repeated to get an even number.
+    pub dashes: Vec<f32>,
+
+    /// The distance into the dash pattern at which every sub-path starts.
+    pub dash_offset: f32,
+
+    /// How the uv coordinates are computed. By default, they run along the path.
+    pub uv: UvMapping,
+}
+
+impl Default for PStrokeOptions {
+    fn default() -> Self {
+        PStrokeOptions {
+            width: StrokeOptions::DEFAULT_LINE_WIDTH,
+            tolerance: StrokeOptions::DEFAULT_TOLERANCE,
+            start_cap: LineCap::Butt,
+            end_cap: LineCap::Butt,
+            join: LineJoin::Miter,
+            miter_limit: StrokeOptions::DEFAULT_MITER_LIMIT,
+            dashes: Vec::new(),
+            dash_offset: 0.0,
+            uv: UvMapping::AlongPath,
+        }
+    }
+}
+
+impl PStrokeOptions {
+    /// Creates solid stroke options with the given width and tolerance.
+    pub fn new(width: f32, tol: f32) -> Self {
+        PStrokeOptions {
+            width,
+            tolerance: tol,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the caps at the start and end of every sub-path and dash.
+    pub fn with_caps(mut self, cap: LineCap) -> Self {
+        self.start_cap = cap;
+        self.end_cap = cap;
+        self
+    }
+
+    /// Sets the cap at the start of every sub-path and dash.
+    pub fn with_start_cap(mut self, cap: LineCap) -> Self {
+        self.start_cap = cap;
+        self
+    }
+
+    /// Sets the cap at the end of every sub-path and dash.
+    pub fn with_end_cap(mut self, cap: LineCap) -> Self {
+        self.end_cap = cap;
+        self
+    }
+
+    /// Sets how the segments are joined.
+    pub fn with_join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+
+    /// Sets the miter limit.
+    pub fn with_miter_limit(mut self, limit: f32) -> Self {
+        self.miter_limit = limit;
+        self
+    }
+
+    /// Sets the lengths of alternating dashes and gaps and the offset into the pattern.
+    pub fn with_dashes(mut self, dashes: Vec<f32>, offset: f32) -> Self {
+        self.dashes = dashes;
+        self.dash_offset = offset;
+        self
+    }
+
+    /// Sets how the uv coordinates are computed.
+    pub fn with_uv(mut self, mapping: UvMapping) -> Self {
+        self.uv = mapping;
+        self
+    }
+
+    fn to_lyon(&self) -> StrokeOptions {
+        StrokeOptions::tolerance(self.tolerance)
+            .with_line_width(self.width)
+            .with_start_cap(self.start_cap)
+            .with_end_cap(self.end_cap)
+            .with_line_join(self.join)
+            .with_miter_limit(self.miter_limit.max(StrokeOptions::MINIMUM_MITER_LIMIT))
+    }
+
+    /// Returns whether the stroke is dashed, i.e., the dash pattern is valid and not empty.
+    fn is_dashed(&self) -> bool {
+        self.dashes.iter().all(|d| d.is_finite() && *d >= 0.0)
+            && self.dashes.iter().sum::<f32>() > 0.0
+    }
+}
+
+/// Reusable buffers for stroking paths.
+#[derive(Default)]
+pub(super) struct StrokeBuffers {
+    /// The dashes of a dashed stroke.
+    dashed: PathBuffer,
+    /// The flattened points of the current sub-path.
+    points: Vec<(Vec2, f32)>,
+    /// The points of the first dash of a closed sub-path.
+    first: Vec<(Vec2, f32)>,
+    /// The first endpoint of every dash and the length of the gaps before it.
+    offsets: Vec<(EndpointId, f32)>,
+    /// The clamped widths of a variable-width stroke by endpoint.
+    widths: Vec<f32>,
+}
+
+/// Writes the dashes of a dashed path and remembers where they start.
+struct DashWriter<'a, B: PathBuilder> {
+    builder: &'a mut B,
+    offsets: &'a mut Vec<(EndpointId, f32)>,
+    /// The length of all dashes so far, i.e., the advancement lyon measures.
+    drawn: f32,
+    /// The first and the last point of the current dash and whether it has a segment.
+    start: Vec2,
+    last: (Vec2, f32),
+    segment: bool,
+}
+
+impl<B: PathBuilder> DashWriter<'_, B> {
+    /// Begins a dash that starts at the given distance along the original path.
+    fn begin(&mut self, (p, w): (Vec2, f32), advancement: f32) {
+        let n = self.builder.num_attributes();
+        let id = self.builder.begin(point(p), &[w][..n]);
+        self.offsets.push((id, advancement - self.drawn));
+        (self.start, self.last, self.segment) = (p, (p, w), false);
+    }
+
+    fn line_to(&mut self, (p, w): (Vec2, f32)) {
+        if p == self.last.0 {
+            return;
+        }
+        let n = self.builder.num_attributes();
+        self.drawn += self.last.0.distance(p);
+        self.builder.line_to(point(p), &[w][..n]);
+        (self.last, self.segment) = ((p, w), true);
+    }
+
+    fn end(&mut self, close: bool) {
+        if close {
+            self.drawn += self.last.0.distance(self.start);
+        } else if !self.segment {
+            // lyon only draws the caps of an empty sub-path if it contains a segment
+            let (p, w) = self.last;
+            let n = self.builder.num_attributes();
+            self.builder.line_to(point(p), &[w][..n]);
+        }
+        self.builder.end(close);
+    }
+}
+
+#[inline]
+fn point(p: Vec2) -> Point {
+    Point::new(p.x, p.y)
+}
+
+/// Splits the path into the dashes of the given pattern and writes them to `buffers.dashed`.
+/// The width of variable-width strokes is interpolated where the dashes are cut.
+///
+/// Like in SVG, a pattern with an odd number of entries is repeated to get an even number.
+/// Also writes the first endpoint of every dash and the length of the gaps before it to `buffers.offsets`.
+/// Lyon measures the advancement only along the dashes, so adding this gives the advancement along the
+/// original path.
+///
+/// Dashes of length zero become empty sub-paths, so they are drawn as dots by round or square caps.
+fn dash_path(path: PathSlice, tol: f32, dashes: &[f32], offset: f32, buffers: &mut StrokeBuffers) {
+    let count = dashes.len() * (1 + dashes.len() % 2);
+    let entry = |i: usize| dashes[i % dashes.len()];
+    let total: f32 = (0..count).map(entry).sum();
+    let lerp =
+        |(a, wa): (Vec2, f32), (b, wb): (Vec2, f32), t: f32| (a.lerp(b, t), wa + (wb - wa) * t);
+
+    let StrokeBuffers {
+        dashed,
+        points,
+        first,
+        offsets,
+        ..
+    } = buffers;
+    dashed.clear();
+    offsets.clear();
+    // keep the width attribute if the path has one
+    let mut builder = PathBufferBuilderWithAttributes::new(dashed, path.num_attributes().min(1));
+    let mut writer = DashWriter {
+        builder: &mut builder,
+        offsets,
+        drawn: 0.0,
+        start: Vec2::ZERO,
+        last: (Vec2::ZERO, 0.0),
+        segment: false,
+    };
+    let mut path_length = 0.0;
+
+    for_each_flattened(path, tol, points, |points, closed| {
+        if points.is_empty() {
+            return;
+        }
+        if closed {
+            points.push(points[0]);
+        }
+
+        // find the entry of the pattern at the start of the sub-path
+        let mut index = 0;
+        let mut position = offset.rem_euclid(total);
+        while position > entry(index) || (position == entry(index) && position > 0.0) {
+            position -= entry(index);
+            index = (index + 1) % count;
+        }
+        let mut remaining = entry(index) - position;
+
+        // Even entries are dashes, odd entries are gaps. The first dash of a closed sub-path might have
+        // to be joined with the last one, so it is kept until the end of the sub-path.
+        let sub_path_start = path_length;
+        let joined = closed && index % 2 == 0;
+        let mut in_first = joined;
+        first.clear();
+        if joined {
+            first.push(points[0]);
+        } else if index % 2 == 0 {
+            writer.begin(points[0], sub_path_start);
+        }
+        let mut traveled = 0.0;
+        for segment in points.windows(2) {
+            let (mut from, to) = (segment[0], segment[1]);
+            let mut length = from.0.distance(to.0);
+            while remaining <= length {
+                from = if length > 0.0 {
+                    lerp(from, to, remaining / length)
+                } else {
+                    from
+                };
+                length -= remaining;
+                traveled += remaining;
+                if index % 2 == 1 {
+                    writer.begin(from, sub_path_start + traveled);
+                } else if in_first {
+                    first.push(from);
+                    in_first = false;
+                } else {
+                    writer.line_to(from);
+                    writer.end(false);
+                }
+                index = (index + 1) % count;
+                remaining = entry(index);
+            }
+            remaining -= length;
+            traveled += length;
+            if index % 2 == 0 {
+                if in_first {
+                    first.push(to);
+                } else {
+                    writer.line_to(to);
+                }
+            }
+        }
+        path_length += traveled;
+
+        if !joined {
+            if index % 2 == 0 {
+                writer.end(false);
+            }
+        } else if in_first {
+            // the dash covers the whole sub-path, so it stays closed
+            writer.begin(first[0], sub_path_start);
+            for p in &first[1..first.len() - 1] {
+                writer.line_to(*p);
+            }
+            writer.end(true);
+        } else {
+            // join the last dash with the first one across the start of the sub-path
+            if index % 2 == 1 {
+                writer.begin(first[0], sub_path_start);
+            }
+            for p in &first[1..] {
+                writer.line_to(*p);
+            }
+            writer.end(false);
+        }
+    });
+    builder.build();
+}
+
+/// The smallest width of variable-width strokes. Lyon can't compute the outline where the width is zero.
+const MIN_WIDTH: f32 = 1e-5;
+
+/// The first custom attribute of a path, clamped to at least `MIN_WIDTH`. See `clamp_widths`.
+struct ClampedWidths<'a>(&'a [f32]);
+
+impl AttributeStore for ClampedWidths<'_> {
+    fn get(&self, id: EndpointId) -> Attributes<'_> {
+        std::slice::from_ref(&self.0[id.0 as usize])
+    }
+
+    fn num_attributes(&self) -> usize {
+        1
+    }
+}
+
+/// Writes the first custom attribute of every endpoint of the path, clamped to at least `MIN_WIDTH`,
+/// to `widths` at the index of the endpoint. Returns whether any of them differs from `width`.
+fn clamp_widths(path: PathSlice, width: f32, widths: &mut Vec<f32>) -> bool {
+    widths.clear();
+    let mut varies = false;
+    for event in path.id_iter() {
+        let id = match event {
+            IdEvent::Begin { at } => at,
+            IdEvent::Line { to, .. }
+            | IdEvent::Quadratic { to, .. }
+            | IdEvent::Cubic { to, .. } => to,
+            IdEvent::End { .. } => continue,
+        };
+        let i = id.0 as usize;
+        if widths.len() <= i {
+            widths.resize(i + 1, MIN_WIDTH);
+        }
+        let w = path.attributes(id)[0];
+        varies |= w != width;
+        widths[i] = w.max(MIN_WIDTH);
+    }
+    varies
+}
+
+/// Strokes the path while it is drawn in the closure and appends it to the mesh. See `PStroke::try_draw`.
+pub(super) fn stroke_solid_into<T, F>(
+    tessellator: &mut StrokeTessellator,
+    options: &PStrokeOptions,
+    mesh: &mut PMesh<T>,
+    draw_commands: F,
+) -> Result<(), PMeshError>
+where
+    T: Add + IndexType + From<VertexId> + MaxIndex,
+    F: FnOnce(&mut PBuilder<StrokeBuilder>),
+{
+    let lyon_options = options.to_lyon();
+    let mut output = PGeometryBuilder::new(mesh).with_uv_mapping(options.uv);
+    let mut builder = PBuilder::new(tessellator.builder(&lyon_options, &mut output));
+    draw_commands(&mut builder);
+    builder.build()?;
+    Ok(())
+}
+
+/// Records the path drawn in the closure in `path`, strokes it, and appends it to the mesh.
+/// See `PStroke::try_draw_with_attributes`.
+pub(super) fn stroke_into<T, F>(
+    tessellator: &mut StrokeTessellator,
+    path: &mut PathBuffer,
+    buffers: &mut StrokeBuffers,
+    options: &PStrokeOptions,
+    mesh: &mut PMesh<T>,
+    draw_commands: F,
+) -> Result<(), PMeshError>
+where
+    T: Add + IndexType + From<VertexId> + MaxIndex,
+    F: FnOnce(&mut PBuilder<PathBufferBuilderWithAttributes<'_>>),
+{
+    path.clear();
+    let mut builder = PBuilder::with_attributes(PathBufferBuilderWithAttributes::new(path, 1));
+    builder.set_width(options.width);
+    draw_commands(&mut builder);
+    builder.build();
+    stroke_path_into(tessellator, buffers, options, mesh, path.get(0))
+}
+
+/// Strokes a lyon path and appends it to the mesh. See `PStroke::try_draw_path`.
+pub(super) fn stroke_path_into<T>(
+    tessellator: &mut StrokeTessellator,
+    buffers: &mut StrokeBuffers,
+    options: &PStrokeOptions,
+    mesh: &mut PMesh<T>,
+    path: PathSlice,
+) -> Result<(), PMeshError>
+where
+    T: Add + IndexType + From<VertexId> + MaxIndex,
+{
+    // dashing isn't supported by lyon, so the path is split into the dashes first
+    let dashed = options.is_dashed();
+    if dashed {
+        dash_path(
+            path,
+            options.tolerance,
+            &options.dashes,
+            options.dash_offset,
+            buffers,
+        );
+    } else {
+        buffers.offsets.clear();
+    }
+    let path = if dashed { buffers.dashed.get(0) } else { path };
+
+    // Lyon's variable-width stroker joins and caps the segments differently, so it is only used if the
+    // width actually changes along the path.
+    let variable =
+        path.num_attributes() > 0 && clamp_widths(path, options.width, &mut buffers.widths);
+    let mut output = PGeometryBuilder::new(mesh)
+        .with_advancement_offsets(&buffers.offsets)
+        .with_uv_mapping(options.uv);
+    if variable {
+        // lyon multiplies the line width with the attribute, so the attribute becomes the width
+        let lyon_options = options
+            .to_lyon()
+            .with_line_width(1.0)
+            .with_variable_line_width(0);
+        tessellator.tessellate_with_ids(
+            path.id_iter(),
+            &path,
+            Some(&ClampedWidths(&buffers.widths)),
+            &lyon_options,
+            &mut output,
+        )?;
+    } else {
+        tessellator.tessellate_with_ids(
+            path.id_iter(),
+            &path,
+            None,
+            &options.to_lyon(),
+            &mut output,
+        )?;
+    }
+    Ok(())
+}
+
 /// This structure wraps a `lyon::tesselation::StrokeTessellator` and adds functionality to apply transformations to the path being built.
 pub struct PStroke<T>
 where
     T: Add + IndexType + From<VertexId> + MaxIndex,
 {
     tessellator: StrokeTessellator,
-    options: StrokeOptions,
-    geometry: VertexBuffers<Point, T>,
+    options: PStrokeOptions,
+    mesh: PMesh<T>,
+    path: PathBuffer,
+    buffers: StrokeBuffers,
 }
 
 impl<T> PStroke<T>
@@ -27,35 +483,102 @@ where
 {
     /// Creates a new stroke tessellator with the given tolerance and width.
     pub fn new(width: f32, tol: f32) -> Self {
-        // Will contain the result of the tessellation.
-        let geometry: VertexBuffers<Point, T> = VertexBuffers::new();
-        let tessellator = StrokeTessellator::new();
-        let options = StrokeOptions::tolerance(tol).with_line_width(width);
+        PStroke::with_options(PStrokeOptions::new(width, tol))
+    }
 
+    /// Creates a new stroke tessellator with the given options.
+    pub fn with_options(options: PStrokeOptions) -> Self {
         PStroke {
-            tessellator,
+            tessellator: StrokeTessellator::new(),
             options,
-            geometry,
+            // Will contain the result of the tessellation.
+            mesh: PMesh::new(),
+            path: PathBuffer::new(),
+            buffers: StrokeBuffers::default(),
         }
     }
 
     /// Draws the path using the given closure.
+    ///
+    /// Panics if the tessellation fails. See `try_draw`.
     pub fn draw<F>(&mut self, draw_commands: F) -> &mut Self
     where
         F: FnOnce(&mut PBuilder<StrokeBuilder>),
     {
-        // TODO: Improve performance: Can I use the BuffersBuilder to directly write to the mesh?
-        let geometry_builder = &mut BuffersBuilder::new(&mut self.geometry, Positions);
-        let builder = self.tessellator.builder(&self.options, geometry_builder);
-        let mut my_builder = PBuilder::new(builder);
-        draw_commands(&mut my_builder);
-        my_builder.build().unwrap();
+        self.try_draw(draw_commands).or_panic();
+        self
+    }
+
+    /// Draws the path using the given closure. Returns an error if the tessellation fails.
+    ///
+    /// The path is tessellated while it is drawn, so the stroke is solid and has the width of the options.
+    /// Use `try_draw_with_attributes` for dashed and variable-width strokes.
+    pub fn try_draw<F>(&mut self, draw_commands: F) -> Result<&mut Self, PMeshError>
+    where
+        F: FnOnce(&mut PBuilder<StrokeBuilder>),
+    {
+        stroke_solid_into(
+            &mut self.tessellator,
+            &self.options,
+            &mut self.mesh,
+            draw_commands,
+        )?;
+        Ok(self)
+    }
+
+    /// Draws the path using the given closure and applies the dash pattern of the options.
+    ///
+    /// Panics if the tessellation fails. See `try_draw_with_attributes`.
+    pub fn draw_with_attributes<F>(&mut self, draw_commands: F) -> &mut Self
+    where
+        F: FnOnce(&mut PBuilder<PathBufferBuilderWithAttributes<'_>>),
+    {
+        self.try_draw_with_attributes(draw_commands).or_panic();
         self
     }
 
+    /// Draws the path using the given closure and applies the dash pattern of the options.
+    /// Returns an error if the tessellation fails.
+    ///
+    /// The path is recorded before it is tessellated. The stroke starts with the width of the options and
+    /// can be varied along the path with `set_width` or the `_w` variants of the path commands, e.g.,
+    /// `line_to_w`. Strokes that keep the width of the options are tessellated exactly like `try_draw`.
+    pub fn try_draw_with_attributes<F>(&mut self, draw_commands: F) -> Result<&mut Self, PMeshError>
+    where
+        F: FnOnce(&mut PBuilder<PathBufferBuilderWithAttributes<'_>>),
+    {
+        stroke_into(
+            &mut self.tessellator,
+            &mut self.path,
+            &mut self.buffers,
+            &self.options,
+            &mut self.mesh,
+            draw_commands,
+        )?;
+        Ok(self)
+    }
+
+    /// Strokes a lyon path. Returns an error if the tessellation fails.
+    ///
+    /// By default, the uv coordinates run along the stroke, see `PGeometryBuilder`.
+    /// Along dashed strokes, they continue across the gaps.
+    ///
+    /// If the path has custom attributes and the first one differs from the width of the options anywhere,
+    /// it is the width of the stroke at each point and the width of the options is ignored.
+    pub fn try_draw_path(&mut self, path: &Path) -> Result<&mut Self, PMeshError> {
+        stroke_path_into(
+            &mut self.tessellator,
+            &mut self.buffers,
+            &self.options,
+            &mut self.mesh,
+            path.as_slice(),
+        )?;
+        Ok(self)
+    }
+
     /// Builds a PMesh object, consuming the tessellator.
     pub fn build(self) -> PMesh<T> {
-        PMesh::import_geometry(&self.geometry, false)
+        self.mesh
     }
 }
 
@@ -63,14 +586,269 @@ impl<T> PMesh<T>
 where
     T: Add + IndexType + From<VertexId> + MaxIndex,
 {
-    /// Fills the path built in the closure and appends it to the mesh.
+    /// Strokes the path built in the closure and appends it to the mesh.
+    ///
+    /// Panics if the tessellation fails. See `try_stroke`.
     pub fn stroke<F>(&mut self, width: f32, tol: f32, draw_commands: F) -> &mut PMesh<T>
     where
         F: FnOnce(&mut PBuilder<StrokeBuilder>),
     {
-        let mut tessellator = PStroke::<T>::new(width, tol);
-        tessellator.draw(draw_commands);
-        self.extend(&tessellator.build());
+        self.try_stroke(width, tol, draw_commands).or_panic();
+        self
+    }
+
+    /// Strokes the path built in the closure and appends it to the mesh. Returns `PMeshError::Tessellation`
+    /// if lyon can't tessellate the path or the vertices don't fit into the index type.
+    pub fn try_stroke<F>(
+        &mut self,
+        width: f32,
+        tol: f32,
+        draw_commands: F,
+    ) -> Result<&mut PMesh<T>, PMeshError>
+    where
+        F: FnOnce(&mut PBuilder<StrokeBuilder>),
+    {
+        PTessellator::with_thread_local(|tessellator| {
+            tessellator
+                .try_stroke_solid_into(self, &PStrokeOptions::new(width, tol), draw_commands)
+                .map(|_| ())
+        })?;
+        Ok(self)
+    }
+
+    /// Strokes the path built in the closure with the given options and appends it to the mesh.
+    /// Unlike `stroke`, this supports dashes and variable widths, see `PStroke::try_draw_with_attributes`.
+    ///
+    /// Panics if the tessellation fails. See `try_stroke_with`.
+    pub fn stroke_with<F>(&mut self, options: &PStrokeOptions, draw_commands: F) -> &mut PMesh<T>
+    where
+        F: FnOnce(&mut PBuilder<PathBufferBuilderWithAttributes<'_>>),
+    {
+        self.try_stroke_with(options, draw_commands).or_panic();
         self
     }
+
+    /// Strokes the path built in the closure with the given options and appends it to the mesh.
+    /// Returns `PMeshError::Tessellation` if lyon can't tessellate the path or the vertices don't fit into
+    /// the index type.
+    pub fn try_stroke_with<F>(
+        &mut self,
+        options: &PStrokeOptions,
+        draw_commands: F,
+    ) -> Result<&mut PMesh<T>, PMeshError>
+    where
+        F: FnOnce(&mut PBuilder<PathBufferBuilderWithAttributes<'_>>),
+    {
+        PTessellator::with_thread_local(|tessellator| {
+            tessellator
+                .try_stroke_into(self, options, draw_commands)
+                .map(|_| ())
+        })?;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lyon::path::Event;
+
+    fn polyline(points: &[[f32; 2]], close: bool) -> Path {
+        let mut builder = Path::builder();
+        builder.begin(Point::new(points[0][0], points[0][1]));
+        for p in &points[1..] {
+            builder.line_to(Point::new(p[0], p[1]));
+        }
+        builder.end(close);
+        builder.build()
+    }
+
+    /// Returns the points of every dash, whether it is closed, and the advancement offsets.
+    fn dash(path: &Path, dashes: &[f32], offset: f32) -> (Vec<(Vec<[f32; 2]>, bool)>, Vec<f32>) {
+        let mut buffers = StrokeBuffers::default();
+        dash_path(path.as_slice(), 0.01, dashes, offset, &mut buffers);
+        let mut sub_paths = Vec::new();
+        let mut points = Vec::new();
+        for event in buffers.dashed.get(0).iter() {
+            match event {
+                Event::Begin { at } => points = vec![[at.x, at.y]],
+                Event::Line { to, .. } => points.push([to.x, to.y]),
+                Event::End { close, .. } => sub_paths.push((std::mem::take(&mut points), close)),
+                _ => unreachable!(),
+            }
+        }
+        (sub_paths, buffers.offsets.iter().map(|(_, o)| *o).collect())
+    }
+
+    #[test]
+    fn solid_strokes_are_streamed_and_recorded_alike() {
+        let mut streamed = PMesh::<u32>::new();
+        streamed.stroke(0.5, 0.01, |b| {
+            b.begin(Vec2::ZERO)
+                .line_to(Vec2::new(4.0, 0.0))
+                .quadratic_bezier_to(Vec2::new(5.0, 1.0), Vec2::new(4.0, 3.0))
+                .end(false);
+        });
+        let mut recorded = PMesh::<u32>::new();
+        recorded.stroke_with(&PStrokeOptions::new(0.5, 0.01), |b| {
+            b.begin(Vec2::ZERO)
+                .line_to(Vec2::new(4.0, 0.0))
+                .quadratic_bezier_to(Vec2::new(5.0, 1.0), Vec2::new(4.0, 3.0))
+                .end(false);
+        });
+        assert_eq!(
+            streamed.vertices.get_vertices(),
+            recorded.vertices.get_vertices()
+        );
+        assert_eq!(
+            streamed.indices.get_indices(),
+            recorded.indices.get_indices()
+        );
+        assert_eq!(streamed.uv, recorded.uv);
+    }
+
+    #[test]
+    fn dashes_of_an_open_path() {
+        let line = polyline(&[[0.0, 0.0], [10.0, 0.0]], false);
+        let (dashes, offsets) = dash(&line, &[2.0, 1.0], 0.0);
+        assert_eq!(
+            dashes,
+            vec![
+                (vec![[0.0, 0.0], [2.0, 0.0]], false),
+                (vec![[3.0, 0.0], [5.0, 0.0]], false),
+                (vec![[6.0, 0.0], [8.0, 0.0]], false),
+                (vec![[9.0, 0.0], [10.0, 0.0]], false),
+            ]
+        );
+        // the gaps before every dash
+        assert_eq!(offsets, vec![0.0, 1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn offsets_wrap_around_the_pattern() {
+        let line = polyline(&[[0.0, 0.0], [10.0, 0.0]], false);
+        let (dashes, offsets) = dash(&line, &[2.0, 1.0], 2.0);
+        assert_eq!(dashes[0], (vec![[1.0, 0.0], [3.0, 0.0]], false));
+        assert_eq!(offsets[0], 1.0);
+        assert_eq!(
+            dash(&line, &[2.0, 1.0], -1.0),
+            (dashes.clone(), offsets.clone())
+        );
+        assert_eq!(dash(&line, &[2.0, 1.0], 5.0), (dashes, offsets));
+
+        // an odd pattern is repeated, so its entries alternate between dashes and gaps
+        let (dashes, _) = dash(&line, &[1.0, 2.0, 3.0], 0.0);
+        assert_eq!(dashes[1], (vec![[3.0, 0.0], [6.0, 0.0]], false));
+        assert_eq!(dashes[2], (vec![[7.0, 0.0], [9.0, 0.0]], false));
+    }
+
+    #[test]
+    fn empty_dashes_become_dots() {
+        let line = polyline(&[[0.0, 0.0], [5.0, 0.0]], false);
+        let (dashes, offsets) = dash(&line, &[0.0, 2.0], 0.0);
+        assert_eq!(
+            dashes,
+            vec![
+                (vec![[0.0, 0.0], [0.0, 0.0]], false),
+                (vec![[2.0, 0.0], [2.0, 0.0]], false),
+                (vec![[4.0, 0.0], [4.0, 0.0]], false),
+            ]
+        );
+        assert_eq!(offsets, vec![0.0, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn closed_sub_paths_join_the_first_and_last_dash() {
+        let square = polyline(&[[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0]], true);
+        let (dashes, offsets) = dash(&square, &[3.0, 1.0], 1.0);
+        assert_eq!(
+            dashes,
+            vec![
+                (vec![[3.0, 0.0], [4.0, 0.0], [4.0, 2.0]], false),
+                (vec![[4.0, 3.0], [4.0, 4.0], [2.0, 4.0]], false),
+                (vec![[1.0, 4.0], [0.0, 4.0], [0.0, 2.0]], false),
+                (vec![[0.0, 1.0], [0.0, 0.0], [2.0, 0.0]], false),
+            ]
+        );
+        // the joined dash starts at the end of the sub-path
+        assert_eq!(offsets, vec![3.0, 4.0, 5.0, 6.0]);
+
+        // a dash longer than the sub-path keeps it closed
+        let (dashes, _) = dash(&square, &[100.0, 1.0], 1.0);
+        assert_eq!(
+            dashes,
+            vec![(vec![[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0]], true)]
+        );
+    }
+
+    #[test]
+    fn variable_widths_reach_the_requested_half_width() {
+        let mut mesh = PMesh::<u32>::new();
+        mesh.stroke_with(&PStrokeOptions::new(1.0, 0.01), |b| {
+            b.set_width(2.0)
+                .begin(Vec2::ZERO)
+                .line_to_w(Vec2::new(10.0, 0.0), 4.0)
+                .end(false);
+        });
+        let vertices = mesh.vertices.get_vertices();
+        let side_at = |x: f32, side: f32| {
+            let v = vertices
+                .iter()
+                .find(|v| (v[0] - x).abs() < 1e-3 && v[1] * side > 0.0)
+                .expect("missing side vertex");
+            Vec2::new(v[0], v[1])
+        };
+        for side in [-1.0, 1.0] {
+            // the sides touch circles around the endpoints whose radius is half the requested width
+            let (a, b) = (side_at(0.0, side), side_at(10.0, side));
+            let normal = (b - a).perp().normalize();
+            assert!((normal.dot(a).abs() - 1.0).abs() < 1e-3, "{} {}", a, b);
+            assert!((normal.dot(a - Vec2::new(10.0, 0.0)).abs() - 2.0).abs() < 1e-3);
+        }
+        assert!(vertices.iter().all(|v| v[1].abs() < 2.1));
+    }
+
+    /// The clamped widths at the endpoints of the path in order.
+    fn endpoint_widths(path: PathSlice, widths: &[f32]) -> Vec<f32> {
+        path.id_iter()
+            .filter_map(|event| match event {
+                IdEvent::Begin { at } => Some(at),
+                IdEvent::Line { to, .. } => Some(to),
+                _ => None,
+            })
+            .map(|id| widths[id.0 as usize])
+            .collect()
+    }
+
+    #[test]
+    fn widths_are_clamped() {
+        let mut path = PathBuffer::new();
+        let mut builder =
+            PBuilder::with_attributes(PathBufferBuilderWithAttributes::new(&mut path, 1));
+        builder
+            .set_width(1.0)
+            .begin(Vec2::ZERO)
+            .line_to_w(Vec2::new(1.0, 0.0), 0.0)
+            .line_to_w(Vec2::new(2.0, 0.0), -1.0)
+            .end(false);
+        builder.build();
+        let mut widths = Vec::new();
+        assert!(clamp_widths(path.get(0), 1.0, &mut widths));
+        assert_eq!(
+            endpoint_widths(path.get(0), &widths),
+            vec![1.0, MIN_WIDTH, MIN_WIDTH]
+        );
+
+        path.clear();
+        let mut builder =
+            PBuilder::with_attributes(PathBufferBuilderWithAttributes::new(&mut path, 1));
+        builder
+            .set_width(1.0)
+            .begin(Vec2::ZERO)
+            .line_to(Vec2::new(1.0, 0.0))
+            .end(false);
+        builder.build();
+        assert!(!clamp_widths(path.get(0), 1.0, &mut widths));
+        assert_eq!(endpoint_widths(path.get(0), &widths), vec![1.0, 1.0]);
+    }
 }
@@ -0,0 +1,409 @@
+use super::super::{PMesh, PMeshError};
+use super::{FillRule, LineCap, LineJoin, PBuilder, PFill, PFillOptions, PStroke, PStrokeOptions};
+use crate::IndexType;
+use bevy::color::{LinearRgba, Srgba};
+use bevy::math::{Affine2, Vec2};
+use lyon::path::traits::PathBuilder;
+use lyon::tessellation::{geometry_builder::MaxIndex, VertexId};
+use std::ops::Add;
+use usvg::tiny_skia_path::PathSegment;
+
+/// Options for importing SVG documents with `PMesh::from_svg` or `PMesh::svg_shapes`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgOptions {
+    /// The maximum distance between the curves and their approximation in the units of the document.
+    pub tolerance: f32,
+
+    /// Whether the y-axis is flipped so the document isn't upside down in the y-up coordinates of bevy.
+    /// The document then covers the area from `(0, 0)` to its size.
+    pub flip_y: bool,
+
+    /// Whether the fills of the shapes are imported.
+    pub fills: bool,
+
+    /// Whether the strokes of the shapes are imported.
+    pub strokes: bool,
+
+    /// The distance along the z-axis between consecutive shapes when they are merged by `PMesh::from_svg`,
+    /// so shapes painted later are in front of earlier ones even with depth testing.
+    pub layer_offset: f32,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            tolerance: PFillOptions::default().tolerance,
+            flip_y: true,
+            fills: true,
+            strokes: true,
+            layer_offset: 0.0,
+        }
+    }
+}
+
+impl SvgOptions {
+    /// Creates import options with the given tolerance.
+    pub fn new(tol: f32) -> Self {
+        SvgOptions {
+            tolerance: tol,
+            ..Default::default()
+        }
+    }
+
+    /// Sets whether the y-axis is flipped.
+    pub fn with_flip_y(mut self, flip_y: bool) -> Self {
+        self.flip_y = flip_y;
+        self
+    }
+
+    /// Sets whether fills and strokes are imported.
+    pub fn with_paints(mut self, fills: bool, strokes: bool) -> Self {
+        self.fills = fills;
+        self.strokes = strokes;
+        self
+    }
+
+    /// Sets the distance along the z-axis between consecutive shapes.
+    pub fn with_layer_offset(mut self, offset: f32) -> Self {
+        self.layer_offset = offset;
+        self
+    }
+}
+
+/// Whether a shape of an SVG document is the fill or the stroke of a path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SvgPaint {
+    /// The interior of the path.
+    Fill,
+
+    /// The outline of the path.
+    Stroke,
+}
+
+/// The fill or stroke of a single path of an SVG document.
+#[derive(Clone, Debug)]
+pub struct SvgShape<T>
+where
+    T: IndexType,
+{
+    /// The id of the SVG element the path was created from. Empty if the element has no id.
+    pub id: String,
+
+    /// Whether the mesh is the fill or the stroke of the path.
+    pub paint: SvgPaint,
+
+    /// The color of the shape including its opacity.
+    pub color: LinearRgba,
+
+    /// The tessellated shape. Its vertex colors are set to `color`.
+    pub mesh: PMesh<T>,
+}
+
+/// Returns the color of a paint. Gradients are approximated by the average color of their stops.
+fn paint_color(paint: &usvg::Paint, opacity: f32) -> Option<LinearRgba> {
+    let linear = |c: usvg::Color| LinearRgba::from(Srgba::rgb_u8(c.red, c.green, c.blue));
+    let stops = match paint {
+        usvg::Paint::Color(c) => {
+            let mut color = linear(*c);
+            color.alpha = opacity;
+            return Some(color);
+        }
+        usvg::Paint::LinearGradient(g) => g.stops(),
+        usvg::Paint::RadialGradient(g) => g.stops(),
+        usvg::Paint::Pattern(_) => return None,
+    };
+    if stops.is_empty() {
+        return None;
+    }
+    let mut sum = [0.0; 4];
+    for stop in stops {
+        let c = linear(stop.color());
+        let values = [c.red, c.green, c.blue, stop.opacity().get()];
+        for (s, v) in sum.iter_mut().zip(values) {
+            *s += v / stops.len() as f32;
+        }
+    }
+    Some(LinearRgba::new(sum[0], sum[1], sum[2], sum[3] * opacity))
+}
+
+/// Adds the segments of a usvg path to the builder.
+fn add_segments<B: PathBuilder>(builder: &mut PBuilder<B>, path: &usvg::Path) {
+    let v = |p: usvg::tiny_skia_path::Point| Vec2::new(p.x, p.y);
+    let mut open = false;
+    let mut start = Vec2::ZERO;
+    let ensure_open = |builder: &mut PBuilder<B>, open: &mut bool, start: Vec2| {
+        if !*open {
+            builder.begin(start);
+            *open = true;
+        }
+    };
+    for segment in path.data().segments() {
+        match segment {
+            PathSegment::MoveTo(p) => {
+                if open {
+                    builder.end(false);
+                }
+                start = v(p);
+                builder.begin(start);
+                open = true;
+            }
+            PathSegment::LineTo(p) => {
+                ensure_open(builder, &mut open, start);
+                builder.line_to(v(p));
+            }
+            PathSegment::QuadTo(ctrl, p) => {
+                ensure_open(builder, &mut open, start);
+                builder.quadratic_bezier_to(v(ctrl), v(p));
+            }
+            PathSegment::CubicTo(ctrl1, ctrl2, p) => {
+                ensure_open(builder, &mut open, start);
+                builder.cubic_bezier_to(v(ctrl1), v(ctrl2), v(p));
+            }
+            PathSegment::Close => {
+                if open {
+                    builder.close();
+                    open = false;
+                }
+            }
+        }
+    }
+    if open {
+        builder.end(false);
+    }
+}
+
+/// Converts the style of a usvg stroke. The lengths are scaled by the given factor.
+fn stroke_options(stroke: &usvg::Stroke, tol: f32, scale: f32) -> PStrokeOptions {
+    let cap = match stroke.linecap() {
+        usvg::LineCap::Butt => LineCap::Butt,
+        usvg::LineCap::Round => LineCap::Round,
+        usvg::LineCap::Square => LineCap::Square,
+    };
+    let join = match stroke.linejoin() {
+        usvg::LineJoin::Miter => LineJoin::Miter,
+        usvg::LineJoin::MiterClip => LineJoin::MiterClip,
+        usvg::LineJoin::Round => LineJoin::Round,
+        usvg::LineJoin::Bevel => LineJoin::Bevel,
+    };
+    PStrokeOptions::new(stroke.width().get() * scale, tol)
+        .with_caps(cap)
+        .with_join(join)
+        .with_miter_limit(stroke.miterlimit().get())
+        .with_dashes(
+            stroke
+                .dasharray()
+                .map(|d| d.iter().map(|x| x * scale).collect())
+                .unwrap_or_default(),
+            stroke.dashoffset() * scale,
+        )
+}
+
+/// Collects the visible paths of the group and their accumulated opacity.
+fn collect_paths<'a>(group: &'a usvg::Group, opacity: f32, paths: &mut Vec<(&'a usvg::Path, f32)>) {
+    for node in group.children() {
+        match node {
+            usvg::Node::Group(g) => collect_paths(g, opacity * g.opacity().get(), paths),
+            usvg::Node::Path(p) if p.is_visible() => paths.push((p, opacity)),
+            _ => {}
+        }
+    }
+}
+
+impl<T> PMesh<T>
+where
+    T: Add + IndexType + From<VertexId> + MaxIndex,
+{
+    /// Imports the fills and strokes of all paths of an SVG document as separate meshes in painting order.
+    ///
+    /// Groups, transformations, and basic shapes like rectangles, circles, and polygons are supported.
+    /// Solid colors are used as they are, gradients are approximated by the average color of their stops,
+    /// and paints with patterns are skipped. Text, images, clip paths, masks, and filters are ignored.
+    ///
+    /// The data can be a plain or gzip-compressed SVG document.
+    pub fn svg_shapes(data: &[u8], options: &SvgOptions) -> Result<Vec<SvgShape<T>>, PMeshError> {
+        let tree = usvg::Tree::from_data(data, &usvg::Options::default())
+            .map_err(|e| PMeshError::Svg(e.to_string()))?;
+        let base = if options.flip_y {
+            Affine2::from_cols(Vec2::X, Vec2::NEG_Y, Vec2::new(0.0, tree.size().height()))
+        } else {
+            Affine2::IDENTITY
+        };
+
+        let mut paths = Vec::new();
+        collect_paths(tree.root(), 1.0, &mut paths);
+
+        let mut shapes = Vec::new();
+        for (path, opacity) in paths {
+            let t = path.abs_transform();
+            let transform = base
+                * Affine2::from_cols(
+                    Vec2::new(t.sx, t.ky),
+                    Vec2::new(t.kx, t.sy),
+                    Vec2::new(t.tx, t.ty),
+                );
+            // strokes are tessellated after the transformation, so their lengths are scaled by its mean scale
+            let scale = transform.matrix2.determinant().abs().sqrt();
+
+            let mut paints = [SvgPaint::Fill, SvgPaint::Stroke];
+            if path.paint_order() == usvg::PaintOrder::StrokeAndFill {
+                paints.reverse();
+            }
+            for paint in paints {
+                let (mesh, color) = match paint {
+                    SvgPaint::Fill => {
+                        let Some(fill) = path.fill().filter(|_| options.fills) else {
+                            continue;
+                        };
+                        let Some(color) = paint_color(fill.paint(), fill.opacity().get() * opacity)
+                        else {
+                            continue;
+                        };
+                        let rule = match fill.rule() {
+                            usvg::FillRule::NonZero => FillRule::NonZero,
+                            usvg::FillRule::EvenOdd => FillRule::EvenOdd,
+                        };
+                        let mut tessellator = PFill::<T>::with_options(
+                            PFillOptions::new(options.tolerance).with_fill_rule(rule),
+                        );
+                        tessellator.try_draw(|builder| {
+                            builder.set_transform(transform);
+                            add_segments(builder, path);
+                        })?;
+                        (tessellator.build(), color)
+                    }
+                    SvgPaint::Stroke => {
+                        let Some(stroke) = path.stroke().filter(|_| options.strokes) else {
+                            continue;
+                        };
+                        let Some(color) =
+                            paint_color(stroke.paint(), stroke.opacity().get() * opacity)
+                        else {
+                            continue;
+                        };
+                        let mut tessellator = PStroke::<T>::with_options(stroke_options(
+                            stroke,
+                            options.tolerance,
+                            scale,
+                        ));
+                        tessellator.try_draw_with_attributes(|builder| {
+                            builder.set_transform(transform);
+                            add_segments(builder, path);
+                        })?;
+                        (tessellator.build(), color)
+                    }
+                };
+                let mut mesh = mesh;
+                let n = mesh.get_vertices().len();
+                mesh.try_set_colors(vec![color.to_f32_array(); n])?;
+                shapes.push(SvgShape {
+                    id: path.id().to_string(),
+                    paint,
+                    color,
+                    mesh,
+                });
+            }
+        }
+        Ok(shapes)
+    }
+
+    /// Imports an SVG document as a single mesh with vertex colors. See `svg_shapes`.
+    ///
+    /// The shapes are appended in painting order, each `layer_offset` in front of the previous one.
+    pub fn from_svg(data: &[u8], options: &SvgOptions) -> Result<PMesh<T>, PMeshError> {
+        let mut mesh = PMesh::new();
+        for (i, shape) in PMesh::<T>::svg_shapes(data, options)?
+            .iter_mut()
+            .enumerate()
+        {
+            shape
+                .mesh
+                .translate(0.0, 0.0, i as f32 * options.layer_offset);
+            mesh.extend(&shape.mesh);
+        }
+        Ok(mesh)
+    }
+
+    /// Reads an SVG document from a file and imports it as a single mesh with vertex colors. See `from_svg`.
+    pub fn from_svg_file(
+        path: impl AsRef<std::path::Path>,
+        options: &SvgOptions,
+    ) -> Result<PMesh<T>, PMeshError> {
+        let data = std::fs::read(path.as_ref()).map_err(|e| {
+            PMeshError::Svg(format!("can't read {}: {}", path.as_ref().display(), e))
+        })?;
+        PMesh::from_svg(&data, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+  <defs>
+    <linearGradient id="gradient">
+      <stop offset="0" stop-color="#ff0000"/>
+      <stop offset="1" stop-color="#0000ff"/>
+    </linearGradient>
+  </defs>
+  <g transform="translate(40 10) scale(2)">
+    <rect id="rect" x="10" y="10" width="20" height="10" fill="#00ff00"/>
+  </g>
+  <path id="line" d="M10 80 L50 80" fill="none" stroke="#0000ff" stroke-width="4"/>
+  <circle id="circle" cx="20" cy="20" r="5" fill="url(#gradient)"/>
+</svg>"##;
+
+    /// Returns the minimum and maximum of the x and y coordinates.
+    fn bounds(mesh: &PMesh<u32>) -> [f32; 4] {
+        mesh.vertices.get_vertices().iter().fold(
+            [f32::MAX, f32::MAX, f32::MIN, f32::MIN],
+            |[x0, y0, x1, y1], v| [x0.min(v[0]), y0.min(v[1]), x1.max(v[0]), y1.max(v[1])],
+        )
+    }
+
+    fn assert_close(a: [f32; 4], b: [f32; 4]) {
+        assert!(
+            a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-3),
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn transforms_strokes_and_gradients() {
+        let shapes = PMesh::<u32>::svg_shapes(DOCUMENT.as_bytes(), &SvgOptions::new(0.01)).unwrap();
+        let ids: Vec<(&str, SvgPaint)> = shapes.iter().map(|s| (s.id.as_str(), s.paint)).collect();
+        assert_eq!(
+            ids,
+            [
+                ("rect", SvgPaint::Fill),
+                ("line", SvgPaint::Stroke),
+                ("circle", SvgPaint::Fill)
+            ]
+        );
+
+        // the rectangle covers x in [60, 100] and y in [30, 50], i.e., [50, 70] after flipping the y-axis
+        assert_close(bounds(&shapes[0].mesh), [60.0, 50.0, 100.0, 70.0]);
+        assert_eq!(shapes[0].color, LinearRgba::new(0.0, 1.0, 0.0, 1.0));
+        // the stroke is 4 units wide with butt caps
+        assert_close(bounds(&shapes[1].mesh), [10.0, 18.0, 50.0, 22.0]);
+        assert_eq!(shapes[1].color, LinearRgba::new(0.0, 0.0, 1.0, 1.0));
+        assert_close(bounds(&shapes[2].mesh), [15.0, 75.0, 25.0, 85.0]);
+        // the gradient is approximated by the average of its stops
+        assert_eq!(shapes[2].color, LinearRgba::new(0.5, 0.0, 0.5, 1.0));
+        for shape in &shapes {
+            let colors = shape.mesh.get_colors().unwrap();
+            assert_eq!(colors.len(), shape.mesh.vertices.len());
+            assert!(colors.iter().all(|c| *c == shape.color.to_f32_array()));
+        }
+
+        let unflipped = PMesh::<u32>::svg_shapes(
+            DOCUMENT.as_bytes(),
+            &SvgOptions::new(0.01)
+                .with_flip_y(false)
+                .with_paints(true, false),
+        )
+        .unwrap();
+        assert_eq!(unflipped.len(), 2);
+        assert_close(bounds(&unflipped[0].mesh), [60.0, 30.0, 100.0, 50.0]);
+    }
+}
@@ -0,0 +1,371 @@
+use super::super::{OrPanic, PMeshError};
+use super::{PBuilder, PathBuilder};
+use bevy::math::Vec2;
+
+/// Reads numbers, flags, and commands of SVG path data.
+struct Tokens<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn error(&self, message: &'static str) -> PMeshError {
+        PMeshError::SvgPath {
+            position: self.position,
+            message,
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while self
+            .data
+            .get(self.position)
+            .is_some_and(|c| c.is_ascii_whitespace() || *c == b',')
+        {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_separators();
+        self.data.get(self.position).copied()
+    }
+
+    /// Returns the next command letter if the next token is one.
+    fn command(&mut self) -> Option<u8> {
+        let c = self.peek().filter(|c| c.is_ascii_alphabetic())?;
+        self.position += 1;
+        Some(c)
+    }
+
+    fn number(&mut self) -> Result<f32, PMeshError> {
+        self.skip_separators();
+        let start = self.position;
+        let digits = |tokens: &mut Self| {
+            let from = tokens.position;
+            while tokens
+                .data
+                .get(tokens.position)
+                .is_some_and(u8::is_ascii_digit)
+            {
+                tokens.position += 1;
+            }
+            tokens.position > from
+        };
+        if matches!(self.data.get(self.position), Some(b'+' | b'-')) {
+            self.position += 1;
+        }
+        let mut valid = digits(self);
+        if self.data.get(self.position) == Some(&b'.') {
+            self.position += 1;
+            valid |= digits(self);
+        }
+        if !valid {
+            self.position = start;
+            return Err(self.error("expected a number"));
+        }
+        if matches!(self.data.get(self.position), Some(b'e' | b'E')) {
+            let mantissa_end = self.position;
+            self.position += 1;
+            if matches!(self.data.get(self.position), Some(b'+' | b'-')) {
+                self.position += 1;
+            }
+            if !digits(self) {
+                self.position = mantissa_end;
+            }
+        }
+        std::str::from_utf8(&self.data[start..self.position])
+            .ok()
+            .and_then(|s| s.parse::<f32>().ok())
+            .ok_or_else(|| self.error("invalid number"))
+    }
+
+    fn point(&mut self) -> Result<Vec2, PMeshError> {
+        Ok(Vec2::new(self.number()?, self.number()?))
+    }
+
+    /// Reads an arc flag, which doesn't need a separator before the next token.
+    fn flag(&mut self) -> Result<bool, PMeshError> {
+        match self.peek() {
+            Some(b'0') => {
+                self.position += 1;
+                Ok(false)
+            }
+            Some(b'1') => {
+                self.position += 1;
+                Ok(true)
+            }
+            _ => Err(self.error("expected a flag")),
+        }
+    }
+}
+
+/// The state of the path while reading SVG path data.
+#[derive(Default)]
+struct SvgState {
+    current: Vec2,
+    start: Vec2,
+    open: bool,
+    /// The last control points of cubic and quadratic curves for the smooth variants.
+    cubic_ctrl: Option<Vec2>,
+    quadratic_ctrl: Option<Vec2>,
+}
+
+impl<T> PBuilder<T>
+where
+    T: PathBuilder,
+{
+    /// Adds the sub-paths of SVG path data, e.g., the `d` attribute of a `<path>` element.
+    /// Panics if the path data is invalid. See `try_svg_path`.
+    ///
+    /// There must be no sub-path in progress when this method is called.
+    /// No sub-path is in progress after the method is called.
+    pub fn svg_path(&mut self, d: &str) -> &mut Self {
+        self.try_svg_path(d).or_panic();
+        self
+    }
+
+    /// Adds the sub-paths of SVG path data, e.g., the `d` attribute of a `<path>` element.
+    ///
+    /// Supports the absolute and relative `M`, `L`, `H`, `V`, `C`, `S`, `Q`, `T`, `A`, and `Z` commands.
+    /// The coordinates are transformed by the current transformation like all other commands,
+    /// so scale by `(1, -1)` to convert from the y-down coordinates of SVG.
+    ///
+    /// Returns an error at the first invalid token. The sub-paths before it are kept.
+    pub fn try_svg_path(&mut self, d: &str) -> Result<&mut Self, PMeshError> {
+        let mut tokens = Tokens {
+            data: d.as_bytes(),
+            position: 0,
+        };
+        let mut state = SvgState::default();
+        let mut previous: Option<u8> = None;
+        let result = loop {
+            tokens.skip_separators();
+            let start = tokens.position;
+            let command = match tokens.command() {
+                Some(c) => c,
+                None if tokens.peek().is_none() => break Ok(()),
+                // further coordinates repeat the previous command, where moves continue as lines
+                None => match previous {
+                    Some(b'M') => b'L',
+                    Some(b'm') => b'l',
+                    Some(c) if !matches!(c, b'Z' | b'z') => c,
+                    _ => break Err(tokens.error("expected a command")),
+                },
+            };
+            if previous.is_none() && !matches!(command, b'M' | b'm') {
+                tokens.position = start;
+                break Err(tokens.error("path data must start with a move"));
+            }
+            if let Err(e) = self.svg_command(&mut tokens, &mut state, command) {
+                break Err(e);
+            }
+            previous = Some(command);
+        };
+
+        if state.open {
+            self.end(false);
+        }
+        result.map(|_| self)
+    }
+
+    /// Starts a sub-path at the current point if there is none, e.g., after a close.
+    fn svg_ensure_open(&mut self, state: &mut SvgState) {
+        if !state.open {
+            self.begin(state.current);
+            state.start = state.current;
+            state.open = true;
+        }
+    }
+
+    /// Reads the arguments of a single SVG path command and adds it to the path.
+    fn svg_command(
+        &mut self,
+        tokens: &mut Tokens,
+        state: &mut SvgState,
+        command: u8,
+    ) -> Result<(), PMeshError> {
+        let origin = if command.is_ascii_lowercase() {
+            state.current
+        } else {
+            Vec2::ZERO
+        };
+        let (mut cubic_ctrl, mut quadratic_ctrl) = (None, None);
+        match command.to_ascii_uppercase() {
+            b'M' => {
+                let p = origin + tokens.point()?;
+                if state.open {
+                    self.end(false);
+                    state.open = false;
+                }
+                state.current = p;
+                self.svg_ensure_open(state);
+            }
+            b'L' => {
+                let p = origin + tokens.point()?;
+                self.svg_ensure_open(state);
+                self.line_to(p);
+                state.current = p;
+            }
+            b'H' => {
+                let p = Vec2::new(origin.x + tokens.number()?, state.current.y);
+                self.svg_ensure_open(state);
+                self.line_to(p);
+                state.current = p;
+            }
+            b'V' => {
+                let p = Vec2::new(state.current.x, origin.y + tokens.number()?);
+                self.svg_ensure_open(state);
+                self.line_to(p);
+                state.current = p;
+            }
+            b'C' | b'S' => {
+                let ctrl1 = if command.eq_ignore_ascii_case(&b'C') {
+                    origin + tokens.point()?
+                } else {
+                    state
+                        .cubic_ctrl
+                        .map_or(state.current, |c| 2.0 * state.current - c)
+                };
+                let ctrl2 = origin + tokens.point()?;
+                let p = origin + tokens.point()?;
+                self.svg_ensure_open(state);
+                self.cubic_bezier_to(ctrl1, ctrl2, p);
+                state.current = p;
+                cubic_ctrl = Some(ctrl2);
+            }
+            b'Q' | b'T' => {
+                let ctrl = if command.eq_ignore_ascii_case(&b'Q') {
+                    origin + tokens.point()?
+                } else {
+                    state
+                        .quadratic_ctrl
+                        .map_or(state.current, |c| 2.0 * state.current - c)
+                };
+                let p = origin + tokens.point()?;
+                self.svg_ensure_open(state);
+                self.quadratic_bezier_to(ctrl, p);
+                state.current = p;
+                quadratic_ctrl = Some(ctrl);
+            }
+            b'A' => {
+                let radii = Vec2::new(tokens.number()?, tokens.number()?);
+                let x_rotation = tokens.number()?;
+                let large_arc = tokens.flag()?;
+                let sweep = tokens.flag()?;
+                let p = origin + tokens.point()?;
+                self.svg_ensure_open(state);
+                self.endpoint_arc(
+                    state.current,
+                    radii,
+                    x_rotation.to_radians(),
+                    large_arc,
+                    sweep,
+                    p,
+                );
+                state.current = p;
+            }
+            b'Z' => {
+                if state.open {
+                    self.close();
+                    state.open = false;
+                }
+                state.current = state.start;
+            }
+            _ => {
+                // point at the command letter
+                tokens.position -= 1;
+                return Err(tokens.error("unknown command"));
+            }
+        }
+        state.cubic_ctrl = cubic_ctrl;
+        state.quadratic_ctrl = quadratic_ctrl;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lyon::path::{Event, Path};
+
+    fn events(d: &str) -> Vec<Event<lyon::math::Point, lyon::math::Point>> {
+        let mut builder = PBuilder::new(Path::builder());
+        builder.svg_path(d);
+        let path: Path = builder.build();
+        path.iter().collect()
+    }
+
+    fn error(d: &str) -> (usize, &'static str) {
+        let mut builder = PBuilder::new(Path::builder());
+        match builder.try_svg_path(d) {
+            Err(PMeshError::SvgPath { position, message }) => (position, message),
+            _ => panic!("expected an error for {:?}", d),
+        }
+    }
+
+    #[test]
+    fn implicit_repeats() {
+        assert_eq!(events("M0 0 1 0 1 1"), events("M0 0 L1 0 L1 1"));
+        assert_eq!(events("m1 1 1 0 0 1"), events("M1 1 L2 1 L2 2"));
+        assert_eq!(events("M0 0 L1 0 2 0 3 0"), events("M0 0 L1 0 L2 0 L3 0"));
+        assert_eq!(events("M0 0 H1 2 V1 2"), events("M0 0 H1 H2 V1 V2"));
+    }
+
+    #[test]
+    fn relative_commands() {
+        assert_eq!(
+            events("m1 1 l2 0 h1 v2 c0 1 1 1 1 0 q1 1 2 0 z m1 1 l1 0"),
+            events("M1 1 L3 1 H4 V3 C4 4 5 4 5 3 Q6 4 7 3 Z M2 2 L3 2")
+        );
+    }
+
+    #[test]
+    fn smooth_curves_reflect_the_control_points() {
+        assert_eq!(
+            events("M0 0 C0 1 1 1 1 0 S2 -1 2 0"),
+            events("M0 0 C0 1 1 1 1 0 C1 -1 2 -1 2 0")
+        );
+        assert_eq!(
+            events("M0 0 Q1 1 2 0 T4 0 t2 0"),
+            events("M0 0 Q1 1 2 0 Q3 -1 4 0 Q5 1 6 0")
+        );
+        // without a previous curve of the same kind, the control point is the current point
+        assert_eq!(
+            events("M0 0 L1 0 S2 1 3 0"),
+            events("M0 0 L1 0 C1 0 2 1 3 0")
+        );
+        assert_eq!(
+            events("M0 0 C0 1 1 1 1 0 T3 0"),
+            events("M0 0 C0 1 1 1 1 0 Q1 0 3 0")
+        );
+    }
+
+    #[test]
+    fn compact_arc_flags() {
+        assert_eq!(
+            events("M0 0 a1 1 0 00 10 10"),
+            events("M0 0 a1 1 0 0 0 10 10")
+        );
+        assert_eq!(
+            events("M0 0 a5,5 0 1,1 10,0"),
+            events("M0 0 A5 5 0 1 1 10 0")
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(events("M1e1-2.5E-1.5.5"), events("M10 -0.25 L0.5 0.5"));
+        assert_eq!(events("M+1,-1 L-.5e+1 2."), events("M1 -1 L-5 2"));
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(error("L0 0"), (0, "path data must start with a move"));
+        assert_eq!(error("M0 0 L1"), (7, "expected a number"));
+        assert_eq!(error("M0 0 L1 x"), (8, "expected a number"));
+        assert_eq!(error("M0 0 X1 1"), (5, "unknown command"));
+        assert_eq!(error("M0 0 Z 1 1"), (7, "expected a command"));
+        assert_eq!(error("M0 0 A1 1 0 2 0 1 1"), (12, "expected a flag"));
+    }
+}
@@ -0,0 +1,269 @@
+use super::super::{OrPanic, PMesh, PMeshError};
+use super::{
+    extrude::{extrude_into, ExtrudeBuffers},
+    fill::fill_into,
+    stroke::{stroke_into, stroke_solid_into, StrokeBuffers},
+    FillBuilder, PBuilder, PFillOptions, PStrokeOptions, PathBufferBuilder,
+    PathBufferBuilderWithAttributes, StrokeBuilder,
+};
+use crate::IndexType;
+use bevy::prelude::*;
+use lyon::{
+    path::{PathBuffer, PathSlice},
+    tessellation::{geometry_builder::MaxIndex, FillTessellator, StrokeTessellator, VertexId},
+};
+use std::{cell::RefCell, ops::Add};
+
+thread_local! {
+    /// The context used by the methods of `PMesh` that tessellate paths.
+    static CONTEXT: RefCell<PTessellator> = RefCell::new(PTessellator::new());
+}
+
+/// Keeps lyon's tessellators and their buffers alive, so shapes can be regenerated every frame without
+/// allocating them again.
+///
+/// Fills are tessellated while the path is drawn. Strokes and extruded shapes record the path in a
+/// reusable buffer first. The shapes are appended directly to the given mesh. Together with `PMesh::clear`, a mesh that is
+/// regenerated every frame reuses its memory, too. Insert it as a resource to share it between systems.
+#[derive(Resource)]
+pub struct PTessellator {
+    fill: FillTessellator,
+    stroke: StrokeTessellator,
+    /// The path drawn in the closure of strokes and extruded shapes.
+    path: PathBuffer,
+    strokes: StrokeBuffers,
+    extrusions: ExtrudeBuffers,
+}
+
+impl Default for PTessellator {
+    fn default() -> Self {
+        PTessellator::new()
+    }
+}
+
+impl PTessellator {
+    /// Creates a new tessellation context.
+    pub fn new() -> Self {
+        PTessellator {
+            fill: FillTessellator::new(),
+            stroke: StrokeTessellator::new(),
+            path: PathBuffer::new(),
+            strokes: StrokeBuffers::default(),
+            extrusions: ExtrudeBuffers::default(),
+        }
+    }
+
+    /// Calls `f` with the context of the current thread, so `PMesh::fill`, `PMesh::stroke`, and the like
+    /// don't allocate new tessellators for every shape. Calls nested inside a closure get a new context.
+    pub(super) fn with_thread_local<R>(f: impl FnOnce(&mut PTessellator) -> R) -> R {
+        CONTEXT.with(|context| match context.try_borrow_mut() {
+            Ok(mut context) => f(&mut context),
+            Err(_) => f(&mut PTessellator::new()),
+        })
+    }
+
+    /// The fill tessellator, e.g., for the caps of lathed shapes.
+    pub(super) fn fill_tessellator(&mut self) -> &mut FillTessellator {
+        &mut self.fill
+    }
+
+    /// Fills the path built in the closure with the given options and appends it to the mesh.
+    ///
+    /// Panics if the tessellation fails. See `try_fill_into`.
+    pub fn fill_into<T, F>(
+        &mut self,
+        mesh: &mut PMesh<T>,
+        options: &PFillOptions,
+        draw_commands: F,
+    ) -> &mut Self
+    where
+        T: Add + IndexType + From<VertexId> + MaxIndex,
+        F: FnOnce(&mut PBuilder<FillBuilder>),
+    {
+        self.try_fill_into(mesh, options, draw_commands).or_panic();
+        self
+    }
+
+    /// Fills the path built in the closure with the given options and appends it to the mesh.
+    /// Returns `PMeshError::Tessellation` if lyon can't tessellate the path or the vertices don't fit into
+    /// the index type.
+    pub fn try_fill_into<T, F>(
+        &mut self,
+        mesh: &mut PMesh<T>,
+        options: &PFillOptions,
+        draw_commands: F,
+    ) -> Result<&mut Self, PMeshError>
+    where
+        T: Add + IndexType + From<VertexId> + MaxIndex,
+        F: FnOnce(&mut PBuilder<FillBuilder>),
+    {
+        fill_into(&mut self.fill, options, mesh, draw_commands)?;
+        Ok(self)
+    }
+
+    /// Strokes the path built in the closure with lyon's stroke tessellator while it is drawn.
+    /// Dashes and variable widths are not supported. See `PStroke::try_draw`.
+    pub(super) fn try_stroke_solid_into<T, F>(
+        &mut self,
+        mesh: &mut PMesh<T>,
+        options: &PStrokeOptions,
+        draw_commands: F,
+    ) -> Result<&mut Self, PMeshError>
+    where
+        T: Add + IndexType + From<VertexId> + MaxIndex,
+        F: FnOnce(&mut PBuilder<StrokeBuilder>),
+    {
+        stroke_solid_into(&mut self.stroke, options, mesh, draw_commands)?;
+        Ok(self)
+    }
+
+    /// Strokes the path built in the closure with the given options and appends it to the mesh.
+    /// See `PStroke::try_draw_with_attributes`.
+    ///
+    /// Panics if the tessellation fails. See `try_stroke_into`.
+    pub fn stroke_into<T, F>(
+        &mut self,
+        mesh: &mut PMesh<T>,
+        options: &PStrokeOptions,
+        draw_commands: F,
+    ) -> &mut Self
+    where
+        T: Add + IndexType + From<VertexId> + MaxIndex,
+        F: FnOnce(&mut PBuilder<PathBufferBuilderWithAttributes<'_>>),
+    {
+        self.try_stroke_into(mesh, options, draw_commands)
+            .or_panic();
+        self
+    }
+
+    /// Strokes the path built in the closure with the given options and appends it to the mesh.
+    /// Returns `PMeshError::Tessellation` if lyon can't tessellate the path or the vertices don't fit into
+    /// the index type.
+    pub fn try_stroke_into<T, F>(
+        &mut self,
+        mesh: &mut PMesh<T>,
+        options: &PStrokeOptions,
+        draw_commands: F,
+    ) -> Result<&mut Self, PMeshError>
+    where
+        T: Add + IndexType + From<VertexId> + MaxIndex,
+        F: FnOnce(&mut PBuilder<PathBufferBuilderWithAttributes<'_>>),
+    {
+        stroke_into(
+            &mut self.stroke,
+            &mut self.path,
+            &mut self.strokes,
+            options,
+            mesh,
+            draw_commands,
+        )?;
+        Ok(self)
+    }
+
+    /// Fills the path built in the closure, extrudes it by `depth` along the z-axis, and appends it to the mesh.
+    /// See `PMesh::fill_extruded`.
+    ///
+    /// Panics if the mesh has vertices but no normals or if the tessellation fails. See `try_fill_extruded_into`.
+    pub fn fill_extruded_into<T, F>(
+        &mut self,
+        mesh: &mut PMesh<T>,
+        tol: f32,
+        depth: f32,
+        draw_commands: F,
+    ) -> &mut Self
+    where
+        T: IndexType,
+        F: FnOnce(&mut PBuilder<PathBufferBuilder<'_>>),
+    {
+        self.try_fill_extruded_into(mesh, tol, depth, draw_commands)
+            .or_panic();
+        self
+    }
+
+    /// Fills the path built in the closure, extrudes it by `depth` along the z-axis, and appends it to the mesh.
+    /// Returns `PMeshError::MissingAttribute` if the mesh has vertices but no normals and
+    /// `PMeshError::Tessellation` if lyon can't tessellate the caps.
+    pub fn try_fill_extruded_into<T, F>(
+        &mut self,
+        mesh: &mut PMesh<T>,
+        tol: f32,
+        depth: f32,
+        draw_commands: F,
+    ) -> Result<&mut Self, PMeshError>
+    where
+        T: IndexType,
+        F: FnOnce(&mut PBuilder<PathBufferBuilder<'_>>),
+    {
+        self.path.clear();
+        let mut builder = PBuilder::with_attributes(self.path.builder());
+        draw_commands(&mut builder);
+        builder.build();
+        extrude_into(
+            &mut self.fill,
+            &mut self.extrusions,
+            self.path.get(0),
+            tol,
+            depth,
+            mesh,
+        )?;
+        Ok(self)
+    }
+
+    /// Extrudes a closed path by `depth` along the z-axis and appends it to the mesh. See `PMesh::fill_extruded`.
+    pub(super) fn try_extrude_into<T>(
+        &mut self,
+        mesh: &mut PMesh<T>,
+        path: PathSlice,
+        tol: f32,
+        depth: f32,
+    ) -> Result<&mut Self, PMeshError>
+    where
+        T: IndexType,
+    {
+        extrude_into(&mut self.fill, &mut self.extrusions, path, tol, depth, mesh)?;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lyon::path::traits::PathBuilder;
+
+    fn square(builder: &mut PBuilder<impl PathBuilder>, size: f32) {
+        builder
+            .begin(Vec2::ZERO)
+            .line_to(Vec2::new(size, 0.0))
+            .line_to(Vec2::new(size, size))
+            .line_to(Vec2::new(0.0, size))
+            .close();
+    }
+
+    #[test]
+    fn mesh_methods_match_the_context() {
+        let mut expected = PMesh::<u32>::new();
+        let mut tessellator = PTessellator::new();
+        tessellator.fill_extruded_into(&mut expected, 0.01, 1.0, |b| square(b, 1.0));
+
+        let mut mesh = PMesh::<u32>::new();
+        mesh.fill_extruded(0.01, 1.0, |b| square(b, 1.0));
+        assert_eq!(
+            mesh.vertices.get_vertices(),
+            expected.vertices.get_vertices()
+        );
+        assert_eq!(mesh.indices.get_indices(), expected.indices.get_indices());
+    }
+
+    #[test]
+    fn nested_calls_get_their_own_context() {
+        let mut inner = PMesh::<u32>::new();
+        let mut mesh = PMesh::<u32>::new();
+        mesh.fill(0.01, |b| {
+            inner.fill(0.01, |b| square(b, 2.0));
+            square(b, 1.0);
+        });
+        assert_eq!(mesh.indices.len(), 6);
+        assert_eq!(inner.indices.len(), 6);
+        assert!(inner.vertices.get_vertices().iter().any(|v| v[0] == 2.0));
+    }
+}
@@ -0,0 +1,435 @@
+use super::super::PMeshError;
+use super::{PBuilder, PathBuilder};
+use bevy::math::Vec2;
+use ttf_parser::{gpos::PairAdjustment, gpos::PositioningSubtable, Face, GlyphId, Tag};
+
+/// How the lines of a text are aligned horizontally relative to the origin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TextAlign {
+    /// The lines start at the origin.
+    #[default]
+    Left,
+
+    /// The lines are centered around the origin.
+    Center,
+
+    /// The lines end at the origin.
+    Right,
+}
+
+/// Options for laying out text with `PFont::layout` or `PBuilder::add_text`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PTextOptions {
+    /// The size of the em square in world units.
+    pub size: f32,
+
+    /// The distance between the baselines of consecutive lines relative to the line height of the font.
+    pub line_height: f32,
+
+    /// Additional space between consecutive glyphs in world units.
+    pub letter_spacing: f32,
+
+    /// Whether the distance between pairs of glyphs is adjusted by the kerning of the font.
+    pub kerning: bool,
+
+    /// How the lines are aligned horizontally.
+    pub align: TextAlign,
+
+    /// The maximum width of a line in world units. Longer lines are wrapped at whitespace.
+    /// Words that are longer than the maximum width get a line on their own.
+    pub max_width: Option<f32>,
+}
+
+impl Default for PTextOptions {
+    fn default() -> Self {
+        PTextOptions {
+            size: 1.0,
+            line_height: 1.0,
+            letter_spacing: 0.0,
+            kerning: true,
+            align: TextAlign::Left,
+            max_width: None,
+        }
+    }
+}
+
+impl PTextOptions {
+    /// Creates text options with the given size of the em square.
+    pub fn new(size: f32) -> Self {
+        PTextOptions {
+            size,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the distance between the baselines relative to the line height of the font.
+    pub fn with_line_height(mut self, line_height: f32) -> Self {
+        self.line_height = line_height;
+        self
+    }
+
+    /// Sets the additional space between consecutive glyphs.
+    pub fn with_letter_spacing(mut self, spacing: f32) -> Self {
+        self.letter_spacing = spacing;
+        self
+    }
+
+    /// Sets whether the kerning of the font is applied.
+    pub fn with_kerning(mut self, kerning: bool) -> Self {
+        self.kerning = kerning;
+        self
+    }
+
+    /// Sets the horizontal alignment of the lines.
+    pub fn with_align(mut self, align: TextAlign) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// Sets the maximum width of a line.
+    pub fn with_max_width(mut self, max_width: f32) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+}
+
+/// A glyph placed by `PFont::layout`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PGlyph {
+    /// The character the glyph was created from.
+    pub character: char,
+
+    /// The index of the glyph in the font.
+    pub glyph: u16,
+
+    /// The origin of the glyph on the baseline in world units.
+    pub position: Vec2,
+
+    /// The horizontal advance of the glyph in world units without kerning and letter spacing.
+    pub advance: f32,
+}
+
+/// A TrueType or OpenType font.
+#[derive(Clone, Debug)]
+pub struct PFont {
+    data: Vec<u8>,
+    index: u32,
+}
+
+impl PFont {
+    /// Parses a TrueType or OpenType font. For font collections, the first font is used.
+    pub fn from_bytes(data: impl Into<Vec<u8>>) -> Result<Self, PMeshError> {
+        PFont::from_collection(data, 0)
+    }
+
+    /// Parses the font with the given index of a font collection.
+    pub fn from_collection(data: impl Into<Vec<u8>>, index: u32) -> Result<Self, PMeshError> {
+        let data = data.into();
+        Face::parse(&data, index).map_err(|e| PMeshError::Font(e.to_string()))?;
+        Ok(PFont { data, index })
+    }
+
+    fn face(&self) -> Face<'_> {
+        // the data was validated when the font was created
+        Face::parse(&self.data, self.index).unwrap()
+    }
+
+    /// Returns the distance between the baselines of consecutive lines in world units.
+    pub fn line_height(&self, options: &PTextOptions) -> f32 {
+        line_height(&self.face(), options)
+    }
+
+    /// Places the glyphs of the text. Lines are separated by line breaks and, if the options have a maximum
+    /// width, wrapped at whitespace.
+    ///
+    /// The first line starts on the x-axis and the following lines go down along the negative y-axis.
+    /// Characters that aren't in the font get the glyph for missing characters.
+    pub fn layout(&self, text: &str, options: &PTextOptions) -> Vec<PGlyph> {
+        layout(&self.face(), text, options)
+    }
+}
+
+/// See `PFont::line_height`.
+fn line_height(face: &Face, options: &PTextOptions) -> f32 {
+    let height = face.ascender() as f32 - face.descender() as f32 + face.line_gap() as f32;
+    height * options.size / face.units_per_em() as f32 * options.line_height
+}
+
+/// See `PFont::layout`.
+fn layout(face: &Face, text: &str, options: &PTextOptions) -> Vec<PGlyph> {
+    let scale = options.size / face.units_per_em() as f32;
+    let line_height = line_height(face, options);
+    let kerning = options.kerning.then(|| Kerning::new(face));
+
+    let mut glyphs = Vec::new();
+    let mut line = 0;
+    for paragraph in text.split('\n') {
+        let paragraph = paragraph.strip_suffix('\r').unwrap_or(paragraph);
+
+        // place the glyphs of the paragraph on a single line
+        let mut placed: Vec<PGlyph> = Vec::new();
+        let mut x = 0.0;
+        let mut previous = None;
+        for character in paragraph.chars() {
+            let id = face.glyph_index(character).unwrap_or(GlyphId(0));
+            if let Some(previous) = previous {
+                let kerning = kerning.as_ref().map_or(0.0, |k| k.get(face, previous, id));
+                x += kerning * scale + options.letter_spacing;
+            }
+            let advance = face.glyph_hor_advance(id).unwrap_or(0) as f32 * scale;
+            placed.push(PGlyph {
+                character,
+                glyph: id.0,
+                position: Vec2::new(x, 0.0),
+                advance,
+            });
+            x += advance;
+            previous = Some(id);
+        }
+
+        for range in wrap(&placed, options.max_width) {
+            let glyphs_of_line = &placed[range];
+            let start = glyphs_of_line.first().map_or(0.0, |g| g.position.x);
+            let width = glyphs_of_line
+                .iter()
+                .rev()
+                .find(|g| !g.character.is_whitespace())
+                .map_or(0.0, |g| g.position.x + g.advance - start);
+            let offset = match options.align {
+                TextAlign::Left => 0.0,
+                TextAlign::Center => -width * 0.5,
+                TextAlign::Right => -width,
+            };
+            glyphs.extend(glyphs_of_line.iter().map(|g| PGlyph {
+                position: Vec2::new(g.position.x - start + offset, -(line as f32) * line_height),
+                ..*g
+            }));
+            line += 1;
+        }
+    }
+    glyphs
+}
+
+/// Splits a line of glyphs into lines that are at most `max_width` wide, breaking at whitespace.
+/// The whitespace at the breaks is dropped.
+fn wrap(glyphs: &[PGlyph], max_width: Option<f32>) -> Vec<std::ops::Range<usize>> {
+    let max_width = max_width.unwrap_or(f32::INFINITY);
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut last_break = None;
+    let mut i = 0;
+    while i < glyphs.len() {
+        let g = &glyphs[i];
+        if g.character.is_whitespace() {
+            // break before the first whitespace of a run
+            if i > start && !glyphs[i - 1].character.is_whitespace() {
+                last_break = Some(i);
+            }
+        } else if let Some(b) = last_break {
+            if g.position.x + g.advance - glyphs[start].position.x > max_width {
+                lines.push(start..b);
+                start = b + 1;
+                while start < i && glyphs[start].character.is_whitespace() {
+                    start += 1;
+                }
+                last_break = None;
+                // check the glyph again on the new line
+                continue;
+            }
+        }
+        i += 1;
+    }
+    lines.push(start..glyphs.len());
+    lines
+}
+
+/// Looks up the kerning of pairs of glyphs in the `GPOS` table or, if the font has no kerning there,
+/// in the `kern` table.
+struct Kerning {
+    /// The indices of the lookups of the `kern` feature in the `GPOS` table.
+    lookups: Vec<u16>,
+}
+
+impl Kerning {
+    fn new(face: &Face) -> Self {
+        let mut lookups = Vec::new();
+        if let Some(gpos) = face.tables().gpos {
+            let tag = Tag::from_bytes(b"kern");
+            for feature in (0..gpos.features.len()).filter_map(|i| gpos.features.get(i)) {
+                if feature.tag == tag {
+                    lookups.extend(feature.lookup_indices);
+                }
+            }
+        }
+        lookups.sort_unstable();
+        lookups.dedup();
+        Kerning { lookups }
+    }
+
+    /// Returns the kerning between the glyphs in font units.
+    fn get(&self, face: &Face, left: GlyphId, right: GlyphId) -> f32 {
+        if self.lookups.is_empty() {
+            return face.tables().kern.map_or(0.0, |kern| {
+                kern.subtables
+                    .into_iter()
+                    .filter(|s| s.horizontal && !s.variable && !s.has_cross_stream)
+                    .filter_map(|s| s.glyphs_kerning(left, right))
+                    .map(|k| k as f32)
+                    .sum()
+            });
+        }
+        let Some(gpos) = face.tables().gpos else {
+            return 0.0;
+        };
+        let mut kerning = 0.0;
+        for lookup in self.lookups.iter().filter_map(|i| gpos.lookups.get(*i)) {
+            // only the first subtable that contains the pair applies
+            let pair = lookup
+                .subtables
+                .into_iter::<PositioningSubtable>()
+                .find_map(|subtable| match subtable {
+                    PositioningSubtable::Pair(pair) => pair_kerning(&pair, left, right),
+                    _ => None,
+                });
+            kerning += pair.unwrap_or(0) as f32;
+        }
+        kerning
+    }
+}
+
+/// Returns the advance adjustment of the first glyph of the pair, or `None` if the subtable doesn't contain the pair.
+fn pair_kerning(pair: &PairAdjustment, left: GlyphId, right: GlyphId) -> Option<i16> {
+    match pair {
+        PairAdjustment::Format1 { coverage, sets } => {
+            let set = sets.get(coverage.get(left)?)?;
+            Some(set.get(right)?.0.x_advance)
+        }
+        PairAdjustment::Format2 {
+            coverage,
+            classes,
+            matrix,
+        } => {
+            if !coverage.contains(left) {
+                return None;
+            }
+            let (first, second) = (classes.0.get(left), classes.1.get(right));
+            Some(matrix.get((first, second))?.0.x_advance)
+        }
+    }
+}
+
+/// Forwards the outline of a glyph to a `PBuilder`.
+struct GlyphOutline<'a, B: PathBuilder> {
+    builder: &'a mut PBuilder<B>,
+    open: bool,
+}
+
+impl<B: PathBuilder> ttf_parser::OutlineBuilder for GlyphOutline<'_, B> {
+    fn move_to(&mut self, x: f32, y: f32) {
+        if self.open {
+            self.builder.end(false);
+        }
+        self.builder.begin(Vec2::new(x, y));
+        self.open = true;
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.builder.line_to(Vec2::new(x, y));
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.builder
+            .quadratic_bezier_to(Vec2::new(x1, y1), Vec2::new(x, y));
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.builder
+            .cubic_bezier_to(Vec2::new(x1, y1), Vec2::new(x2, y2), Vec2::new(x, y));
+    }
+
+    fn close(&mut self) {
+        if self.open {
+            self.builder.close();
+            self.open = false;
+        }
+    }
+}
+
+impl<B: PathBuilder> PBuilder<B> {
+    /// Adds the transformed outlines of the text laid out with the given options. See `PFont::layout`.
+    ///
+    /// Fill the outlines using `FillRule::NonZero` if the font has overlapping contours, e.g., variable fonts.
+    pub fn add_text(&mut self, font: &PFont, text: &str, options: &PTextOptions) -> &mut Self {
+        let face = font.face();
+        for glyph in layout(&face, text, options) {
+            self.add_outline(&face, glyph.glyph, glyph.position, options.size);
+        }
+        self
+    }
+
+    /// Adds the transformed outline of the glyph with the given index.
+    /// `position` is the origin of the glyph on the baseline and `size` the size of the em square.
+    pub fn add_glyph(&mut self, font: &PFont, glyph: u16, position: Vec2, size: f32) -> &mut Self {
+        self.add_outline(&font.face(), glyph, position, size)
+    }
+
+    /// Like `add_glyph`, but uses an already parsed font.
+    fn add_outline(&mut self, face: &Face, glyph: u16, position: Vec2, size: f32) -> &mut Self {
+        self.push()
+            .translate(position)
+            .scale_uniform(size / face.units_per_em() as f32);
+        let mut outline = GlyphOutline {
+            builder: self,
+            open: false,
+        };
+        face.outline_glyph(GlyphId(glyph), &mut outline);
+        if outline.open {
+            outline.builder.end(false);
+        }
+        self.pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Places every character of the text one unit after the previous one.
+    fn glyphs(text: &str) -> Vec<PGlyph> {
+        text.chars()
+            .enumerate()
+            .map(|(i, character)| PGlyph {
+                character,
+                glyph: 0,
+                position: Vec2::new(i as f32, 0.0),
+                advance: 1.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn wrap_breaks_before_whitespace() {
+        assert_eq!(wrap(&glyphs("ab cd"), Some(3.0)), vec![0..2, 3..5]);
+        assert_eq!(wrap(&glyphs("ab cd"), Some(5.0)), vec![0..5]);
+        assert_eq!(wrap(&glyphs("ab cd"), None), vec![0..5]);
+        assert_eq!(wrap(&glyphs("a b c d"), Some(3.0)), vec![0..3, 4..7]);
+    }
+
+    #[test]
+    fn wrap_skips_whitespace_at_the_start_of_lines() {
+        assert_eq!(wrap(&glyphs("ab   cd"), Some(3.0)), vec![0..2, 5..7]);
+        assert_eq!(
+            wrap(&glyphs("ab \t cd ef"), Some(4.0)),
+            vec![0..2, 5..7, 8..10]
+        );
+    }
+
+    #[test]
+    fn wrap_puts_long_words_on_their_own_line() {
+        assert_eq!(
+            wrap(&glyphs("a bcdefg h"), Some(3.0)),
+            vec![0..1, 2..8, 9..10]
+        );
+        assert_eq!(wrap(&glyphs("abcdef"), Some(3.0)), vec![0..6]);
+    }
+}
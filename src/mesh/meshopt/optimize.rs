@@ -93,6 +93,17 @@ fn mesh_opt_complete(mesh: &mut MeshoptMesh, settings: &MeshoptSettings) {
     }
 
     if settings.optimize_vertex_fetch {
+        // vertices are reordered by their first occurrence in the index buffer
+        let mut seen = vec![false; mesh.vertices.len()];
+        let mut origin = Vec::new();
+        for i in &mesh.indices {
+            if !seen[*i as usize] {
+                seen[*i as usize] = true;
+                origin.push(mesh.origin[*i as usize]);
+            }
+        }
+        mesh.origin = origin;
+
         let final_size =
             meshopt::optimize_vertex_fetch_in_place(&mut mesh.indices, &mut mesh.vertices);
         mesh.vertices.resize(final_size, Default::default());
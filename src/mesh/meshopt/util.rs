@@ -1,4 +1,4 @@
-use super::super::{indices::PIndices, vertices::PVertices, IndexType, PMesh};
+use super::super::{indices::PIndices, vertices::PVertices, IndexType, PAttributes, PMesh};
 use memoffset::offset_of;
 use meshopt::{typed_to_bytes, VertexDataAdapter};
 
@@ -14,6 +14,8 @@ pub fn get_adapter(vertices: &Vec<meshopt::Vertex>) -> VertexDataAdapter {
 pub struct MeshoptMesh {
     pub vertices: Vec<meshopt::Vertex>,
     pub indices: Vec<u32>,
+    /// The index of the original vertex of the `PMesh` for each vertex. Used to restore the attributes.
+    pub origin: Vec<u32>,
 }
 
 impl<T> PMesh<T>
@@ -30,8 +32,8 @@ where
                     self.vertices[i][1],
                     self.vertices[i][2],
                 ],
-                n: [0.0, 0.0, 0.0],
-                t: [0.0, 0.0],
+                n: self.normals.as_ref().map_or([0.0, 0.0, 0.0], |n| n[i]),
+                t: self.uv.as_ref().map_or([0.0, 0.0], |uv| uv[i]),
             });
         }
 
@@ -42,11 +44,27 @@ where
             .map(|x| x.index() as u32)
             .collect();
 
-        return MeshoptMesh { vertices, indices };
+        let origin = (0..self.vertices.len() as u32).collect();
+
+        return MeshoptMesh {
+            vertices,
+            indices,
+            origin,
+        };
     }
 
     /// Imports the meshopt data into the mesh.
+    /// The uvs, normals, and attributes are taken from the original vertices given by `mesh.origin`.
     pub fn import_meshopt_data(&mut self, mesh: &MeshoptMesh) {
+        let origin: Vec<usize> = mesh.origin.iter().map(|i| *i as usize).collect();
+        if origin.len() == mesh.vertices.len() && origin.iter().all(|i| *i < self.vertices.len()) {
+            self.gather_vertices(&origin);
+        } else {
+            self.uv = None;
+            self.normals = None;
+            self.attributes = PAttributes::new();
+        }
+
         self.vertices = PVertices::build(
             mesh.vertices
                 .iter()
@@ -54,7 +72,48 @@ where
                 .collect(),
         );
         self.indices = PIndices::build(mesh.indices.iter().map(|x| T::new(*x as usize)).collect());
-        self.normals = None;
-        self.uv = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::MeshoptSettings;
+    use super::*;
+    use bevy::{
+        mesh::{MeshVertexAttribute, VertexAttributeValues},
+        render::render_resource::VertexFormat,
+    };
+
+    const ATTRIBUTE_X: MeshVertexAttribute =
+        MeshVertexAttribute::new("Vertex_X", 988_540_917, VertexFormat::Float32);
+
+    fn assert_aligned(mesh: &PMesh<u32>) {
+        let Some(VertexAttributeValues::Float32(x)) = mesh.get_attribute(ATTRIBUTE_X) else {
+            panic!("the custom attribute is missing");
+        };
+        assert_eq!(x.len(), mesh.vertices.len());
+        for (i, x) in x.iter().enumerate() {
+            assert_eq!(*x, mesh.vertices[i][0]);
+        }
+    }
+
+    #[test]
+    fn custom_attributes_follow_the_origin() {
+        let mut mesh = PMesh::<u32>::cuboid(1.0, 2.0, 3.0, 1);
+        let x: Vec<f32> = (0..mesh.vertices.len())
+            .map(|i| mesh.vertices[i][0])
+            .collect();
+        mesh.set_attribute(ATTRIBUTE_X, x);
+
+        let mut data = mesh.to_meshopt_data();
+        let n = data.vertices.len() as u32;
+        data.vertices.reverse();
+        data.origin.reverse();
+        data.indices.iter_mut().for_each(|i| *i = n - 1 - *i);
+        mesh.import_meshopt_data(&data);
+        assert_aligned(&mesh);
+
+        mesh.mesh_opt(&MeshoptSettings::default());
+        assert_aligned(&mesh);
     }
 }
@@ -2,16 +2,37 @@
 
 use super::IndexType;
 use bevy::prelude::*;
+mod attributes;
+mod error;
+mod extend;
 mod indices;
 mod vertices;
+pub use attributes::PAttributes;
+pub(crate) use error::OrPanic;
+pub use error::PMeshError;
+pub use extend::MissingAttributes;
 pub use indices::PIndices;
+pub use normals::{NormalWeighting, SmoothNormalsSettings};
+pub use sweep::PPath3;
+pub use unwrap::UnwrapSettings;
+pub use uv::UvProjection;
 pub use vertices::PVertices;
+pub use weld::WeldSettings;
 mod backend_bevy;
 mod geometry;
 mod iter;
+mod loft;
 mod normals;
 mod operator;
+mod profile;
 mod shapes;
+mod sweep;
+mod tangents;
+mod transform;
+mod unwrap;
+mod uv;
+mod validate;
+mod weld;
 //mod optimize;
 
 #[cfg(feature = "meshopt")]
@@ -21,9 +42,15 @@ pub mod meshopt;
 pub mod lyon;
 
 #[cfg(feature = "lyon")]
-pub use lyon::Winding;
-
-/// A mesh with vertices, indices of type T, uv coordinates and normals.
+pub use lyon::{
+    FillRule, LineCap, LineJoin, PFillOptions, PStrokeOptions, PTessellator, UvMapping, Winding,
+};
+#[cfg(feature = "svg")]
+pub use lyon::{SvgOptions, SvgPaint, SvgShape};
+#[cfg(feature = "text")]
+pub use lyon::{PFont, PGlyph, PTextOptions, TextAlign};
+
+/// A mesh with vertices, indices of type T, uv coordinates, normals, and additional vertex attributes.
 ///
 /// It will always use a triangle list topology, because on most hardware,
 /// indexed triangle lists are more efficient than triangle strips (see meshopt-rs).
@@ -33,11 +60,11 @@ pub struct PMesh<T>
 where
     T: IndexType,
 {
-    // TODO: allow a dynamic number of attributes
     vertices: PVertices,
     indices: PIndices<T>,
     uv: Option<Vec<[f32; 2]>>,
     normals: Option<Vec<[f32; 3]>>,
+    attributes: PAttributes,
 }
 
 impl<T> Default for PMesh<T>
@@ -61,6 +88,7 @@ where
             indices: PIndices::<T>::new(),
             uv: Some(Vec::new()),
             normals: None,
+            attributes: PAttributes::new(),
         }
     }
 
@@ -104,20 +132,10 @@ where
         self.normals.as_mut()
     }
 
-    /// Removes all duplicate indices by duplicating the vertices, uvs, and normals and replacing the indices with a linear sequence.
+    /// Removes all duplicate indices by duplicating the vertices, uvs, normals, and attributes and replacing the indices with a linear sequence.
     pub fn duplicate(&mut self) -> &mut PMesh<T> {
-        self.vertices = PVertices::build(
-            self.indices
-                .iter_usize()
-                .map(|i| self.vertices[i])
-                .collect(),
-        );
-        if let Some(uv) = &self.uv {
-            self.uv = Some(self.indices.iter_usize().map(|i| uv[i]).collect());
-        }
-        if let Some(normals) = &self.normals {
-            self.normals = Some(self.indices.iter_usize().map(|i| normals[i]).collect());
-        }
+        let corners: Vec<usize> = self.indices.iter_usize().collect();
+        self.gather_vertices(&corners);
         self.indices
             .reset_to_interval(T::new(0), T::new(self.indices.len()));
         self
@@ -128,30 +146,55 @@ where
         Vec3::from(self.vertices.vec(i))
     }
 
-    /// Creates a PMesh from vertices, indices, uvs, normals, and the topology.
+    /// Creates a `PMesh` from vertices, indices, uvs, and normals.
+    ///
+    /// Panics if the indices or the lengths of the uvs and normals are invalid. See `try_build_ex`.
     pub fn build_ex(
         vertices: Vec<[f32; 3]>,
         indices: Vec<T>,
         uv: Option<Vec<[f32; 2]>>,
         normals: Option<Vec<[f32; 3]>>,
     ) -> Self {
-        if let Some(uv) = &uv {
-            assert!(vertices.len() == uv.len());
-        }
-        if let Some(normals) = &normals {
-            assert!(vertices.len() == normals.len());
-        }
-        PMesh {
+        PMesh::try_build_ex(vertices, indices, uv, normals).or_panic()
+    }
+
+    /// Creates a `PMesh` from vertices, indices, uvs, and normals.
+    /// Returns an error if the indices or the lengths of the uvs and normals are invalid.
+    pub fn try_build_ex(
+        vertices: Vec<[f32; 3]>,
+        indices: Vec<T>,
+        uv: Option<Vec<[f32; 2]>>,
+        normals: Option<Vec<[f32; 3]>>,
+    ) -> Result<Self, PMeshError> {
+        let mesh = PMesh {
             vertices: PVertices::build(vertices),
             indices: PIndices::build(indices),
             uv,
             normals,
-        }
+            attributes: PAttributes::new(),
+        };
+        mesh.validate_structure()?;
+        Ok(mesh)
     }
 
     /// Creates a TriangleList-PMesh from vertices, indices, and uvs and converts the indices to the given index type.
+    ///
+    /// Panics if the indices or the length of the uvs are invalid. See `try_build`.
     pub fn build(vertices: Vec<[f32; 3]>, indices: Vec<u32>, uv: Option<Vec<[f32; 2]>>) -> Self {
-        PMesh::build_ex(
+        PMesh::try_build(vertices, indices, uv).or_panic()
+    }
+
+    /// Creates a TriangleList-PMesh from vertices, indices, and uvs and converts the indices to the given index type.
+    /// Returns an error if the indices or the length of the uvs are invalid.
+    pub fn try_build(
+        vertices: Vec<[f32; 3]>,
+        indices: Vec<u32>,
+        uv: Option<Vec<[f32; 2]>>,
+    ) -> Result<Self, PMeshError> {
+        if let Some(i) = indices.iter().find(|i| **i as usize > <T as IndexType>::max().index()) {
+            return Err(PMeshError::IndexTypeOverflow(*i as usize));
+        }
+        PMesh::try_build_ex(
             vertices,
             indices.iter().map(|i| T::new(*i as usize)).collect(),
             uv,
@@ -159,85 +202,19 @@ where
         )
     }
 
-    /// Appends another mesh to this one.
-    pub fn extend(&mut self, m: &PMesh<T>) -> &mut PMesh<T> {
-        let offset = self.vertices.len();
-        self.vertices.extend(&m.vertices);
-        self.indices
-            .extend(m.clone().indices.map(|i: T| i.add(T::new(offset))));
-        if let Some(uv) = &mut self.uv {
-            if let Some(uv2) = &m.uv {
-                uv.extend(uv2);
-            } else {
-                // TODO: println!("WARN: Appending mesh without uv");
-                self.uv = None;
-            }
+    /// Removes all vertices, indices, uv coordinates, normals, and attribute values but keeps the allocated memory,
+    /// so the mesh can be refilled without allocating. Whether the mesh has uv coordinates, normals, and which
+    /// attributes doesn't change.
+    pub fn clear(&mut self) -> &mut PMesh<T> {
+        self.vertices.get_vertices_mut().clear();
+        self.indices.get_indices_mut().clear();
+        if let Some(uv) = self.uv.as_mut() {
+            uv.clear();
         }
-        self
-    }
-
-    /// Rotates the mesh around the y-axis.
-    pub fn rotate_y(&mut self, angle: f32) -> &mut PMesh<T> {
-        self.vertices.rotate_y(angle);
-        self
-    }
-
-    /// Translates the mesh.
-    pub fn translate(&mut self, x: f32, y: f32, z: f32) -> &mut PMesh<T> {
-        self.vertices.translate(x, y, z);
-        self
-    }
-
-    /// Scales the mesh.
-    pub fn scale(&mut self, x: f32, y: f32, z: f32) -> &mut PMesh<T> {
-        self.vertices.scale(x, y, z);
-        self
-    }
-
-    /// Scales the mesh uniformly.
-    pub fn scale_uniform(&mut self, x: f32) -> &mut PMesh<T> {
-        self.vertices.scale(x, x, x);
-        self
-    }
-
-    /// Tries to introduce more shared indices and remove empty triangles.
-    /// UV maps might be broken.
-    pub fn optimize(&mut self) -> &mut PMesh<T> {
-        let mut new_indices = Vec::new();
-        let mut new_vertices = Vec::new();
-        let mut new_uv = Vec::new();
-        let eps = 0.0001;
-
-        for i in self.indices.iter_usize() {
-            let v = self.vertices[i];
-
-            if let Some(index) = new_vertices
-                .iter()
-                .position(|v2| Vec3::from(v).distance(Vec3::from(*v2)) < eps)
-            {
-                new_indices.push(T::new(index));
-            } else {
-                new_indices.push(T::new(new_vertices.len()));
-                new_vertices.push(v);
-                if let Some(v) = &self.uv {
-                    new_uv.push(v[i]);
-                }
-            }
+        if let Some(normals) = self.normals.as_mut() {
+            normals.clear();
         }
-
-        /*println!(
-            "Optimized {} vertices to {}",
-            self.vertices.len(),
-            new_vertices.len()
-        );*/
-
-        self.vertices = PVertices::build(new_vertices);
-        self.indices = PIndices::build(new_indices);
-        self.uv = match new_uv.len() > 0 {
-            true => Some(new_uv),
-            false => None,
-        };
-
+        self.attributes.resize(0);
         self
     }
 
@@ -247,11 +224,9 @@ where
         self
     }
 
-    /// Flips the y and z coordinates of the vertices.
-    ///
-    /// This is useful to place a mesh "lying on the ground" in a 3D scene.
-    pub fn flip_yz(&mut self) -> &mut PMesh<T> {
-        self.vertices.flip_yz();
+    /// Flips the winding order of all triangles, i.e., turns the mesh inside out.
+    pub fn flip_winding(&mut self) -> &mut PMesh<T> {
+        self.indices.flip_winding();
         self
     }
 }
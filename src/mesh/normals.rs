@@ -1,78 +1,329 @@
-use super::{IndexType, PMesh};
+use super::{IndexType, PIndices, PMesh};
 use bevy::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::PI,
+};
+
+/// How the normals of the adjacent faces are weighted when averaging them at a vertex.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NormalWeighting {
+    /// Every face has the same weight.
+    Uniform,
+
+    /// Faces are weighted by their angle at the vertex.
+    #[default]
+    Angle,
+
+    /// Faces are weighted by their area.
+    Area,
+
+    /// Faces are weighted by their angle at the vertex and their area.
+    AngleArea,
+}
+
+/// Settings for `PMesh::auto_smooth`.
+#[derive(Clone, Debug)]
+pub struct SmoothNormalsSettings {
+    /// Edges between faces whose normals differ by more than this angle (in radians) stay sharp.
+    pub crease_angle: f32,
+
+    /// How the normals of the adjacent faces are weighted.
+    pub weighting: NormalWeighting,
+
+    /// Edges that always stay sharp, given by the indices of their two vertices.
+    pub hard_edges: Vec<[usize; 2]>,
+
+    /// Vertices closer than this distance are treated as being at the same position.
+    pub epsilon: f32,
+}
+
+impl Default for SmoothNormalsSettings {
+    fn default() -> Self {
+        SmoothNormalsSettings {
+            crease_angle: PI / 6.0,
+            weighting: NormalWeighting::Angle,
+            hard_edges: Vec::new(),
+            epsilon: 0.0001,
+        }
+    }
+}
+
+impl SmoothNormalsSettings {
+    /// The settings of `PMesh::flat_normals`.
+    pub(crate) fn flat() -> Self {
+        // a tiny crease angle only joins faces that are coplanar up to rounding errors
+        SmoothNormalsSettings {
+            crease_angle: 0.001,
+            weighting: NormalWeighting::Area,
+            ..Default::default()
+        }
+    }
+
+    /// Creates settings with the given crease angle in radians.
+    pub fn new(crease_angle: f32) -> Self {
+        SmoothNormalsSettings {
+            crease_angle,
+            ..Default::default()
+        }
+    }
+
+    /// Sets how the normals of the adjacent faces are weighted.
+    pub fn with_weighting(mut self, weighting: NormalWeighting) -> Self {
+        self.weighting = weighting;
+        self
+    }
+
+    /// Marks the edge between the two vertices as sharp.
+    pub fn with_hard_edge(mut self, a: usize, b: usize) -> Self {
+        self.hard_edges.push([a, b]);
+        self
+    }
+}
 
 impl<T> PMesh<T>
 where
     T: IndexType,
 {
-    /// Calculates the normals of the mesh.
+    /// Calculates flat normals, i.e., every face gets its own normal.
+    ///
+    /// Works on indexed meshes: vertices are only split where the adjacent faces are not coplanar,
+    /// so flat regions keep sharing their vertices.
     pub fn flat_normals(&mut self) -> &mut PMesh<T> {
-        assert!(
-            self.indices.len() == self.vertices.len(),
-            "requires duplicated vertices"
-        );
-
-        self.normals = Some(
-            self.iter_faces()
-                .map(|[v1, v2, v3]| {
-                    (self.vec3_at(v2) - self.vec3_at(v1))
-                        .cross(self.vec3_at(v3) - self.vec3_at(v1))
-                        .normalize()
-                        .into()
-                })
-                .flat_map(|n| [n; 3])
-                .collect(),
-        );
+        self.auto_smooth(&SmoothNormalsSettings::flat())
+    }
 
+    /// Calculates smooth normals by averaging the normals of the adjacent faces, weighted by their angle
+    /// at the vertex and, if `area_weighting` is set, by their area.
+    ///
+    /// Vertices at the same position are smoothed together, so seams in the uv map don't show up as hard edges.
+    /// The vertices and indices are not changed. See `auto_smooth` to keep sharp edges.
+    pub fn smooth_normals(&mut self, area_weighting: bool) -> &mut PMesh<T> {
+        let weighting = if area_weighting {
+            NormalWeighting::AngleArea
+        } else {
+            NormalWeighting::Angle
+        };
+        self.normals = Some(self.accumulated_normals(weighting, 0.0001));
         self
     }
 
-    /// Calculates the normals of the mesh with smoothing based on https://stackoverflow.com/a/45496726/6144727
+    /// Returns one normal per vertex by summing up the weighted normals of all faces
+    /// at vertices with the same position. Never splits vertices.
+    fn accumulated_normals(&self, weighting: NormalWeighting, epsilon: f32) -> Vec<[f32; 3]> {
+        let groups = self.position_groups(epsilon);
+        let faces: Vec<[usize; 3]> = self.iter_faces().collect();
+        let (face_normals, weights) = self.face_weights(&faces, weighting);
+        let mut sums = vec![Vec3::ZERO; self.vertices.len()];
+        for (f, face) in faces.iter().enumerate() {
+            for (k, v) in face.iter().enumerate() {
+                sums[groups[*v]] += face_normals[f] * weights[f * 3 + k];
+            }
+        }
+        groups
+            .iter()
+            .map(|g| sums[*g].normalize_or_zero().to_array())
+            .collect()
+    }
+
+    /// Returns the normals of the faces and the weights of their corners.
+    fn face_weights(
+        &self,
+        faces: &[[usize; 3]],
+        weighting: NormalWeighting,
+    ) -> (Vec<Vec3>, Vec<f32>) {
+        let mut face_normals = Vec::with_capacity(faces.len());
+        let mut weights = Vec::with_capacity(faces.len() * 3);
+        for [a, b, c] in faces {
+            let p = [self.vec3_at(*a), self.vec3_at(*b), self.vec3_at(*c)];
+            let cross = (p[1] - p[0]).cross(p[2] - p[0]);
+            face_normals.push(cross.normalize_or_zero());
+            for k in 0..3 {
+                let e1 = p[(k + 1) % 3] - p[k];
+                let e2 = p[(k + 2) % 3] - p[k];
+                let angle = if e1.length_squared() > 0.0 && e2.length_squared() > 0.0 {
+                    e1.angle_between(e2)
+                } else {
+                    0.0
+                };
+                let area = cross.length() * 0.5;
+                weights.push(match weighting {
+                    NormalWeighting::Uniform => 1.0,
+                    NormalWeighting::Angle => angle,
+                    NormalWeighting::Area => area,
+                    NormalWeighting::AngleArea => angle * area,
+                });
+            }
+        }
+        (face_normals, weights)
+    }
+
+    /// Calculates smooth normals but keeps the edges between faces whose normals differ by more than
+    /// the crease angle and the marked hard edges sharp. See `SmoothNormalsSettings`.
     ///
-    /// WARNING: This might be buggy
-    pub fn smooth_normals(&mut self, area_weighting: bool) -> &mut PMesh<T> {
-        // TODO: this looks a bit buggy on screen. Not sure whether it's a problem with the normals or the normal interpolation/color grading in the shader.
-
-        let mut normals = vec![Vec3::ZERO; self.vertices.len()];
-        for [f1, f2, f3] in self.iter_faces() {
-            let v1 = self.vec3_at(f1);
-            let v2 = self.vec3_at(f2);
-            let v3 = self.vec3_at(f3);
-
-            // calculate facet normal of the triangle using cross product;
-            // both components are "normalized" against a common point chosen as the base
-            let normal = (v2 - v1).cross(v3 - v1);
-
-            let normal = if area_weighting {
-                normal
-            } else {
-                normal.normalize()
-            };
-
-            // get the angle between the two other points for each point;
-            // the starting point will be the 'base' and the two adjacent points will be normalized against it
-            let angle1 = (v2 - v1).angle_between(v3 - v1);
-            let angle2 = (v3 - v2).angle_between(v1 - v2);
-            let angle3 = (v1 - v3).angle_between(v2 - v3);
-
-            normals[f1] += normal * angle1;
-            normals[f2] += normal * angle2;
-            normals[f3] += normal * angle3;
-        }
-
-        self.normals = Some(
-            normals
-                .iter()
-                .map(|n| {
-                    let n = n.normalize();
-                    [n.x, n.y, n.z]
-                })
-                .collect(),
-        );
+    /// Vertices on sharp edges are split. Vertices at the same position that are connected by smooth
+    /// edges get the same normal, even if they are separate vertices, e.g., because of a uv seam.
+    /// Without hard edges and with a crease angle of at least `PI`, nothing is split, like in `smooth_normals`.
+    pub fn auto_smooth(&mut self, settings: &SmoothNormalsSettings) -> &mut PMesh<T> {
+        if settings.crease_angle >= PI && settings.hard_edges.is_empty() {
+            self.normals = Some(self.accumulated_normals(settings.weighting, settings.epsilon));
+            return self;
+        }
 
+        let n = self.vertices.len();
+        let normals = self.normals.take().unwrap_or_else(|| vec![[0.0; 3]; n]);
+        let (kept, indices, normals) = self.split_normals(settings, normals);
+        if kept.len() > n {
+            self.gather_vertices(&kept);
+        }
+        self.indices = PIndices::build(indices);
+        self.normals = Some(normals);
         self
     }
 
+    /// Computes the normals of `auto_smooth` without modifying the mesh. `normals` is reused for the
+    /// normals of the existing vertices.
+    ///
+    /// Returns the original vertex of every vertex, the new indices, and the normals.
+    pub(crate) fn split_normals(
+        &self,
+        settings: &SmoothNormalsSettings,
+        mut normals: Vec<[f32; 3]>,
+    ) -> (Vec<usize>, Vec<T>, Vec<[f32; 3]>) {
+        let groups = self.position_groups(settings.epsilon);
+        let faces: Vec<[usize; 3]> = self.iter_faces().collect();
+        let (face_normals, weights) = self.face_weights(&faces, settings.weighting);
+
+        // collect the faces at every edge between two positions
+        let mut edges: HashMap<(usize, usize), Vec<(usize, usize, usize)>> = HashMap::new();
+        for (f, face) in faces.iter().enumerate() {
+            for k in 0..3 {
+                let (k1, k2) = (k, (k + 1) % 3);
+                let (g1, g2) = (groups[face[k1]], groups[face[k2]]);
+                if g1 == g2 {
+                    continue;
+                }
+                let (key, corners) = if g1 < g2 {
+                    ((g1, g2), (f, k1, k2))
+                } else {
+                    ((g2, g1), (f, k2, k1))
+                };
+                edges.entry(key).or_default().push(corners);
+            }
+        }
+        let hard: HashSet<(usize, usize)> = settings
+            .hard_edges
+            .iter()
+            .filter_map(|[a, b]| Some((*groups.get(*a)?, *groups.get(*b)?)))
+            .map(|(a, b)| (a.min(b), a.max(b)))
+            .collect();
+
+        // join the corners of faces that meet at smooth edges
+        let cos_crease = settings.crease_angle.min(PI).cos();
+        let is_smooth = |f1: usize, f2: usize| {
+            let (n1, n2): (Vec3, Vec3) = (face_normals[f1], face_normals[f2]);
+            n1 == Vec3::ZERO || n2 == Vec3::ZERO || n1.dot(n2) >= cos_crease
+        };
+        let mut parent: Vec<usize> = (0..faces.len() * 3).collect();
+        fn find(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        // the pairs of corners that are separated by a sharp edge, by position
+        let mut sharp: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+        for (key, list) in &edges {
+            let is_hard = hard.contains(key);
+            for (i, (f1, a1, b1)) in list.iter().enumerate() {
+                for (f2, a2, b2) in &list[i + 1..] {
+                    let pairs = [(f1 * 3 + a1, f2 * 3 + a2), (f1 * 3 + b1, f2 * 3 + b2)];
+                    if is_hard || !is_smooth(*f1, *f2) {
+                        sharp.entry(key.0).or_default().push(pairs[0]);
+                        sharp.entry(key.1).or_default().push(pairs[1]);
+                        continue;
+                    }
+                    for (c1, c2) in pairs {
+                        let (r1, r2) = (find(&mut parent, c1), find(&mut parent, c2));
+                        parent[r1] = r2;
+                    }
+                }
+            }
+        }
+
+        // sum up the weighted face normals of each cluster of corners
+        let mut sums = vec![Vec3::ZERO; faces.len() * 3];
+        for c in 0..faces.len() * 3 {
+            let r = find(&mut parent, c);
+            sums[r] += face_normals[c / 3] * weights[c];
+        }
+
+        // Clusters that only touch at a single position, e.g., the two fans of a bowtie, aren't separated
+        // by a crease. Join them if they aren't separated by a sharp edge and their normals are close enough.
+        let mut corners_at: HashMap<usize, Vec<usize>> = HashMap::new();
+        for c in 0..faces.len() * 3 {
+            corners_at
+                .entry(groups[faces[c / 3][c % 3]])
+                .or_default()
+                .push(c);
+        }
+        for (g, corners) in &corners_at {
+            let mut roots: Vec<usize> = corners.iter().map(|c| find(&mut parent, *c)).collect();
+            roots.sort_unstable();
+            roots.dedup();
+            for i in 0..roots.len() {
+                for j in i + 1..roots.len() {
+                    let (r1, r2) = (find(&mut parent, roots[i]), find(&mut parent, roots[j]));
+                    if r1 == r2 {
+                        continue;
+                    }
+                    let separated = sharp.get(g).is_some_and(|pairs| {
+                        pairs.iter().any(|(c1, c2)| {
+                            let (s1, s2) = (find(&mut parent, *c1), find(&mut parent, *c2));
+                            (s1, s2) == (r1, r2) || (s1, s2) == (r2, r1)
+                        })
+                    });
+                    let (n1, n2) = (sums[r1].normalize_or_zero(), sums[r2].normalize_or_zero());
+                    let smooth = n1 == Vec3::ZERO || n2 == Vec3::ZERO || n1.dot(n2) >= cos_crease;
+                    if !separated && smooth {
+                        parent[r1] = r2;
+                        let sum = sums[r1];
+                        sums[r2] += sum;
+                    }
+                }
+            }
+        }
+
+        // every vertex keeps its index for the first cluster; further clusters get new vertices
+        let n = self.vertices.len();
+        normals.resize(n, [0.0; 3]);
+        let mut assigned: Vec<Option<usize>> = vec![None; n];
+        let mut splits: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut kept: Vec<usize> = (0..n).collect();
+        let mut indices = Vec::with_capacity(faces.len() * 3);
+        for (f, face) in faces.iter().enumerate() {
+            for (k, v) in face.iter().enumerate() {
+                let r = find(&mut parent, f * 3 + k);
+                let index = match assigned[*v] {
+                    None => {
+                        assigned[*v] = Some(r);
+                        normals[*v] = sums[r].normalize_or_zero().to_array();
+                        *v
+                    }
+                    Some(r0) if r0 == r => *v,
+                    Some(_) => *splits.entry((*v, r)).or_insert_with(|| {
+                        kept.push(*v);
+                        normals.push(sums[r].normalize_or_zero().to_array());
+                        kept.len() - 1
+                    }),
+                };
+                indices.push(T::new(index));
+            }
+        }
+
+        (kept, indices, normals)
+    }
     /*
     /// Assume the shape is (roughly) a 2D-polygon without holes.
     /// Detects the outline and sorts the vertices clockwise.
@@ -163,3 +414,117 @@ where
         res
     }*/
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::WeldSettings;
+    use bevy::{
+        asset::RenderAssetUsages, mesh::VertexAttributeValues,
+        render::render_resource::PrimitiveTopology,
+    };
+
+    /// Two triangles that only touch at the vertex 0.
+    fn bowtie() -> PMesh<u32> {
+        PMesh::build(
+            vec![
+                [0.0, 0.0, 0.0],
+                [1.0, -1.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [-1.0, 1.0, 0.0],
+                [-1.0, -1.0, 0.0],
+            ],
+            vec![0, 1, 2, 0, 3, 4],
+            None,
+        )
+    }
+
+    #[test]
+    fn smooth_normals_keep_the_vertices_of_a_bowtie() {
+        let mut mesh = bowtie();
+        mesh.smooth_normals(false);
+        assert_eq!(mesh.vertices.len(), 5);
+        assert_eq!(mesh.indices.len(), 6);
+        assert_eq!(mesh.normals, Some(vec![[0.0, 0.0, 1.0]; 5]));
+        assert!(mesh.validate().is_ok());
+
+        let mut mesh = bowtie();
+        mesh.auto_smooth(&SmoothNormalsSettings::default());
+        assert_eq!(mesh.vertices.len(), 5);
+        assert!(mesh.validate().is_ok());
+
+        assert_eq!(bowtie().computed_normals().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn flat_normals_only_split_at_edges() {
+        let mut mesh = PMesh::<u32>::cuboid(1.0, 1.0, 1.0, 1);
+        mesh.weld(&WeldSettings::positions_only(0.0001));
+        mesh.normals = None;
+        assert_eq!(mesh.vertices.len(), 8);
+
+        let mut bevy_mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        );
+        mesh.bevy_set(&mut bevy_mesh);
+        assert_eq!(
+            bevy_mesh
+                .attribute(Mesh::ATTRIBUTE_POSITION)
+                .map(|v| v.len()),
+            Some(24)
+        );
+        assert!(mesh.normals.is_none());
+
+        mesh.flat_normals();
+        assert_eq!(mesh.vertices.len(), 24);
+        assert_eq!(mesh.indices.len(), 36);
+        let normals = mesh.normals.as_ref().unwrap();
+        for [a, b, c] in mesh.iter_faces() {
+            let (pa, pb, pc) = (mesh.vec3_at(a), mesh.vec3_at(b), mesh.vec3_at(c));
+            let face = (pb - pa).cross(pc - pa).normalize();
+            for v in [a, b, c] {
+                assert!(Vec3::from(normals[v]).distance(face) < 1e-5);
+            }
+        }
+        let Some(VertexAttributeValues::Float32x3(bevy_normals)) =
+            bevy_mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+        else {
+            panic!("the bevy mesh has no normals");
+        };
+        assert_eq!(bevy_normals, normals);
+    }
+
+    #[test]
+    fn auto_smooth_splits_at_creases() {
+        // two triangles folded by 90 degrees along the edge between the vertices 0 and 1
+        let mut mesh = PMesh::<u32>::build(
+            vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [0.0, 0.0, 1.0],
+            ],
+            vec![0, 1, 2, 0, 3, 1],
+            None,
+        );
+        mesh.auto_smooth(&SmoothNormalsSettings::default());
+        assert_eq!(mesh.vertices.len(), 6);
+        assert!(mesh.validate().is_ok());
+
+        // a bowtie with a fold at the shared vertex stays split
+        let mut mesh = PMesh::<u32>::build(
+            vec![
+                [0.0, 0.0, 0.0],
+                [1.0, -1.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [-1.0, 0.0, 1.0],
+                [-1.0, 0.0, -1.0],
+            ],
+            vec![0, 1, 2, 0, 3, 4],
+            None,
+        );
+        mesh.auto_smooth(&SmoothNormalsSettings::default());
+        assert_eq!(mesh.vertices.len(), 6);
+    }
+}
@@ -0,0 +1,201 @@
+//! Helpers for 2D profiles that are revolved, swept or lofted into 3D meshes.
+
+use bevy::math::Vec2;
+use std::f32::consts::PI;
+
+/// Profile corners sharper than this angle get split normals.
+pub(crate) const CREASE_ANGLE: f32 = PI / 6.0;
+
+/// A point of a profile together with its normal and its arc length along the profile.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ProfileSample {
+    pub position: Vec2,
+    pub normal: Vec2,
+    pub arc_len: f32,
+}
+
+/// Computes the samples along the profile. The normals point to the right of the drawing direction.
+///
+/// Corners sharper than `CREASE_ANGLE` are split into two samples at the same position.
+/// Closed profiles repeat their first point at the end.
+pub(crate) fn profile_samples(points: &[Vec2], closed: bool) -> Vec<ProfileSample> {
+    let n = points.len();
+    if n < 2 {
+        return Vec::new();
+    }
+    let num_segments = if closed { n } else { n - 1 };
+    let segment_normal = |k: usize| {
+        let d = points[(k + 1) % n] - points[k];
+        Vec2::new(d.y, -d.x).normalize_or_zero()
+    };
+
+    let mut samples = Vec::new();
+    let mut arc_len = 0.0;
+    for k in 0..=num_segments {
+        let position = points[k % n];
+        if k > 0 {
+            arc_len += position.distance(points[k - 1]);
+        }
+        let incoming = (closed || k > 0).then(|| segment_normal((k + n - 1) % n));
+        let outgoing = (closed || k < num_segments).then(|| segment_normal(k % n));
+        match (incoming, outgoing) {
+            (Some(i), Some(o)) if i.angle_to(o).abs() > CREASE_ANGLE => {
+                // the first sample of a closed profile only needs the outgoing and the last one the incoming normal
+                if k > 0 {
+                    samples.push(ProfileSample {
+                        position,
+                        normal: i,
+                        arc_len,
+                    });
+                }
+                if k < num_segments {
+                    samples.push(ProfileSample {
+                        position,
+                        normal: o,
+                        arc_len,
+                    });
+                }
+            }
+            (i, o) => samples.push(ProfileSample {
+                position,
+                normal: (i.unwrap_or(Vec2::ZERO) + o.unwrap_or(Vec2::ZERO)).normalize_or_zero(),
+                arc_len,
+            }),
+        }
+    }
+    samples
+}
+
+/// Returns the signed area of the polygon. It is positive if the polygon is counter-clockwise.
+pub(crate) fn signed_area(polygon: &[Vec2]) -> f32 {
+    let mut area = 0.0;
+    for i in 0..polygon.len() {
+        let a = polygon[i];
+        let b = polygon[(i + 1) % polygon.len()];
+        area += a.perp_dot(b);
+    }
+    area * 0.5
+}
+
+/// Triangulates a simple polygon using ear clipping. The triangles are always counter-clockwise.
+pub(crate) fn triangulate_polygon(points: &[Vec2]) -> Vec<[usize; 3]> {
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    if signed_area(points) < 0.0 {
+        remaining.reverse();
+    }
+
+    let mut triangles = Vec::new();
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let corner = |i: usize| {
+            let (a, b, c) = (
+                remaining[(i + m - 1) % m],
+                remaining[i],
+                remaining[(i + 1) % m],
+            );
+            (
+                a,
+                b,
+                c,
+                (points[b] - points[a]).perp_dot(points[c] - points[a]),
+            )
+        };
+        let ear = (0..m).find(|&i| {
+            let (a, b, c, area) = corner(i);
+            area > 0.0
+                && remaining.iter().all(|&j| {
+                    j == a
+                        || j == b
+                        || j == c
+                        || !in_triangle(points[j], points[a], points[b], points[c])
+                })
+        });
+        if let Some(i) = ear {
+            let (a, b, c, _) = corner(i);
+            triangles.push([a, b, c]);
+            remaining.remove(i);
+        } else if let Some(i) = (0..m).find(|&i| corner(i).3.abs() <= f32::EPSILON) {
+            // drop collinear points
+            remaining.remove(i);
+        } else {
+            // the polygon is self-intersecting
+            break;
+        }
+    }
+    if remaining.len() == 3 {
+        triangles.push([remaining[0], remaining[1], remaining[2]]);
+    }
+    triangles
+}
+
+fn in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    (b - a).perp_dot(p - a) >= 0.0
+        && (c - b).perp_dot(p - b) >= 0.0
+        && (a - c).perp_dot(p - c) >= 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: [Vec2; 4] = [
+        Vec2::new(0.0, 0.0),
+        Vec2::new(1.0, 0.0),
+        Vec2::new(1.0, 1.0),
+        Vec2::new(0.0, 1.0),
+    ];
+
+    #[test]
+    fn corners_are_split() {
+        // counter-clockwise, so the normals point outwards
+        let samples = profile_samples(&SQUARE, true);
+        // the first and last corner only get one sample each
+        assert_eq!(samples.len(), 8);
+        assert_eq!(samples[0].position, samples[7].position);
+        assert_eq!(samples[0].normal, Vec2::new(0.0, -1.0));
+        assert_eq!(samples[7].normal, Vec2::new(-1.0, 0.0));
+        assert_eq!(samples[1].position, samples[2].position);
+        assert_eq!(samples[1].normal, Vec2::new(0.0, -1.0));
+        assert_eq!(samples[2].normal, Vec2::new(1.0, 0.0));
+        assert_eq!(samples[7].arc_len, 4.0);
+
+        let samples = profile_samples(&SQUARE[..2], false);
+        assert_eq!(samples.len(), 2);
+        assert!(profile_samples(&SQUARE[..1], false).is_empty());
+    }
+
+    #[test]
+    fn smooth_profiles_are_not_split() {
+        let circle: Vec<Vec2> = (0..32)
+            .map(|i| Vec2::from_angle(i as f32 / 32.0 * 2.0 * PI))
+            .collect();
+        let samples = profile_samples(&circle, true);
+        assert_eq!(samples.len(), 33);
+        for s in &samples {
+            // the normals to the right of a counter-clockwise circle point outwards
+            assert!((s.normal - s.position).length() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn polygons_are_triangulated_counter_clockwise() {
+        let l_shape = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.0, 2.0),
+            Vec2::new(1.0, 2.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(2.0, 1.0),
+            Vec2::new(2.0, 0.0),
+        ];
+        assert_eq!(signed_area(&l_shape), -3.0);
+        let triangles = triangulate_polygon(&l_shape);
+        assert_eq!(triangles.len(), 4);
+        let mut area = 0.0;
+        for [a, b, c] in triangles {
+            let doubled = (l_shape[b] - l_shape[a]).perp_dot(l_shape[c] - l_shape[a]);
+            assert!(doubled > 0.0);
+            area += doubled * 0.5;
+        }
+        assert!((area - 3.0).abs() < 1e-5);
+    }
+}
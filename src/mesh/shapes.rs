@@ -2,6 +2,7 @@
 
 use super::{IndexType, PMesh};
 use bevy::prelude::*;
+use std::{collections::HashMap, f32::consts::PI};
 
 impl<T> PMesh<T>
 where
@@ -81,6 +82,308 @@ where
             Some(vec![[0.0, 0.0], [1.0, 0.0], [0.5, 1.0]]),
         )
     }
+
+    /// Creates a box centered at the origin where each face is subdivided into `segments` x `segments` quads.
+    ///
+    /// It is called `cuboid` like bevy's primitive because `box` is a reserved keyword.
+    pub fn cuboid(width: f32, height: f32, depth: f32, segments: usize) -> PMesh<T> {
+        let segments = segments.max(1);
+        let size = Vec3::new(width, height, depth);
+        let mut shape = ShapeBuffers::default();
+
+        // (normal, right, down) for each face such that down x right = normal
+        let faces = [
+            (Vec3::Z, Vec3::X, Vec3::NEG_Y),
+            (Vec3::NEG_Z, Vec3::NEG_X, Vec3::NEG_Y),
+            (Vec3::X, Vec3::NEG_Z, Vec3::NEG_Y),
+            (Vec3::NEG_X, Vec3::Z, Vec3::NEG_Y),
+            (Vec3::Y, Vec3::X, Vec3::Z),
+            (Vec3::NEG_Y, Vec3::X, Vec3::NEG_Z),
+        ];
+
+        for (normal, right, down) in faces {
+            let offset = shape.vertices.len() as u32;
+            let center = normal * normal.abs().dot(size) * 0.5;
+            let right_len = right.abs().dot(size);
+            let down_len = down.abs().dot(size);
+            for r in 0..=segments {
+                let v = r as f32 / segments as f32;
+                for s in 0..=segments {
+                    let u = s as f32 / segments as f32;
+                    let p = center + right * (u - 0.5) * right_len + down * (v - 0.5) * down_len;
+                    shape.push(p, [u, v], normal);
+                }
+            }
+            shape.push_grid(offset, segments, segments, false, false);
+        }
+
+        shape.build()
+    }
+
+    /// Creates a sphere centered at the origin with `sectors` longitudinal and `stacks` latitudinal segments.
+    pub fn uv_sphere(radius: f32, sectors: usize, stacks: usize) -> PMesh<T> {
+        let sectors = sectors.max(3);
+        let stacks = stacks.max(2);
+        let mut shape = ShapeBuffers::default();
+
+        for r in 0..=stacks {
+            let v = r as f32 / stacks as f32;
+            let phi = v * PI;
+            for s in 0..=sectors {
+                let u = s as f32 / sectors as f32;
+                let n = ring_point(phi.sin(), phi.cos(), u * 2.0 * PI);
+                shape.push(n * radius, [u, v], n);
+            }
+        }
+
+        shape.push_grid(0, stacks, sectors, true, true);
+        shape.build()
+    }
+
+    /// Creates a sphere centered at the origin by subdividing an icosahedron `subdivisions` times.
+    ///
+    /// The uv coordinates are spherical. Vertices along the seam and at the poles are duplicated.
+    pub fn icosphere(radius: f32, subdivisions: usize) -> PMesh<T> {
+        // an icosahedron with a vertex at each pole and two rings of five vertices in between
+        let (y, r) = (1.0 / 5.0f32.sqrt(), 2.0 / 5.0f32.sqrt());
+        let mut positions = vec![Vec3::Y, Vec3::NEG_Y];
+        for k in 0..5 {
+            positions.push(ring_point(r, y, (k as f32 + 0.25) * 0.4 * PI));
+        }
+        for k in 0..5 {
+            positions.push(ring_point(r, -y, (k as f32 + 0.75) * 0.4 * PI));
+        }
+        let mut faces: Vec<[u32; 3]> = Vec::with_capacity(20);
+        for k in 0..5 {
+            let (up, up_next, low, low_next) = (2 + k, 2 + (k + 1) % 5, 7 + k, 7 + (k + 1) % 5);
+            faces.push([0, up, up_next]);
+            faces.push([up, low, up_next]);
+            faces.push([up_next, low, low_next]);
+            faces.push([1, low_next, low]);
+        }
+
+        for _ in 0..subdivisions {
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut midpoint = |a: u32, b: u32, positions: &mut Vec<Vec3>| -> u32 {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    let p = (positions[a as usize] + positions[b as usize]).normalize();
+                    positions.push(p);
+                    positions.len() as u32 - 1
+                })
+            };
+            let mut new_faces = Vec::with_capacity(faces.len() * 4);
+            for [a, b, c] in faces {
+                let ab = midpoint(a, b, &mut positions);
+                let bc = midpoint(b, c, &mut positions);
+                let ca = midpoint(c, a, &mut positions);
+                new_faces.push([a, ab, ca]);
+                new_faces.push([b, bc, ab]);
+                new_faces.push([c, ca, bc]);
+                new_faces.push([ab, bc, ca]);
+            }
+            faces = new_faces;
+        }
+
+        let mut uv: Vec<[f32; 2]> = positions
+            .iter()
+            .map(|p| {
+                [
+                    0.5 + p.x.atan2(p.z) / (2.0 * PI),
+                    p.y.clamp(-1.0, 1.0).acos() / PI,
+                ]
+            })
+            .collect();
+
+        // split triangles crossing the seam at u = 0 / 1 behind the sphere into a part with u close to 0
+        // and one with u close to 1. Vertices on the seam are duplicated with both u coordinates.
+        let side = |p: Vec3| {
+            if p.x.abs() < 1e-6 {
+                0
+            } else {
+                p.x.signum() as i32
+            }
+        };
+        let mut seam_vertices: HashMap<u32, [u32; 2]> = HashMap::new();
+        let mut seam_edges: HashMap<(u32, u32), [u32; 2]> = HashMap::new();
+        let seam_copies = |p: Vec3, v: f32, positions: &mut Vec<Vec3>, uv: &mut Vec<[f32; 2]>| {
+            positions.extend([p, p]);
+            uv.extend([[0.0, v], [1.0, v]]);
+            let n = positions.len() as u32;
+            [n - 2, n - 1]
+        };
+        let mut split_faces = Vec::with_capacity(faces.len());
+        for face in faces {
+            let us = face
+                .iter()
+                .filter(|i| positions[**i as usize].y.abs() < 1.0 - 1e-6)
+                .map(|i| uv[*i as usize][0]);
+            let max = us.clone().fold(f32::MIN, f32::max);
+            let min = us.fold(f32::MAX, f32::min);
+            if max - min <= 0.5 {
+                split_faces.push(face);
+                continue;
+            }
+            let (mut low, mut high) = (Vec::with_capacity(4), Vec::with_capacity(4));
+            for k in 0..3 {
+                let (a, b) = (face[k], face[(k + 1) % 3]);
+                let (pa, pb) = (positions[a as usize], positions[b as usize]);
+                match side(pa) {
+                    -1 => low.push(a),
+                    1 => high.push(a),
+                    _ if pa.y.abs() >= 1.0 - 1e-6 => {
+                        low.push(a);
+                        high.push(a);
+                    }
+                    _ => {
+                        let v = uv[a as usize][1];
+                        let [l, h] = *seam_vertices
+                            .entry(a)
+                            .or_insert_with(|| seam_copies(pa, v, &mut positions, &mut uv));
+                        low.push(l);
+                        high.push(h);
+                    }
+                }
+                if side(pa) * side(pb) < 0 {
+                    let [l, h] = *seam_edges.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                        let p = pa.lerp(pb, pa.x / (pa.x - pb.x)).normalize();
+                        let v = p.y.clamp(-1.0, 1.0).acos() / PI;
+                        seam_copies(p, v, &mut positions, &mut uv)
+                    });
+                    low.push(l);
+                    high.push(h);
+                }
+            }
+            for polygon in [low, high] {
+                for i in 1..polygon.len().saturating_sub(1) {
+                    split_faces.push([polygon[0], polygon[i], polygon[i + 1]]);
+                }
+            }
+        }
+        let mut faces = split_faces;
+
+        // give every triangle its own pole vertex with the average u of the other two
+        for face in &mut faces {
+            for k in 0..3 {
+                let i = face[k] as usize;
+                if positions[i].y.abs() < 1.0 - 1e-6 {
+                    continue;
+                }
+                let u =
+                    (uv[face[(k + 1) % 3] as usize][0] + uv[face[(k + 2) % 3] as usize][0]) * 0.5;
+                positions.push(positions[i]);
+                uv.push([u, uv[i][1]]);
+                face[k] = positions.len() as u32 - 1;
+            }
+        }
+
+        ShapeBuffers {
+            vertices: positions.iter().map(|p| (*p * radius).to_array()).collect(),
+            uv,
+            normals: positions.iter().map(|p| p.to_array()).collect(),
+            indices: faces.iter().flatten().cloned().collect(),
+        }
+        .build()
+    }
+
+    /// Creates a capped cylinder centered at the origin along the y-axis.
+    pub fn cylinder(radius: f32, height: f32, sectors: usize, stacks: usize) -> PMesh<T> {
+        let sectors = sectors.max(3);
+        let stacks = stacks.max(1);
+        let mut shape = ShapeBuffers::default();
+
+        for r in 0..=stacks {
+            let v = r as f32 / stacks as f32;
+            let y = height * (0.5 - v);
+            for s in 0..=sectors {
+                let u = s as f32 / sectors as f32;
+                let n = ring_point(1.0, 0.0, u * 2.0 * PI);
+                shape.push(n * radius + Vec3::Y * y, [u, v], n);
+            }
+        }
+
+        shape.push_grid(0, stacks, sectors, false, false);
+        shape.push_cap(radius, height * 0.5, sectors, true);
+        shape.push_cap(radius, -height * 0.5, sectors, false);
+        shape.build()
+    }
+
+    /// Creates a capped cone centered at the origin along the y-axis with the apex at the top.
+    pub fn cone(radius: f32, height: f32, sectors: usize, stacks: usize) -> PMesh<T> {
+        let sectors = sectors.max(3);
+        let stacks = stacks.max(1);
+        let mut shape = ShapeBuffers::default();
+
+        let slope = Vec2::new(height, radius).normalize();
+        for r in 0..=stacks {
+            let v = r as f32 / stacks as f32;
+            let y = height * (0.5 - v);
+            for s in 0..=sectors {
+                let u = s as f32 / sectors as f32;
+                let dir = ring_point(1.0, 0.0, u * 2.0 * PI);
+                shape.push(
+                    dir * radius * v + Vec3::Y * y,
+                    [u, v],
+                    Vec3::new(dir.x * slope.x, slope.y, dir.z * slope.x),
+                );
+            }
+        }
+
+        shape.push_grid(0, stacks, sectors, true, false);
+        shape.push_cap(radius, -height * 0.5, sectors, false);
+        shape.build()
+    }
+
+    /// Creates a torus centered at the origin lying in the xz-plane.
+    ///
+    /// `radius` is the distance from the center to the center of the tube.
+    pub fn torus(radius: f32, tube_radius: f32, sectors: usize, tube_sectors: usize) -> PMesh<T> {
+        let sectors = sectors.max(3);
+        let tube_sectors = tube_sectors.max(3);
+        let mut shape = ShapeBuffers::default();
+
+        for r in 0..=tube_sectors {
+            let v = r as f32 / tube_sectors as f32;
+            let phi = -v * 2.0 * PI;
+            for s in 0..=sectors {
+                let u = s as f32 / sectors as f32;
+                let dir = ring_point(1.0, 0.0, u * 2.0 * PI);
+                let n = dir * phi.cos() + Vec3::Y * phi.sin();
+                shape.push(dir * radius + n * tube_radius, [u, v], n);
+            }
+        }
+
+        shape.push_grid(0, tube_sectors, sectors, false, false);
+        shape.build()
+    }
+
+    /// Creates a capsule centered at the origin along the y-axis.
+    ///
+    /// `height` is the length of the cylindrical part and each hemisphere is made of `stacks` rings.
+    pub fn capsule(radius: f32, height: f32, sectors: usize, stacks: usize) -> PMesh<T> {
+        let sectors = sectors.max(3);
+        let stacks = stacks.max(1);
+        let mut shape = ShapeBuffers::default();
+
+        // the v coordinate follows the arc length of the profile
+        let total = PI * radius + height;
+        for r in 0..=(2 * stacks + 1) {
+            let (phi, y, arc) = if r <= stacks {
+                let phi = r as f32 / stacks as f32 * PI * 0.5;
+                (phi, height * 0.5, phi * radius)
+            } else {
+                let phi = (r - 1) as f32 / stacks as f32 * PI * 0.5;
+                (phi, -height * 0.5, phi * radius + height)
+            };
+            for s in 0..=sectors {
+                let u = s as f32 / sectors as f32;
+                let n = ring_point(phi.sin(), phi.cos(), u * 2.0 * PI);
+                shape.push(n * radius + Vec3::Y * y, [u, arc / total], n);
+            }
+        }
+
+        shape.push_grid(0, 2 * stacks + 1, sectors, true, true);
+        shape.build()
+    }
 }
 
 fn simplify_vertices(input_vertices: Vec<[f32; 3]>) -> Vec<[f32; 3]> {
@@ -163,3 +466,209 @@ fn generate_uv_for_fan(
     }
     return None;
 }
+
+/// Returns the point on a ring around the y-axis with the given radius, height and angle.
+/// An angle of zero points towards +z and the angle increases towards +x.
+#[inline]
+fn ring_point(radius: f32, y: f32, angle: f32) -> Vec3 {
+    Vec3::new(radius * angle.sin(), y, radius * angle.cos())
+}
+
+/// Collects the buffers of a primitive shape with uv coordinates and normals.
+#[derive(Default)]
+struct ShapeBuffers {
+    vertices: Vec<[f32; 3]>,
+    uv: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+    indices: Vec<u32>,
+}
+
+impl ShapeBuffers {
+    fn push(&mut self, position: Vec3, uv: [f32; 2], normal: Vec3) {
+        self.vertices.push(position.to_array());
+        self.uv.push(uv);
+        self.normals.push(normal.to_array());
+    }
+
+    /// Appends the indices of a grid of `rows + 1` rings with `cols + 1` vertices each.
+    ///
+    /// Rows are expected to go "down" while columns go "right" when looking at the front face.
+    /// When `skip_top` or `skip_bottom` is set, the degenerate triangles at collapsed first or last rings are omitted.
+    fn push_grid(
+        &mut self,
+        offset: u32,
+        rows: usize,
+        cols: usize,
+        skip_top: bool,
+        skip_bottom: bool,
+    ) {
+        let stride = cols as u32 + 1;
+        for r in 0..rows as u32 {
+            for s in 0..cols as u32 {
+                let a = offset + r * stride + s;
+                let b = a + stride;
+                let c = b + 1;
+                let d = a + 1;
+                if !(skip_bottom && r == rows as u32 - 1) {
+                    self.indices.extend([a, b, c]);
+                }
+                if !(skip_top && r == 0) {
+                    self.indices.extend([a, c, d]);
+                }
+            }
+        }
+    }
+
+    /// Appends a disk at the given height facing up or down.
+    fn push_cap(&mut self, radius: f32, y: f32, sectors: usize, up: bool) {
+        let center = self.vertices.len() as u32;
+        let normal = if up { Vec3::Y } else { Vec3::NEG_Y };
+        self.push(Vec3::Y * y, [0.5, 0.5], normal);
+        for s in 0..=sectors {
+            let dir = ring_point(1.0, 0.0, s as f32 / sectors as f32 * 2.0 * PI);
+            self.push(
+                dir * radius + Vec3::Y * y,
+                [0.5 + 0.5 * dir.x, 0.5 - 0.5 * dir.z],
+                normal,
+            );
+        }
+        for s in 1..=sectors as u32 {
+            if up {
+                self.indices.extend([center, center + s, center + s + 1]);
+            } else {
+                self.indices.extend([center, center + s + 1, center + s]);
+            }
+        }
+    }
+
+    fn build<T: IndexType>(self) -> PMesh<T> {
+        PMesh::build_ex(
+            self.vertices,
+            self.indices.iter().map(|i| T::new(*i as usize)).collect(),
+            Some(self.uv),
+            Some(self.normals),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::WeldSettings;
+
+    /// Asserts the number of vertices and triangles and that the normals are unit vectors that agree with the winding order.
+    fn check(mesh: &PMesh<u32>, vertices: usize, triangles: usize) {
+        assert_eq!(mesh.vertices.len(), vertices);
+        assert_eq!(mesh.indices.len(), triangles * 3);
+        assert!(mesh.validate().is_ok());
+        let normals = mesh.normals.as_ref().unwrap();
+        assert!(normals
+            .iter()
+            .all(|n| (Vec3::from(*n).length() - 1.0).abs() < 1e-5));
+        for [a, b, c] in mesh.iter_faces() {
+            let (pa, pb, pc) = (mesh.vec3_at(a), mesh.vec3_at(b), mesh.vec3_at(c));
+            let face = (pb - pa).cross(pc - pa);
+            let n = Vec3::from(normals[a]) + Vec3::from(normals[b]) + Vec3::from(normals[c]);
+            assert!(face.dot(n) > 0.0, "face {face} against normal {n}");
+        }
+    }
+
+    #[test]
+    fn cuboid() {
+        let mesh = PMesh::<u32>::cuboid(1.0, 2.0, 3.0, 3);
+        check(&mesh, 6 * 4 * 4, 6 * 3 * 3 * 2);
+        let bounds = mesh
+            .vertices
+            .get_vertices()
+            .iter()
+            .fold(Vec3::ZERO, |b, v| b.max(Vec3::from(*v).abs()));
+        assert_eq!(bounds, Vec3::new(0.5, 1.0, 1.5));
+    }
+
+    #[test]
+    fn uv_sphere() {
+        let mesh = PMesh::<u32>::uv_sphere(2.0, 16, 8);
+        check(&mesh, 9 * 17, 2 * 8 * 16 - 2 * 16);
+        let normals = mesh.normals.as_ref().unwrap();
+        for (i, n) in normals.iter().enumerate() {
+            assert!(Vec3::from(*n).dot(mesh.vec3_at(i)) > 0.0);
+            assert!((mesh.vec3_at(i).length() - 2.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn icosphere() {
+        for subdivisions in 0..4 {
+            let mut mesh = PMesh::<u32>::icosphere(2.0, subdivisions);
+            // triangles crossing the seam are split
+            let triangles = 20 * 4usize.pow(subdivisions as u32);
+            assert!(mesh.indices.len() / 3 >= triangles);
+            check(&mesh, mesh.vertices.len(), mesh.indices.len() / 3);
+
+            let normals = mesh.normals.as_ref().unwrap();
+            for (i, n) in normals.iter().enumerate() {
+                assert!(Vec3::from(*n).dot(mesh.vec3_at(i)) > 0.0);
+                assert!((mesh.vec3_at(i).length() - 2.0).abs() < 1e-5);
+            }
+            // the seam and pole vertices are duplicated, so u stays within [0, 1] and never wraps inside a triangle
+            let uv = mesh.uv.as_ref().unwrap();
+            assert!(uv
+                .iter()
+                .all(|[u, v]| (0.0..=1.0 + 1e-5).contains(u) && (0.0..=1.0).contains(v)));
+            for face in mesh.iter_faces() {
+                let u = face.map(|i| uv[i][0]);
+                let max = u.iter().cloned().fold(f32::MIN, f32::max);
+                let min = u.iter().cloned().fold(f32::MAX, f32::min);
+                assert!(max - min < 0.5);
+            }
+
+            // the welded mesh is a closed surface without cracks at the seam
+            mesh.weld(&WeldSettings::positions_only(1e-4));
+            let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+            for [a, b, c] in mesh.iter_faces() {
+                for (x, y) in [(a, b), (b, c), (c, a)] {
+                    *edges.entry((x.min(y), x.max(y))).or_default() += 1;
+                }
+            }
+            assert!(edges.values().all(|n| *n == 2));
+            let faces = mesh.indices.len() / 3;
+            assert_eq!(mesh.vertices.len() + faces, edges.len() + 2);
+        }
+    }
+
+    #[test]
+    fn cylinder() {
+        let mesh = PMesh::<u32>::cylinder(1.0, 2.0, 12, 3);
+        check(&mesh, 4 * 13 + 2 * 14, 2 * 3 * 12 + 2 * 12);
+    }
+
+    #[test]
+    fn cone() {
+        let mesh = PMesh::<u32>::cone(1.0, 2.0, 12, 3);
+        check(&mesh, 4 * 13 + 14, 2 * 3 * 12 - 12 + 12);
+    }
+
+    #[test]
+    fn torus() {
+        let mesh = PMesh::<u32>::torus(2.0, 0.5, 16, 8);
+        check(&mesh, 9 * 17, 2 * 8 * 16);
+        let normals = mesh.normals.as_ref().unwrap();
+        for (i, n) in normals.iter().enumerate() {
+            let p = mesh.vec3_at(i);
+            let tube_center = Vec3::new(p.x, 0.0, p.z).normalize() * 2.0;
+            assert!(Vec3::from(*n).dot(p - tube_center) > 0.0);
+            if p.xz().length() > 2.4 {
+                assert!(Vec3::from(*n).dot(p) > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn capsule() {
+        let mesh = PMesh::<u32>::capsule(1.0, 2.0, 12, 4);
+        check(&mesh, 10 * 13, 2 * 9 * 12 - 2 * 12);
+        let uv = mesh.uv.as_ref().unwrap();
+        assert_eq!(uv[0][1], 0.0);
+        assert!((uv[uv.len() - 1][1] - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,434 @@
+//! Sweeping a 2D profile along a 3D path.
+
+use super::{
+    profile::{profile_samples, triangulate_polygon},
+    IndexType, PMesh, PVertices,
+};
+use bevy::math::{Quat, Vec2, Vec3};
+
+/// A 3D path built from lines and bézier curves with a scale and twist at every sample.
+///
+/// Segments interpolate the scale and twist linearly from the previous point to the current values
+/// set by `set_scale` and `set_twist`.
+#[derive(Clone, Debug)]
+pub struct PPath3 {
+    points: Vec<Vec3>,
+    scale: Vec<f32>,
+    twist: Vec<f32>,
+    current_scale: f32,
+    current_twist: f32,
+    closed: bool,
+    tol: f32,
+}
+
+impl PPath3 {
+    /// Creates a new path starting at the given point. Curves are flattened with the given tolerance.
+    pub fn new(start: Vec3, tol: f32) -> Self {
+        PPath3 {
+            points: vec![start],
+            scale: vec![1.0],
+            twist: vec![0.0],
+            current_scale: 1.0,
+            current_twist: 0.0,
+            closed: false,
+            tol,
+        }
+    }
+
+    /// Creates a polyline from the given points. Curves added later are flattened with the given tolerance.
+    ///
+    /// Returns `None` if there are no points.
+    pub fn from_points(points: &[Vec3], tol: f32) -> Option<Self> {
+        let (first, rest) = points.split_first()?;
+        let mut path = PPath3::new(*first, tol);
+        for p in rest {
+            path.line_to(*p);
+        }
+        Some(path)
+    }
+
+    /// Sets the scale of the profile at the end of the next segments.
+    pub fn set_scale(&mut self, scale: f32) -> &mut Self {
+        self.current_scale = scale;
+        self
+    }
+
+    /// Sets the twist (rotation of the profile around the path in radians) at the end of the next segments.
+    pub fn set_twist(&mut self, twist: f32) -> &mut Self {
+        self.current_twist = twist;
+        self
+    }
+
+    fn push_samples(&mut self, samples: impl Iterator<Item = (f32, Vec3)>) {
+        let scale = *self.scale.last().unwrap();
+        let twist = *self.twist.last().unwrap();
+        for (t, p) in samples {
+            self.points.push(p);
+            self.scale.push(scale + (self.current_scale - scale) * t);
+            self.twist.push(twist + (self.current_twist - twist) * t);
+        }
+    }
+
+    /// Adds a line segment to the path.
+    pub fn line_to(&mut self, to: Vec3) -> &mut Self {
+        self.push_samples(std::iter::once((1.0, to)));
+        self
+    }
+
+    /// Adds a quadratic bézier curve to the path.
+    pub fn quadratic_bezier_to(&mut self, ctrl: Vec3, to: Vec3) -> &mut Self {
+        let from = *self.points.last().unwrap();
+        // Wang's formula for the number of segments
+        let n = ((from - 2.0 * ctrl + to).length() / (4.0 * self.tol))
+            .sqrt()
+            .ceil()
+            .max(1.0) as usize;
+        self.push_samples((1..=n).map(|i| {
+            let t = i as f32 / n as f32;
+            let s = 1.0 - t;
+            (t, from * s * s + ctrl * 2.0 * s * t + to * t * t)
+        }));
+        self
+    }
+
+    /// Adds a cubic bézier curve to the path.
+    pub fn cubic_bezier_to(&mut self, ctrl1: Vec3, ctrl2: Vec3, to: Vec3) -> &mut Self {
+        let from = *self.points.last().unwrap();
+        // Wang's formula for the number of segments
+        let m = (from - 2.0 * ctrl1 + ctrl2)
+            .length()
+            .max((ctrl1 - 2.0 * ctrl2 + to).length());
+        let n = (3.0 * m / (4.0 * self.tol)).sqrt().ceil().max(1.0) as usize;
+        self.push_samples((1..=n).map(|i| {
+            let t = i as f32 / n as f32;
+            let s = 1.0 - t;
+            (
+                t,
+                from * s * s * s
+                    + ctrl1 * 3.0 * s * s * t
+                    + ctrl2 * 3.0 * s * t * t
+                    + to * t * t * t,
+            )
+        }));
+        self
+    }
+
+    /// Closes the path. The last point is connected to the first one.
+    pub fn close(&mut self) -> &mut Self {
+        if self.points.len() > 1 && self.points[0].distance(*self.points.last().unwrap()) < self.tol
+        {
+            self.points.pop();
+            self.scale.pop();
+            self.twist.pop();
+        }
+        self.closed = true;
+        self
+    }
+
+    /// Returns the sampled points of the path.
+    pub fn points(&self) -> &Vec<Vec3> {
+        &self.points
+    }
+
+    /// Returns whether the path is closed.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Computes rotation-minimizing frames along the path using the double reflection method.
+    ///
+    /// Returns the "right" vector at each sample or nothing if there are no tangents. For closed paths,
+    /// the remaining rotation between the last and the first frame is distributed along the path.
+    fn frames(&self, tangents: &[Vec3]) -> Vec<Vec3> {
+        let n = self.points.len();
+        if n == 0 || tangents.len() != n {
+            return Vec::new();
+        }
+        let t0 = tangents[0];
+        let up = if t0.dot(Vec3::Y).abs() < 0.999 {
+            Vec3::Y
+        } else {
+            Vec3::Z
+        };
+        let mut rights = vec![t0.cross(up.reject_from_normalized(t0).normalize())];
+
+        let steps = if self.closed { n } else { n - 1 };
+        for i in 0..steps {
+            let (x0, x1) = (self.points[i], self.points[(i + 1) % n]);
+            let (t0, t1) = (tangents[i], tangents[(i + 1) % n]);
+            let r = rights[i];
+            let v1 = x1 - x0;
+            let c1 = v1.dot(v1);
+            if c1 <= f32::EPSILON {
+                rights.push(r);
+                continue;
+            }
+            let r_l = r - (2.0 / c1) * v1.dot(r) * v1;
+            let t_l = t0 - (2.0 / c1) * v1.dot(t0) * v1;
+            let v2 = t1 - t_l;
+            let c2 = v2.dot(v2);
+            rights.push(if c2 <= f32::EPSILON {
+                r_l
+            } else {
+                r_l - (2.0 / c2) * v2.dot(r_l) * v2
+            });
+        }
+
+        if self.closed {
+            let last = rights.pop().unwrap();
+            let angle = last.cross(rights[0]).dot(t0).atan2(last.dot(rights[0]));
+            let lengths = arc_lengths(&self.points, true);
+            let total = lengths[n];
+            for i in 0..n {
+                rights[i] =
+                    Quat::from_axis_angle(tangents[i], angle * lengths[i] / total) * rights[i];
+            }
+        }
+        rights
+    }
+}
+
+fn arc_lengths(points: &[Vec3], closed: bool) -> Vec<f32> {
+    let n = points.len();
+    let mut lengths = vec![0.0];
+    let steps = if closed { n } else { n.saturating_sub(1) };
+    for i in 0..steps {
+        let len = lengths[i] + points[i].distance(points[(i + 1) % n]);
+        lengths.push(len);
+    }
+    lengths
+}
+
+impl PVertices {
+    /// Sweeps the vertices as a profile along the given path and returns the resulting mesh.
+    ///
+    /// The x and y coordinates of the vertices are used as the profile. Looking along the path, the x-axis
+    /// of the profile points to the right and the y-axis points upwards. The frames along the path are
+    /// rotation-minimizing, so they don't flip at inflection points.
+    /// The side to the right of the drawing direction of the profile faces outwards, i.e., closed
+    /// counter-clockwise profiles produce outward-facing tubes. If `caps` is set, the ends of
+    /// open paths with closed profiles are filled.
+    /// The uv coordinates follow the arc length of the profile (u) and of the path (v), both normalized to [0, 1].
+    pub fn sweep<T>(&self, path: &PPath3, closed_profile: bool, caps: bool) -> PMesh<T>
+    where
+        T: IndexType,
+    {
+        let mut vertices: Vec<[f32; 3]> = Vec::new();
+        let mut uv: Vec<[f32; 2]> = Vec::new();
+        let mut normals: Vec<[f32; 3]> = Vec::new();
+        let mut indices: Vec<T> = Vec::new();
+
+        let profile: Vec<Vec2> = self
+            .get_vertices()
+            .iter()
+            .map(|v| Vec2::new(v[0], v[1]))
+            .collect();
+        let samples = profile_samples(&profile, closed_profile);
+        let n = path.points.len();
+        if samples.is_empty() || n < 2 {
+            return PMesh::new();
+        }
+        let profile_len = samples.last().unwrap().arc_len;
+
+        // tangents at the samples
+        let tangents: Vec<Vec3> = (0..n)
+            .map(|i| {
+                let prev = if path.closed || i > 0 {
+                    path.points[(i + n - 1) % n]
+                } else {
+                    path.points[i]
+                };
+                let next = if path.closed || i < n - 1 {
+                    path.points[(i + 1) % n]
+                } else {
+                    path.points[i]
+                };
+                (next - prev).normalize_or_zero()
+            })
+            .collect();
+        let rights = path.frames(&tangents);
+        let lengths = arc_lengths(&path.points, path.closed);
+        let path_len = *lengths.last().unwrap();
+
+        // closed paths repeat the first ring at the end
+        let rings = if path.closed { n + 1 } else { n };
+        let ring_frame = |k: usize| {
+            let i = k % n;
+            let right = rights[i];
+            let up = right.cross(tangents[i]);
+            let rot = Vec2::from_angle(path.twist[i]);
+            let map = move |p: Vec2| {
+                let p = rot.rotate(p);
+                right * p.x + up * p.y
+            };
+            (path.points[i], path.scale[i], map)
+        };
+        for (k, length) in lengths.iter().enumerate() {
+            let (origin, scale, map) = ring_frame(k);
+            let v = if path_len > 0.0 {
+                length / path_len
+            } else {
+                0.0
+            };
+            for sample in &samples {
+                vertices.push((origin + map(sample.position) * scale).to_array());
+                normals.push(map(sample.normal).normalize_or_zero().to_array());
+                uv.push([
+                    if profile_len > 0.0 {
+                        sample.arc_len / profile_len
+                    } else {
+                        0.0
+                    },
+                    v,
+                ]);
+            }
+        }
+
+        let stride = samples.len();
+        for k in 0..rings - 1 {
+            for i in 0..stride - 1 {
+                if samples[i].position.distance(samples[i + 1].position) <= f32::EPSILON {
+                    continue;
+                }
+                let a = k * stride + i;
+                let b = a + stride;
+                let c = b + 1;
+                let d = a + 1;
+                indices.extend([a, b, c, a, c, d].map(T::new));
+            }
+        }
+
+        if caps && closed_profile && !path.closed {
+            for (k, start) in [(0, true), (n - 1, false)] {
+                let (origin, scale, map) = ring_frame(k);
+                let normal = if start { -tangents[k] } else { tangents[k] };
+                let offset = vertices.len();
+                for p in &profile {
+                    vertices.push((origin + map(*p) * scale).to_array());
+                    normals.push(normal.to_array());
+                    uv.push([p.x, p.y]);
+                }
+                // counter-clockwise profile triangles face against the path direction
+                for [a, b, c] in triangulate_polygon(&profile) {
+                    let (b, c) = if start { (b, c) } else { (c, b) };
+                    indices.extend([a, b, c].map(|i| T::new(offset + i)));
+                }
+            }
+        }
+
+        PMesh::build_ex(vertices, indices, Some(uv), Some(normals))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A counter-clockwise circle in the xy-plane.
+    fn circle(radius: f32, segments: usize) -> PVertices {
+        PVertices::build(
+            (0..segments)
+                .map(|i| {
+                    let p = Vec2::from_angle(i as f32 / segments as f32 * std::f32::consts::TAU);
+                    [p.x * radius, p.y * radius, 0.0]
+                })
+                .collect(),
+        )
+    }
+
+    /// Asserts that the faces agree with the normals.
+    fn assert_outwards(mesh: &PMesh<u32>) {
+        let normals = mesh.get_normals().unwrap();
+        for [a, b, c] in mesh.iter_faces() {
+            let (pa, pb, pc) = (mesh.vec3_at(a), mesh.vec3_at(b), mesh.vec3_at(c));
+            let face = (pb - pa).cross(pc - pa);
+            let n = Vec3::from(normals[a]) + Vec3::from(normals[b]) + Vec3::from(normals[c]);
+            assert!(face.dot(n) > 0.0);
+        }
+    }
+
+    #[test]
+    fn frames_of_empty_paths() {
+        let mut path = PPath3::new(Vec3::ZERO, 0.01);
+        path.points.clear();
+        assert!(path.frames(&[]).is_empty());
+        assert_eq!(
+            circle(1.0, 8)
+                .sweep::<u32>(&path, true, true)
+                .vertices
+                .len(),
+            0
+        );
+    }
+
+    #[test]
+    fn closed_loops_have_no_seam() {
+        // a loop winding around a torus, so the rotation-minimizing frames don't return to the first frame on their own
+        let n = 64;
+        let points: Vec<Vec3> = (0..n)
+            .map(|i| {
+                let a = i as f32 / n as f32 * std::f32::consts::TAU;
+                let r = 3.0 + (3.0 * a).cos();
+                Vec3::new(r * a.cos(), (3.0 * a).sin(), r * a.sin())
+            })
+            .collect();
+        let mut path = PPath3::from_points(&points, 0.01).unwrap();
+        path.close();
+        let tangents: Vec<Vec3> = (0..n)
+            .map(|i| (points[(i + 1) % n] - points[(i + n - 1) % n]).normalize())
+            .collect();
+        let rights = path.frames(&tangents);
+        assert_eq!(rights.len(), n);
+
+        // the twist between consecutive frames is small everywhere, including from the last to the first frame
+        for i in 0..n {
+            let j = (i + 1) % n;
+            let transported = Quat::from_rotation_arc(tangents[i], tangents[j]) * rights[i];
+            assert!(rights[i].dot(tangents[i]).abs() < 1e-4);
+            assert!(transported.angle_between(rights[j]) < 0.05);
+        }
+
+        let mesh = circle(0.5, 16).sweep::<u32>(&path, true, true);
+        assert!(mesh.validate().is_ok());
+        assert_outwards(&mesh);
+        let stride = 17;
+        assert_eq!(mesh.vertices.len(), (n + 1) * stride);
+        for i in 0..stride {
+            assert_eq!(mesh.vec3_at(i), mesh.vec3_at(n * stride + i));
+        }
+    }
+
+    #[test]
+    fn square_loop() {
+        let mut path = PPath3::new(Vec3::ZERO, 0.01);
+        path.line_to(Vec3::new(4.0, 0.0, 0.0))
+            .line_to(Vec3::new(4.0, 0.0, 4.0))
+            .line_to(Vec3::new(0.0, 0.0, 4.0))
+            .close();
+        let mesh = circle(0.5, 12).sweep::<u32>(&path, true, true);
+        assert!(mesh.validate().is_ok());
+        assert_outwards(&mesh);
+        // the loop is planar, so the profile never twists out of the tube
+        for v in mesh.vertices.get_vertices() {
+            assert!(v[1].abs() <= 0.5 + 1e-5);
+        }
+    }
+
+    #[test]
+    fn caps_face_outwards() {
+        let path =
+            PPath3::from_points(&[Vec3::ZERO, Vec3::Z, Vec3::new(0.0, 1.0, 2.0)], 0.01).unwrap();
+        let mesh = circle(0.5, 12).sweep::<u32>(&path, true, true);
+        assert!(mesh.validate().is_ok());
+        assert_outwards(&mesh);
+
+        let normals = mesh.get_normals().unwrap();
+        let start = -Vec3::Z;
+        let end = Vec3::new(0.0, 1.0, 1.0).normalize();
+        let caps = mesh.vertices.len() - 2 * 12;
+        for (i, normal) in normals.iter().enumerate().skip(caps) {
+            let expected = if i < caps + 12 { start } else { end };
+            assert!(Vec3::from(*normal).distance(expected) < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Generating tangents for normal mapping.
+
+use super::{IndexType, PMesh, PMeshError};
+use bevy::{
+    asset::RenderAssetUsages, mesh::VertexAttributeValues, prelude::*,
+    render::render_resource::PrimitiveTopology,
+};
+
+impl<T> PMesh<T>
+where
+    T: IndexType,
+{
+    /// Computes `MikkTSpace` tangents and stores them as `Mesh::ATTRIBUTE_TANGENT`.
+    ///
+    /// Requires uv coordinates and normals. Returns an error without modifying the mesh
+    /// if they are missing or if the uv coordinates of a triangle have no area.
+    pub fn compute_tangents(&mut self) -> Result<&mut PMesh<T>, PMeshError> {
+        self.validate_structure()?;
+        let Some(uv) = &self.uv else {
+            return Err(PMeshError::MissingAttribute(Mesh::ATTRIBUTE_UV_0.name));
+        };
+        let Some(normals) = &self.normals else {
+            return Err(PMeshError::MissingAttribute(Mesh::ATTRIBUTE_NORMAL.name));
+        };
+
+        // MikkTSpace produces NaNs or panics for triangles without uv area
+        for (i, [a, b, c]) in self.iter_faces().enumerate() {
+            let (a, b, c) = (Vec2::from(uv[a]), Vec2::from(uv[b]), Vec2::from(uv[c]));
+            let scale = (b - a)
+                .length_squared()
+                .max((c - a).length_squared())
+                .max((c - b).length_squared());
+            let area = (b - a).perp_dot(c - a).abs();
+            if !area.is_finite() || area <= f32::EPSILON * scale {
+                return Err(PMeshError::DegenerateUv(i));
+            }
+        }
+
+        let mut mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        );
+        mesh.insert_indices(self.indices.get_bevy());
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.vertices.to_bevy());
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_NORMAL,
+            VertexAttributeValues::Float32x3(normals.clone()),
+        );
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_UV_0,
+            VertexAttributeValues::Float32x2(uv.clone()),
+        );
+        mesh.generate_tangents()
+            .map_err(|e| PMeshError::TangentGeneration(e.to_string()))?;
+
+        let Some(VertexAttributeValues::Float32x4(tangents)) =
+            mesh.remove_attribute(Mesh::ATTRIBUTE_TANGENT)
+        else {
+            return Err(PMeshError::TangentGeneration(
+                "no tangents were generated".to_string(),
+            ));
+        };
+        if let Some(i) = tangents
+            .iter()
+            .position(|t| !t.iter().all(|x| x.is_finite()))
+        {
+            return Err(PMeshError::TangentGeneration(format!(
+                "the tangent of vertex {} is not finite",
+                i
+            )));
+        }
+        self.attributes.insert(
+            Mesh::ATTRIBUTE_TANGENT,
+            VertexAttributeValues::Float32x4(tangents),
+        );
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that the tangents are unit vectors orthogonal to the normals with a handedness of ±1.
+    fn assert_orthonormal(mesh: &PMesh<u32>) {
+        let tangents = mesh.get_tangents().unwrap();
+        let normals = mesh.get_normals().unwrap();
+        assert_eq!(tangents.len(), mesh.vertices.len());
+        for (t, n) in tangents.iter().zip(normals) {
+            let tangent = Vec3::new(t[0], t[1], t[2]);
+            assert!((tangent.length() - 1.0).abs() < 1e-4);
+            assert!(tangent.dot(Vec3::from(*n)).abs() < 1e-4);
+            assert_eq!(t[3].abs(), 1.0);
+        }
+    }
+
+    #[test]
+    fn tangents_are_orthogonal_to_the_normals() {
+        let mut mesh = PMesh::<u32>::cuboid(1.0, 2.0, 3.0, 2);
+        mesh.compute_tangents().unwrap();
+        assert_orthonormal(&mesh);
+        // the u coordinate of the front face runs along the x-axis
+        let tangents = mesh.get_tangents().unwrap();
+        assert!(Vec3::new(tangents[0][0], tangents[0][1], tangents[0][2]).distance(Vec3::X) < 1e-4);
+
+        let mut mesh = PMesh::<u32>::torus(2.0, 0.5, 16, 8);
+        mesh.compute_tangents().unwrap();
+        assert_orthonormal(&mesh);
+    }
+
+    #[test]
+    fn degenerate_uvs_are_an_error() {
+        let mut mesh = PMesh::<u32>::cuboid(1.0, 1.0, 1.0, 1);
+        let uv = mesh.uv.as_mut().unwrap();
+        // the second triangle of the first face gets collinear uv coordinates
+        let [_, _, _, a, b, c] = [0, 1, 2, 3, 4, 5].map(|i| mesh.indices.get_indices()[i] as usize);
+        uv[a] = [0.0, 0.0];
+        uv[b] = [0.5, 0.5];
+        uv[c] = [1.0, 1.0];
+        assert_eq!(
+            mesh.compute_tangents().err(),
+            Some(PMeshError::DegenerateUv(1))
+        );
+        assert!(mesh.get_tangents().is_none());
+
+        let mut mesh = PMesh::<u32>::cuboid(1.0, 1.0, 1.0, 1);
+        mesh.uv = Some(vec![[0.5, 0.5]; 24]);
+        assert_eq!(
+            mesh.compute_tangents().err(),
+            Some(PMeshError::DegenerateUv(0))
+        );
+
+        let mut mesh = PMesh::<u32>::cuboid(1.0, 1.0, 1.0, 1);
+        mesh.uv = None;
+        assert_eq!(
+            mesh.compute_tangents().err(),
+            Some(PMeshError::MissingAttribute(Mesh::ATTRIBUTE_UV_0.name))
+        );
+    }
+}
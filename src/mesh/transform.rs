@@ -0,0 +1,218 @@
+//! Affine transformations of meshes.
+
+use super::{IndexType, PMesh};
+use bevy::{mesh::VertexAttributeValues, prelude::*};
+
+impl<T> PMesh<T>
+where
+    T: IndexType,
+{
+    /// Applies an affine transformation to the vertices, normals, and tangents of the mesh.
+    ///
+    /// Normals are transformed by the inverse-transpose and tangents by the linear part of the transformation.
+    /// If the transformation is a reflection (negative determinant), the winding order is flipped
+    /// so the faces keep pointing in the same direction relative to the surface.
+    pub fn transform(&mut self, affine: Affine3A) -> &mut PMesh<T> {
+        self.transform_attributes(affine);
+        if affine.matrix3.determinant() < 0.0 {
+            self.flip_winding();
+        }
+        self
+    }
+
+    /// Applies an affine transformation to the vertices, normals, and tangents without changing the winding order.
+    /// The normals and tangents describe the transformed surface, so after a reflection,
+    /// the winding order disagrees with them until `flip_winding` is called.
+    fn transform_attributes(&mut self, affine: Affine3A) {
+        let linear = affine.matrix3;
+        let sign = if linear.determinant() < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+
+        for v in self.vertices.get_vertices_mut() {
+            *v = affine.transform_point3(Vec3::from(*v)).to_array();
+        }
+
+        // the cofactor matrix is the inverse-transpose scaled by the determinant,
+        // so it also works for singular transformations like flattening scales
+        let cofactor = Mat3A::from_cols(
+            linear.y_axis.cross(linear.z_axis),
+            linear.z_axis.cross(linear.x_axis),
+            linear.x_axis.cross(linear.y_axis),
+        ) * sign;
+        if let Some(normals) = &mut self.normals {
+            for n in normals {
+                *n = cofactor
+                    .mul_vec3(Vec3::from(*n))
+                    .normalize_or_zero()
+                    .to_array();
+            }
+        }
+
+        if let Some(VertexAttributeValues::Float32x4(tangents)) =
+            self.attributes.get_mut(Mesh::ATTRIBUTE_TANGENT)
+        {
+            for t in tangents {
+                let v = linear
+                    .mul_vec3(Vec3::new(t[0], t[1], t[2]))
+                    .normalize_or_zero();
+                *t = [v.x, v.y, v.z, t[3] * sign];
+            }
+        }
+    }
+
+    /// Applies a transformation matrix to the mesh. The projective part of the matrix is ignored.
+    pub fn transform_mat4(&mut self, matrix: Mat4) -> &mut PMesh<T> {
+        self.transform(Affine3A::from_mat4(matrix))
+    }
+
+    /// Applies a bevy `Transform` to the mesh.
+    pub fn apply_transform(&mut self, transform: &Transform) -> &mut PMesh<T> {
+        self.transform(transform.compute_affine())
+    }
+
+    /// Rotates the mesh around the origin.
+    pub fn rotate(&mut self, rotation: Quat) -> &mut PMesh<T> {
+        self.transform(Affine3A::from_quat(rotation))
+    }
+
+    /// Rotates the mesh around the y-axis.
+    pub fn rotate_y(&mut self, angle: f32) -> &mut PMesh<T> {
+        // turns x towards z, i.e., the opposite direction of `Quat::from_rotation_y`
+        self.rotate(Quat::from_rotation_y(-angle))
+    }
+
+    /// Translates the mesh.
+    pub fn translate(&mut self, x: f32, y: f32, z: f32) -> &mut PMesh<T> {
+        self.vertices.translate(x, y, z);
+        self
+    }
+
+    /// Scales the mesh and its normals and tangents.
+    ///
+    /// Negative scales that mirror the mesh don't change the winding order, so the faces point in the opposite
+    /// direction unless you also call `flip_winding`. Use `transform` to flip the winding order automatically.
+    pub fn scale(&mut self, x: f32, y: f32, z: f32) -> &mut PMesh<T> {
+        self.transform_attributes(Affine3A::from_scale(Vec3::new(x, y, z)));
+        self
+    }
+
+    /// Scales the mesh uniformly.
+    pub fn scale_uniform(&mut self, x: f32) -> &mut PMesh<T> {
+        self.scale(x, x, x)
+    }
+
+    /// Mirrors the mesh across the plane through `point` with the given normal.
+    /// The winding order is flipped so the faces keep pointing outwards.
+    ///
+    /// Doesn't change the mesh if the normal is zero or not finite.
+    pub fn mirror(&mut self, point: Vec3, normal: Vec3) -> &mut PMesh<T> {
+        let Some(n) = normal.try_normalize() else {
+            return self;
+        };
+        let reflection = Mat3::IDENTITY - 2.0 * Mat3::from_cols(n * n.x, n * n.y, n * n.z);
+        self.transform(Affine3A::from_mat3_translation(
+            reflection,
+            2.0 * point.dot(n) * n,
+        ))
+    }
+
+    /// Flips the y and z coordinates of the vertices, normals, and tangents.
+    ///
+    /// This is useful to place a mesh "lying on the ground" in a 3D scene.
+    /// Since this is a reflection, the faces will point in the opposite direction unless you also call `flip_winding`.
+    pub fn flip_yz(&mut self) -> &mut PMesh<T> {
+        self.transform_attributes(Affine3A::from_cols(
+            Vec3A::X,
+            Vec3A::Z,
+            Vec3A::Y,
+            Vec3A::ZERO,
+        ));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tilted triangle whose normals are its face normal.
+    fn triangle() -> PMesh<u32> {
+        let mut mesh = PMesh::build(
+            vec![[0.0, 0.0, 0.0], [1.0, 0.5, 0.0], [0.0, 0.5, 1.0]],
+            vec![0, 1, 2],
+            None,
+        );
+        let normal = face_normal(&mesh);
+        mesh.normals = Some(vec![normal.to_array(); 3]);
+        mesh
+    }
+
+    /// The normal of the first face according to its winding order.
+    fn face_normal(mesh: &PMesh<u32>) -> Vec3 {
+        let [a, b, c] = mesh.iter_faces().next().unwrap();
+        let (a, b, c) = (mesh.vec3_at(a), mesh.vec3_at(b), mesh.vec3_at(c));
+        (b - a).cross(c - a).normalize()
+    }
+
+    fn normal(mesh: &PMesh<u32>) -> Vec3 {
+        Vec3::from(mesh.normals.as_ref().unwrap()[0])
+    }
+
+    #[test]
+    fn normals_use_the_inverse_transpose() {
+        let mut mesh = triangle();
+        mesh.transform(Affine3A::from_scale_rotation_translation(
+            Vec3::new(3.0, 0.5, 1.0),
+            Quat::from_rotation_x(0.3),
+            Vec3::ONE,
+        ));
+        assert!(normal(&mesh).distance(face_normal(&mesh)) < 1e-5);
+
+        let mut mesh = triangle();
+        mesh.scale(2.0, 1.0, 0.25);
+        assert!(normal(&mesh).distance(face_normal(&mesh)) < 1e-5);
+    }
+
+    #[test]
+    fn mirrored_faces_stay_front_facing() {
+        let mut mesh = triangle();
+        mesh.mirror(Vec3::new(0.5, 0.0, 0.0), Vec3::new(1.0, 2.0, -0.5));
+        assert!(normal(&mesh).distance(face_normal(&mesh)) < 1e-5);
+
+        let mut mesh = triangle();
+        mesh.flip_yz().flip_winding();
+        assert_eq!(mesh.vec3_at(1), Vec3::new(1.0, 0.0, 0.5));
+        assert!(normal(&mesh).distance(face_normal(&mesh)) < 1e-5);
+
+        let mut mesh = triangle();
+        mesh.transform(Affine3A::from_scale(Vec3::new(-1.0, 1.0, 1.0)));
+        assert!(normal(&mesh).distance(face_normal(&mesh)) < 1e-5);
+    }
+
+    #[test]
+    fn scale_and_flip_yz_keep_the_winding_order() {
+        let mut mesh = triangle();
+        mesh.scale(-1.0, 1.0, 1.0);
+        assert_eq!(mesh.indices.get_indices(), &vec![0, 1, 2]);
+        assert!(normal(&mesh).distance(-face_normal(&mesh)) < 1e-5);
+
+        let mut mesh = triangle();
+        mesh.flip_yz();
+        assert_eq!(mesh.indices.get_indices(), &vec![0, 1, 2]);
+        assert!(normal(&mesh).distance(-face_normal(&mesh)) < 1e-5);
+    }
+
+    #[test]
+    fn mirroring_at_a_zero_normal_does_nothing() {
+        let mut mesh = triangle();
+        mesh.mirror(Vec3::ONE, Vec3::ZERO);
+        assert_eq!(
+            mesh.vertices.get_vertices(),
+            triangle().vertices.get_vertices()
+        );
+        assert_eq!(mesh.normals, triangle().normals);
+    }
+}
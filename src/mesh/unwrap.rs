@@ -0,0 +1,521 @@
+//! Unwrapping meshes into non-overlapping uv atlases, e.g., for lightmaps.
+
+use super::{IndexType, OrPanic, PMesh, PMeshError};
+use bevy::prelude::*;
+use std::{
+    collections::{HashMap, VecDeque},
+    f32::consts::PI,
+};
+
+/// Settings for `PMesh::unwrap_uv1`.
+#[derive(Clone, Debug)]
+pub struct UnwrapSettings {
+    /// The maximum angle between the normal of a face and the normal of the first face of its chart.
+    /// Should be below 90° so every chart can be flattened without folding over.
+    pub max_cone_angle: f32,
+
+    /// The space between charts and around the border of the atlas in uv units.
+    pub padding: f32,
+
+    /// The maximum number of iterations of the solver that flattens each chart.
+    pub max_iterations: usize,
+
+    /// The maximum distance between vertices that are treated as connected.
+    pub epsilon: f32,
+}
+
+impl Default for UnwrapSettings {
+    fn default() -> Self {
+        UnwrapSettings {
+            max_cone_angle: PI / 3.0,
+            padding: 0.005,
+            max_iterations: 1000,
+            epsilon: 0.0001,
+        }
+    }
+}
+
+impl UnwrapSettings {
+    /// Creates settings with the given padding between charts in uv units.
+    pub fn new(padding: f32) -> Self {
+        UnwrapSettings {
+            padding,
+            ..Default::default()
+        }
+    }
+}
+
+/// A flattened chart of the mesh.
+struct Chart {
+    /// The uv coordinates of every position group in the chart.
+    uv: HashMap<usize, Vec2>,
+    min: Vec2,
+    size: Vec2,
+    /// Whether the chart is rotated by 90° in the atlas.
+    rotated: bool,
+    offset: Vec2,
+}
+
+/// Minimizes the least squares conformal energy of a chart with two pinned vertices.
+///
+/// `faces` are given as local vertex indices, `initial` are the starting uv coordinates.
+/// Returns `None` if the solution contains NaNs or flipped triangles.
+fn lscm(
+    positions: &[Vec3],
+    faces: &[[usize; 3]],
+    initial: &[Vec2],
+    pinned: [usize; 2],
+    max_iterations: usize,
+) -> Option<Vec<Vec2>> {
+    let n = positions.len();
+
+    // every row is the real or imaginary part of the Cauchy-Riemann equations of a triangle
+    // with coefficients for the variables u_i = 2 * i and v_i = 2 * i + 1
+    let mut rows: Vec<[(usize, f32); 6]> = Vec::with_capacity(faces.len() * 2);
+    for face in faces {
+        let [p0, p1, p2] = face.map(|i| positions[i]);
+        let normal = (p1 - p0).cross(p2 - p0);
+        let double_area = normal.length();
+        if double_area <= f32::EPSILON * (p1 - p0).length_squared().max((p2 - p0).length_squared())
+        {
+            continue;
+        }
+        let x = (p1 - p0).normalize();
+        let y = (normal / double_area).cross(x);
+        let q = [p0, p1, p2].map(|p| Vec2::new((p - p0).dot(x), (p - p0).dot(y)));
+        let weight = (double_area * 0.5).sqrt() / double_area;
+        let mut real = [(0, 0.0); 6];
+        let mut imaginary = [(0, 0.0); 6];
+        for k in 0..3 {
+            // the gradient of the barycentric coordinate of corner k is the rotated opposite edge
+            let e = q[(k + 2) % 3] - q[(k + 1) % 3];
+            let g = Vec2::new(-e.y, e.x) * weight;
+            let (u, v) = (face[k] * 2, face[k] * 2 + 1);
+            real[2 * k] = (u, g.x);
+            real[2 * k + 1] = (v, -g.y);
+            imaginary[2 * k] = (u, g.y);
+            imaginary[2 * k + 1] = (v, g.x);
+        }
+        rows.push(real);
+        rows.push(imaginary);
+    }
+
+    let mut free = vec![true; 2 * n];
+    for p in pinned {
+        free[2 * p] = false;
+        free[2 * p + 1] = false;
+    }
+    // computes A^T A x restricted to the free variables
+    let apply = |x: &[f32]| {
+        let mut result = vec![0.0; 2 * n];
+        for row in &rows {
+            let r: f32 = row.iter().map(|(i, c)| c * x[*i]).sum();
+            for (i, c) in row {
+                result[*i] += c * r;
+            }
+        }
+        for (r, f) in result.iter_mut().zip(&free) {
+            if !f {
+                *r = 0.0;
+            }
+        }
+        result
+    };
+    let dot = |a: &[f32], b: &[f32]| a.iter().zip(b).map(|(a, b)| a * b).sum::<f32>();
+
+    // jacobi preconditioner
+    let mut diagonal = vec![0.0; 2 * n];
+    for row in &rows {
+        for (i, c) in row {
+            diagonal[*i] += c * c;
+        }
+    }
+    let precondition = |r: &[f32]| -> Vec<f32> {
+        r.iter()
+            .zip(&diagonal)
+            .map(|(r, d)| if *d > 0.0 { r / d } else { 0.0 })
+            .collect()
+    };
+
+    // preconditioned conjugate gradients on the normal equations
+    let mut x: Vec<f32> = initial.iter().flat_map(|p| [p.x, p.y]).collect();
+    let mut r: Vec<f32> = apply(&x).iter().map(|v| -v).collect();
+    let mut z = precondition(&r);
+    let mut p = z.clone();
+    let mut rz = dot(&r, &z);
+    let tolerance = rz.abs() * 1e-10;
+    for _ in 0..max_iterations {
+        if rz.abs() <= tolerance || rz == 0.0 {
+            break;
+        }
+        let ap = apply(&p);
+        let pap = dot(&p, &ap);
+        if pap <= 0.0 {
+            break;
+        }
+        let alpha = rz / pap;
+        for i in 0..2 * n {
+            x[i] += alpha * p[i];
+            r[i] -= alpha * ap[i];
+        }
+        z = precondition(&r);
+        let rz_next = dot(&r, &z);
+        let beta = rz_next / rz;
+        rz = rz_next;
+        for i in 0..2 * n {
+            p[i] = z[i] + beta * p[i];
+        }
+    }
+
+    let uv: Vec<Vec2> = x.chunks(2).map(|c| Vec2::new(c[0], c[1])).collect();
+    let valid = uv.iter().all(|p| p.is_finite())
+        && faces.iter().all(|[a, b, c]| {
+            let (pa, pb, pc) = (positions[*a], positions[*b], positions[*c]);
+            let area = (pb - pa).cross(pc - pa).length();
+            area <= f32::EPSILON * (pb - pa).length_squared().max((pc - pa).length_squared())
+                || (uv[*b] - uv[*a]).perp_dot(uv[*c] - uv[*a]) > 0.0
+        });
+    valid.then_some(uv)
+}
+
+/// How often the atlas is enlarged by 5% before giving up. The charts always fit after enlarging it by a
+/// factor of about 10⁶ unless the padding leaves no space for them.
+const MAX_PACKING_STEPS: usize = 300;
+
+/// Places the rectangles with the given sizes in rows of the given width.
+/// Returns the offsets of the rectangles and the total height.
+fn pack_shelves(sizes: &[Vec2], order: &[usize], width: f32, padding: f32) -> (Vec<Vec2>, f32) {
+    let mut offsets = vec![Vec2::ZERO; sizes.len()];
+    let mut cursor = Vec2::splat(padding);
+    let mut shelf_height = 0.0f32;
+    for i in order {
+        let size = sizes[*i];
+        if cursor.x > padding && cursor.x + size.x + padding > width {
+            cursor = Vec2::new(padding, cursor.y + shelf_height + padding);
+            shelf_height = 0.0;
+        }
+        offsets[*i] = cursor;
+        cursor.x += size.x + padding;
+        shelf_height = shelf_height.max(size.y);
+    }
+    (offsets, cursor.y + shelf_height + padding)
+}
+
+impl<T> PMesh<T>
+where
+    T: IndexType,
+{
+    /// Unwraps the mesh into non-overlapping charts packed into `[0, 1]²` and stores the result
+    /// as `Mesh::ATTRIBUTE_UV_1`, e.g., to bake lightmaps. See `UnwrapSettings`.
+    ///
+    /// Faces are grouped into charts of connected faces whose normals lie within a cone.
+    /// Every chart is flattened with least squares conformal maps (LSCM), or by projecting it
+    /// onto its plane if that fails, and the charts are packed into rows with the same scale.
+    /// Vertices on the border between charts are split.
+    ///
+    /// Panics if the mesh can't be unwrapped. See `try_unwrap_uv1`.
+    pub fn unwrap_uv1(&mut self, settings: &UnwrapSettings) -> &mut PMesh<T> {
+        self.try_unwrap_uv1(settings).or_panic();
+        self
+    }
+
+    /// Unwraps the mesh into a uv atlas like `unwrap_uv1`. Returns `PMeshError::IndexCount`,
+    /// `PMeshError::IndexOutOfRange`, or `PMeshError::AttributeLength` if the indices or attributes are
+    /// invalid, `PMeshError::InvalidPosition` if a position isn't finite, and `PMeshError::UvUnwrap`
+    /// if the padding is too large to fit the charts into the atlas.
+    /// These errors are found before the mesh is changed.
+    pub fn try_unwrap_uv1(
+        &mut self,
+        settings: &UnwrapSettings,
+    ) -> Result<&mut PMesh<T>, PMeshError> {
+        self.validate_structure()?;
+        if let Some(i) = (0..self.vertices.len()).find(|i| !self.vec3_at(*i).is_finite()) {
+            return Err(PMeshError::InvalidPosition(i));
+        }
+        let groups = self.position_groups(settings.epsilon);
+        let faces: Vec<[usize; 3]> = self.iter_faces().collect();
+        let face_normals: Vec<Vec3> = faces
+            .iter()
+            .map(|[a, b, c]| {
+                let (pa, pb, pc) = (self.vec3_at(*a), self.vec3_at(*b), self.vec3_at(*c));
+                (pb - pa).cross(pc - pa).normalize_or_zero()
+            })
+            .collect();
+
+        // faces sharing an edge between two positions are neighbors
+        let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (f, face) in faces.iter().enumerate() {
+            for k in 0..3 {
+                let (g1, g2) = (groups[face[k]], groups[face[(k + 1) % 3]]);
+                if g1 != g2 {
+                    edges.entry((g1.min(g2), g1.max(g2))).or_default().push(f);
+                }
+            }
+        }
+
+        // grow charts from seed faces while the normals stay within the cone around the seed normal
+        let cos_cone = settings.max_cone_angle.min(PI).cos();
+        let mut chart_of: Vec<Option<usize>> = vec![None; faces.len()];
+        let mut chart_faces: Vec<Vec<usize>> = Vec::new();
+        for seed in 0..faces.len() {
+            if chart_of[seed].is_some() {
+                continue;
+            }
+            let chart = chart_faces.len();
+            let mut axis = face_normals[seed];
+            chart_of[seed] = Some(chart);
+            let mut members = vec![seed];
+            let mut queue = VecDeque::from([seed]);
+            while let Some(f) = queue.pop_front() {
+                for k in 0..3 {
+                    let (g1, g2) = (groups[faces[f][k]], groups[faces[f][(k + 1) % 3]]);
+                    let Some(neighbors) = edges.get(&(g1.min(g2), g1.max(g2))) else {
+                        continue;
+                    };
+                    for g in neighbors {
+                        let n = face_normals[*g];
+                        if chart_of[*g].is_none()
+                            && (axis == Vec3::ZERO || n == Vec3::ZERO || n.dot(axis) >= cos_cone)
+                        {
+                            if axis == Vec3::ZERO {
+                                axis = n;
+                            }
+                            chart_of[*g] = Some(chart);
+                            members.push(*g);
+                            queue.push_back(*g);
+                        }
+                    }
+                }
+            }
+            chart_faces.push(members);
+        }
+
+        // flatten every chart
+        let mut charts: Vec<Chart> = Vec::with_capacity(chart_faces.len());
+        for members in &chart_faces {
+            let mut local: HashMap<usize, usize> = HashMap::new();
+            let mut positions: Vec<Vec3> = Vec::new();
+            let mut normal = Vec3::ZERO;
+            let local_faces: Vec<[usize; 3]> = members
+                .iter()
+                .map(|f| {
+                    let [a, b, c] = faces[*f];
+                    normal += (self.vec3_at(b) - self.vec3_at(a))
+                        .cross(self.vec3_at(c) - self.vec3_at(a));
+                    faces[*f].map(|v| {
+                        *local.entry(groups[v]).or_insert_with(|| {
+                            positions.push(self.vec3_at(v));
+                            positions.len() - 1
+                        })
+                    })
+                })
+                .collect();
+
+            // project onto the plane of the chart as a starting point and fallback
+            let normal = normal.try_normalize().unwrap_or(Vec3::Y);
+            let tangent = normal.any_orthonormal_vector();
+            let bitangent = normal.cross(tangent);
+            let projected: Vec<Vec2> = positions
+                .iter()
+                .map(|p| Vec2::new(p.dot(tangent), p.dot(bitangent)))
+                .collect();
+
+            // pin the two vertices furthest apart along the longer axis of the projection
+            let (min, max) = projected.iter().fold(
+                (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+                |(a, b), p| (a.min(*p), b.max(*p)),
+            );
+            let axis = if max.x - min.x >= max.y - min.y {
+                Vec2::X
+            } else {
+                Vec2::Y
+            };
+            let extreme = |sign: f32| {
+                (0..projected.len())
+                    .max_by(|a, b| {
+                        (projected[*a].dot(axis) * sign)
+                            .total_cmp(&(projected[*b].dot(axis) * sign))
+                    })
+                    .unwrap_or(0)
+            };
+            let pinned = [extreme(-1.0), extreme(1.0)];
+
+            let uv = if pinned[0] != pinned[1] {
+                lscm(
+                    &positions,
+                    &local_faces,
+                    &projected,
+                    pinned,
+                    settings.max_iterations,
+                )
+            } else {
+                None
+            }
+            .unwrap_or(projected);
+
+            let (min, max) = uv.iter().fold(
+                (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+                |(a, b), p| (a.min(*p), b.max(*p)),
+            );
+            let (min, size) = if uv.is_empty() {
+                (Vec2::ZERO, Vec2::ZERO)
+            } else {
+                (min, max - min)
+            };
+            charts.push(Chart {
+                uv: local.iter().map(|(g, i)| (*g, uv[*i])).collect(),
+                min,
+                size,
+                rotated: size.y > size.x,
+                offset: Vec2::ZERO,
+            });
+        }
+
+        // pack the charts into rows, growing the atlas until everything fits into a square
+        let sizes: Vec<Vec2> = charts
+            .iter()
+            .map(|c| if c.rotated { c.size.yx() } else { c.size })
+            .collect();
+        let mut order: Vec<usize> = (0..charts.len()).collect();
+        order.sort_by(|a, b| sizes[*b].y.total_cmp(&sizes[*a].y));
+        let padding = settings.padding.clamp(0.0, 0.25);
+        let area: f32 = sizes.iter().map(|s| s.x * s.y).sum();
+        let widest = sizes.iter().map(|s| s.x).fold(0.0, f32::max);
+        let mut side = area
+            .sqrt()
+            .max(widest / (1.0 - 2.0 * padding))
+            .max(f32::MIN_POSITIVE);
+        if !side.is_finite() {
+            return Err(PMeshError::UvUnwrap("the charts are too large"));
+        }
+        let mut packed = None;
+        for _ in 0..MAX_PACKING_STEPS {
+            let (offsets, height) = pack_shelves(&sizes, &order, side, padding * side);
+            if height <= side {
+                packed = Some(offsets);
+                break;
+            }
+            side *= 1.05;
+        }
+        let offsets = packed.ok_or(PMeshError::UvUnwrap(
+            "the padding leaves no space for the charts",
+        ))?;
+        for (chart, offset) in charts.iter_mut().zip(offsets) {
+            chart.offset = offset;
+        }
+
+        // split the vertices at the borders of the charts and write the packed coordinates
+        let corners: Vec<usize> = chart_of.iter().flat_map(|c| [c.unwrap(); 3]).collect();
+        let keys = self.split_corners(corners);
+        let uv1 = keys
+            .iter()
+            .map(|(v, chart)| {
+                let Some(chart) = chart.map(|c| &charts[c]) else {
+                    return Ok([0.0; 2]);
+                };
+                let p = *chart
+                    .uv
+                    .get(&groups[*v])
+                    .ok_or(PMeshError::UvUnwrap("a vertex isn't part of its chart"))?
+                    - chart.min;
+                let p = if chart.rotated {
+                    Vec2::new(chart.size.y - p.y, p.x)
+                } else {
+                    p
+                };
+                Ok(((chart.offset + p) / side).to_array())
+            })
+            .collect::<Result<Vec<_>, PMeshError>>()?;
+        self.try_set_attribute(Mesh::ATTRIBUTE_UV_1, uv1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+        let t = ((p - a).dot(b - a) / (b - a).length_squared()).clamp(0.0, 1.0);
+        p.distance(a + (b - a) * t)
+    }
+
+    /// The distance between two triangles that don't intersect.
+    fn distance(a: [Vec2; 3], b: [Vec2; 3]) -> f32 {
+        let mut d = f32::INFINITY;
+        for (x, y) in [(a, b), (b, a)] {
+            for p in x {
+                for k in 0..3 {
+                    d = d.min(distance_to_segment(p, y[k], y[(k + 1) % 3]));
+                }
+            }
+        }
+        d
+    }
+
+    fn inside(p: Vec2, [a, b, c]: [Vec2; 3]) -> bool {
+        let s = [(a, b), (b, c), (c, a)].map(|(x, y)| (y - x).perp_dot(p - x));
+        s.iter().all(|s| *s > 1e-6) || s.iter().all(|s| *s < -1e-6)
+    }
+
+    #[test]
+    fn cuboid_charts_are_padded_and_dont_overlap() {
+        let padding = 0.02;
+        let mut mesh = PMesh::<u32>::cuboid(1.0, 2.0, 3.0, 1);
+        mesh.unwrap_uv1(&UnwrapSettings::new(padding));
+        assert!(mesh.validate().is_ok());
+        let uv1 = mesh.get_uv1().unwrap();
+        assert_eq!(uv1.len(), mesh.vertices.len());
+        for uv in uv1 {
+            assert!(uv
+                .iter()
+                .all(|x| (padding - 1e-5..=1.0 - padding + 1e-5).contains(x)));
+        }
+
+        // every side of the cuboid is a chart of two triangles
+        let triangles: Vec<(Vec3, [Vec2; 3])> = mesh
+            .iter_faces()
+            .map(|[a, b, c]| {
+                let normal = (mesh.vec3_at(b) - mesh.vec3_at(a))
+                    .cross(mesh.vec3_at(c) - mesh.vec3_at(a))
+                    .normalize();
+                (normal, [a, b, c].map(|v| Vec2::from(uv1[v])))
+            })
+            .collect();
+        for (i, (n1, t1)) in triangles.iter().enumerate() {
+            assert!((t1[1] - t1[0]).perp_dot(t1[2] - t1[0]) > 0.0);
+            for (n2, t2) in &triangles[i + 1..] {
+                if n1.dot(*n2) > 0.99 {
+                    continue;
+                }
+                assert!(distance(*t1, *t2) >= padding - 1e-5);
+                assert!(!t1.iter().any(|p| inside(*p, *t2)));
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_meshes_are_an_error() {
+        let mut mesh = PMesh::<u32>::cuboid(1.0, 1.0, 1.0, 1);
+        mesh.vertices.get_vertices_mut()[3] = [f32::NAN, 0.0, 0.0];
+        assert_eq!(
+            mesh.try_unwrap_uv1(&UnwrapSettings::default()).err(),
+            Some(PMeshError::InvalidPosition(3))
+        );
+        assert!(mesh.get_uv1().is_none());
+    }
+
+    #[test]
+    fn too_much_padding_is_an_error() {
+        let mut mesh = PMesh::<u32>::uv_sphere(1.0, 16, 8);
+        let vertices = mesh.vertices.len();
+        let settings = UnwrapSettings {
+            max_cone_angle: 0.01,
+            ..UnwrapSettings::new(0.25)
+        };
+        assert!(matches!(
+            mesh.try_unwrap_uv1(&settings),
+            Err(PMeshError::UvUnwrap(_))
+        ));
+        assert_eq!(mesh.vertices.len(), vertices);
+    }
+}
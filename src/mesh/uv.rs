@@ -0,0 +1,226 @@
+//! Generating uv coordinates by projection.
+
+use super::{IndexType, PIndices, PMesh};
+use bevy::prelude::*;
+use std::{collections::HashMap, f32::consts::PI, hash::Hash};
+
+/// How `PMesh::project_uv` maps positions to uv coordinates.
+///
+/// The projections are defined in projection space, i.e., after applying the transform passed to `project_uv`.
+/// One unit in projection space corresponds to one unit in uv space unless noted otherwise.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UvProjection {
+    /// Projects along the z-axis, i.e., `u` follows x and `v` follows -y.
+    #[default]
+    Planar,
+
+    /// Projects every face along the axis its normal is closest to, like a planar projection on each side of a box.
+    /// Vertices shared by faces with different axes are split.
+    Box,
+
+    /// Wraps around the y-axis. `u` goes from 0 to 1 around the axis starting at +z and turning towards +x,
+    /// `v` follows -y.
+    Cylindrical,
+
+    /// Wraps around the origin. `u` is the same as for `Cylindrical`,
+    /// `v` goes from 0 at the +y pole to 1 at the -y pole. The origin gets `v = 0.5`.
+    Spherical,
+}
+
+impl<T> PMesh<T>
+where
+    T: IndexType,
+{
+    /// Replaces the uv coordinates with a projection of the positions.
+    /// `transform` maps the mesh into projection space, e.g., to scale, rotate or move the projection.
+    ///
+    /// Vertices are split where faces need different uv coordinates, e.g., at the wrap seam of
+    /// cylindrical and spherical projections or at the edges of a box projection.
+    /// Vertices on the axis of a wrapping projection get the mean `u` of the rest of their triangle.
+    pub fn project_uv(&mut self, mode: UvProjection, transform: Affine3A) -> &mut PMesh<T> {
+        let positions: Vec<Vec3> = (0..self.vertices.len())
+            .map(|i| transform.transform_point3(self.vec3_at(i)))
+            .collect();
+        let around_y = |p: Vec3| (p.x.atan2(p.z) / (2.0 * PI)).rem_euclid(1.0);
+        let on_axis = |p: Vec3| p.x * p.x + p.z * p.z <= f32::EPSILON * p.length_squared();
+
+        let mut corners = Vec::with_capacity(self.indices.len());
+        for face in self.iter_faces() {
+            let p = face.map(|i| positions[i]);
+            match mode {
+                UvProjection::Planar => {
+                    corners.extend(p.map(|p| (0, [p.x, -p.y])));
+                }
+                UvProjection::Box => {
+                    let n = (p[1] - p[0]).cross(p[2] - p[0]);
+                    let a = n.abs();
+                    let (chart, uv): (usize, fn(Vec3) -> [f32; 2]) = if a.x >= a.y && a.x >= a.z {
+                        if n.x >= 0.0 {
+                            (0, |p| [-p.z, -p.y])
+                        } else {
+                            (1, |p| [p.z, -p.y])
+                        }
+                    } else if a.y >= a.z {
+                        if n.y >= 0.0 {
+                            (2, |p| [p.x, p.z])
+                        } else {
+                            (3, |p| [p.x, -p.z])
+                        }
+                    } else if n.z >= 0.0 {
+                        (4, |p| [p.x, -p.y])
+                    } else {
+                        (5, |p| [-p.x, -p.y])
+                    };
+                    corners.extend(p.map(|p| (chart, uv(p))));
+                }
+                UvProjection::Cylindrical | UvProjection::Spherical => {
+                    let v = |p: Vec3| match mode {
+                        UvProjection::Cylindrical => -p.y,
+                        // the latitude is 0 at the origin instead of NaN
+                        _ => 0.5 - p.y.atan2(p.xz().length()) / PI,
+                    };
+                    let mut u = p.map(|p| (!on_axis(p)).then(|| around_y(p)));
+
+                    // move the part of a triangle that crossed the seam to the other side
+                    let (min, max) = u
+                        .iter()
+                        .flatten()
+                        .fold((1.0f32, 0.0f32), |(a, b), u| (a.min(*u), b.max(*u)));
+                    if max - min > 0.5 {
+                        for u in u.iter_mut().flatten() {
+                            if *u < 0.5 {
+                                *u += 1.0;
+                            }
+                        }
+                    }
+
+                    let known: Vec<f32> = u.iter().flatten().copied().collect();
+                    let mean = if known.is_empty() {
+                        0.0
+                    } else {
+                        known.iter().sum::<f32>() / known.len() as f32
+                    };
+                    for k in 0..3 {
+                        corners.push((0, [u[k].unwrap_or(mean), v(p[k])]));
+                    }
+                }
+            }
+        }
+        self.set_corner_uv(corners)
+    }
+
+    /// Sets the chart and uv coordinates of every corner, i.e., every entry in the index buffer.
+    /// Vertices whose corners have different charts or uv coordinates are split.
+    pub(crate) fn set_corner_uv(&mut self, corners: Vec<(usize, [f32; 2])>) -> &mut PMesh<T> {
+        let keys = self.split_corners(
+            corners
+                .iter()
+                .map(|(chart, uv)| (*chart, uv.map(f32::to_bits)))
+                .collect(),
+        );
+        self.uv = Some(
+            keys.iter()
+                .map(|(_, key)| key.map_or([0.0; 2], |(_, uv)| uv.map(f32::from_bits)))
+                .collect(),
+        );
+        self
+    }
+
+    /// Splits vertices whose corners, i.e., entries in the index buffer, have different keys.
+    ///
+    /// Returns the original index and the key of every vertex afterwards.
+    /// The key is `None` for vertices that are not referenced by any triangle.
+    pub(crate) fn split_corners<K: Copy + Eq + Hash>(
+        &mut self,
+        keys: Vec<K>,
+    ) -> Vec<(usize, Option<K>)> {
+        // every vertex keeps its index for its first key; other keys get new vertices
+        let n = self.vertices.len();
+        let mut result: Vec<(usize, Option<K>)> = (0..n).map(|i| (i, None)).collect();
+        let mut splits: HashMap<(usize, K), usize> = HashMap::new();
+        let mut indices = Vec::with_capacity(keys.len());
+        for (v, key) in self.indices.iter_usize().zip(keys) {
+            let index = match result[v].1 {
+                None => {
+                    result[v].1 = Some(key);
+                    v
+                }
+                Some(k) if k == key => v,
+                Some(_) => *splits.entry((v, key)).or_insert_with(|| {
+                    result.push((v, Some(key)));
+                    result.len() - 1
+                }),
+            };
+            indices.push(T::new(index));
+        }
+
+        if result.len() > n {
+            let kept: Vec<usize> = result.iter().map(|(i, _)| *i).collect();
+            self.gather_vertices(&kept);
+        }
+        self.indices = PIndices::build(indices);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::WeldSettings;
+
+    /// Asserts that no triangle wraps around the seam, i.e., u never jumps by about 1 inside a triangle.
+    fn assert_no_wrap(mesh: &PMesh<u32>) {
+        let uv = mesh.uv.as_ref().unwrap();
+        for face in mesh.iter_faces() {
+            let u = face.map(|i| uv[i][0]);
+            let max = u.iter().cloned().fold(f32::MIN, f32::max);
+            let min = u.iter().cloned().fold(f32::MAX, f32::min);
+            assert!(max - min < 0.5, "u jumps from {min} to {max}");
+        }
+        assert!(uv.iter().flatten().all(|x| x.is_finite()));
+    }
+
+    #[test]
+    fn cylindrical_seams_are_split() {
+        let mut mesh = PMesh::<u32>::cylinder(1.0, 2.0, 16, 2);
+        mesh.weld(&WeldSettings::positions_only(0.0001));
+        let welded = mesh.vertices.len();
+        mesh.project_uv(UvProjection::Cylindrical, Affine3A::IDENTITY);
+        assert!(mesh.validate().is_ok());
+        assert_no_wrap(&mesh);
+        // the vertices at the seam are split
+        assert!(mesh.vertices.len() > welded + 3);
+        let uv = mesh.uv.as_ref().unwrap();
+        assert!(uv.iter().any(|uv| uv[0] > 0.9) && uv.iter().any(|uv| uv[0] < 0.1));
+    }
+
+    #[test]
+    fn spherical_seams_are_split() {
+        let mut mesh = PMesh::<u32>::icosphere(1.0, 2);
+        mesh.weld(&WeldSettings::positions_only(0.0001));
+        let welded = mesh.vertices.len();
+        mesh.project_uv(UvProjection::Spherical, Affine3A::IDENTITY);
+        assert!(mesh.validate().is_ok());
+        assert!(mesh.vertices.len() > welded);
+        assert_no_wrap(&mesh);
+        let uv = mesh.uv.as_ref().unwrap();
+        for (i, uv) in uv.iter().enumerate() {
+            let p = mesh.vec3_at(i);
+            assert!((uv[1] - p.y.clamp(-1.0, 1.0).acos() / PI).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn spherical_projection_at_the_origin() {
+        let mut mesh = PMesh::<u32>::build(
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            vec![0, 1, 2],
+            None,
+        );
+        mesh.project_uv(UvProjection::Spherical, Affine3A::IDENTITY);
+        let uv = mesh.uv.as_ref().unwrap();
+        assert!(uv.iter().flatten().all(|x| x.is_finite()));
+        assert_eq!(uv[0][1], 0.5);
+        assert_eq!(uv[2], [0.25, 0.0]);
+    }
+}
@@ -0,0 +1,180 @@
+//! Validation of meshes.
+
+use super::{IndexType, PMesh, PMeshError};
+use bevy::prelude::*;
+
+impl<T> PMesh<T>
+where
+    T: IndexType,
+{
+    /// Checks the number and range of the indices and the number of values of every attribute.
+    pub(crate) fn validate_structure(&self) -> Result<(), PMeshError> {
+        let n = self.vertices.len();
+        if !self.indices.len().is_multiple_of(3) {
+            return Err(PMeshError::IndexCount(self.indices.len()));
+        }
+        if let Some(index) = self.indices.iter_usize().find(|i| *i >= n) {
+            return Err(PMeshError::IndexOutOfRange {
+                index,
+                vertex_count: n,
+            });
+        }
+        let lengths = [
+            (
+                Mesh::ATTRIBUTE_UV_0.name,
+                self.uv.as_ref().map(|uv| uv.len()),
+            ),
+            (
+                Mesh::ATTRIBUTE_NORMAL.name,
+                self.normals.as_ref().map(|normals| normals.len()),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, len)| len.map(|len| (name, len)))
+        .chain(self.attributes.iter().map(|(a, v)| (a.name, v.len())));
+        for (name, len) in lengths {
+            if len != n {
+                return Err(PMeshError::AttributeLength {
+                    name,
+                    len,
+                    expected: n,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the mesh for errors that would make it unusable or crash when converting it to a bevy mesh.
+    ///
+    /// Returns the first error found: indices that are out of range or not a multiple of 3,
+    /// attributes whose length doesn't match the number of vertices, positions that are NaN or infinite,
+    /// and triangles without area.
+    pub fn validate(&self) -> Result<(), PMeshError> {
+        self.validate_structure()?;
+        if let Some(i) = (0..self.vertices.len()).find(|i| !self.vec3_at(*i).is_finite()) {
+            return Err(PMeshError::InvalidPosition(i));
+        }
+        for (i, [a, b, c]) in self.iter_faces().enumerate() {
+            let (a, b, c) = (self.vec3_at(a), self.vec3_at(b), self.vec3_at(c));
+            let scale = (b - a)
+                .length_squared()
+                .max((c - a).length_squared())
+                .max((c - b).length_squared());
+            if (b - a).cross(c - a).length() <= f32::EPSILON * scale {
+                return Err(PMeshError::DegenerateTriangle(i));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::{asset::RenderAssetUsages, render::render_resource::PrimitiveTopology};
+
+    fn triangle() -> PMesh<u32> {
+        PMesh::build(
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            vec![0, 1, 2],
+            Some(vec![[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]),
+        )
+    }
+
+    #[test]
+    fn structural_errors() {
+        let vertices = vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        assert_eq!(
+            PMesh::<u32>::try_build(vertices.clone(), vec![0, 1], None).err(),
+            Some(PMeshError::IndexCount(2))
+        );
+        assert_eq!(
+            PMesh::<u32>::try_build(vertices.clone(), vec![0, 1, 3], None).err(),
+            Some(PMeshError::IndexOutOfRange {
+                index: 3,
+                vertex_count: 3
+            })
+        );
+        assert_eq!(
+            PMesh::<u32>::try_build(vertices.clone(), vec![0, 1, 2], Some(vec![[0.0; 2]; 2])).err(),
+            Some(PMeshError::AttributeLength {
+                name: Mesh::ATTRIBUTE_UV_0.name,
+                len: 2,
+                expected: 3
+            })
+        );
+        assert_eq!(
+            PMesh::<u32>::try_build_ex(vertices.clone(), vec![0, 1, 2], None, Some(Vec::new()))
+                .err(),
+            Some(PMeshError::AttributeLength {
+                name: Mesh::ATTRIBUTE_NORMAL.name,
+                len: 0,
+                expected: 3
+            })
+        );
+        assert_eq!(
+            PMesh::<u16>::try_build(vertices, vec![0, 1, 70_000], None).err(),
+            Some(PMeshError::IndexTypeOverflow(70_000))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "the index 3 is out of range for 3 vertices")]
+    fn build_panics_on_invalid_indices() {
+        PMesh::<u32>::build(
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            vec![0, 1, 3],
+            None,
+        );
+    }
+
+    #[test]
+    fn validate_finds_broken_geometry() {
+        assert_eq!(triangle().validate(), Ok(()));
+
+        let mut mesh = triangle();
+        mesh.vertices.get_vertices_mut()[1][2] = f32::NAN;
+        assert_eq!(mesh.validate(), Err(PMeshError::InvalidPosition(1)));
+
+        let mut mesh = triangle();
+        mesh.vertices.get_vertices_mut()[2] = [2.0, 0.0, 0.0];
+        assert_eq!(mesh.validate(), Err(PMeshError::DegenerateTriangle(0)));
+
+        let mut mesh = triangle();
+        mesh.normals = Some(vec![[0.0, 0.0, 1.0]]);
+        assert_eq!(
+            mesh.validate(),
+            Err(PMeshError::AttributeLength {
+                name: Mesh::ATTRIBUTE_NORMAL.name,
+                len: 1,
+                expected: 3
+            })
+        );
+    }
+
+    #[test]
+    fn bevy_set_checks_the_mesh_first() {
+        let mut bevy_mesh = Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::default());
+        assert_eq!(
+            triangle().try_bevy_set(&mut bevy_mesh),
+            Err(PMeshError::UnsupportedTopology)
+        );
+
+        let mut bevy_mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        );
+        triangle().bevy_set(&mut bevy_mesh);
+        let mut mesh = triangle();
+        mesh.uv = Some(Vec::new());
+        assert!(matches!(
+            mesh.try_bevy_set(&mut bevy_mesh),
+            Err(PMeshError::AttributeLength { len: 0, .. })
+        ));
+        // the bevy mesh is unchanged
+        assert_eq!(
+            bevy_mesh.attribute(Mesh::ATTRIBUTE_UV_0).map(|uv| uv.len()),
+            Some(3)
+        );
+    }
+}
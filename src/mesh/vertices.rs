@@ -110,6 +110,8 @@ impl PVertices {
     }
 
     /// Extrudes the vertices in the given direction.
+    ///
+    /// To extrude along a curve, use `sweep` instead.
     pub fn extrude<T>(&mut self, direction: Vec3) -> PMesh<T>
     where
         T: IndexType,
@@ -0,0 +1,305 @@
+//! Welding vertices that share the same position.
+
+use super::{IndexType, PIndices, PMesh};
+use bevy::{mesh::MeshVertexAttributeId, prelude::*};
+use std::collections::{BTreeMap, HashMap};
+
+/// Settings for merging vertices with `PMesh::weld`.
+///
+/// Vertices are only merged if their positions and all of their attributes are within the given tolerances,
+/// so seams in uv maps or hard edges in the normals are preserved. Use `f32::INFINITY` to ignore an attribute.
+#[derive(Clone, Debug)]
+pub struct WeldSettings {
+    /// The maximum distance between the positions of merged vertices.
+    pub epsilon: f32,
+
+    /// The maximum difference of the uv coordinates of merged vertices.
+    pub uv_tolerance: f32,
+
+    /// The maximum difference of the components of the normals of merged vertices.
+    pub normal_tolerance: f32,
+
+    /// The maximum difference of the components of all other attributes of merged vertices.
+    pub attribute_tolerance: f32,
+
+    /// Tolerances for specific attributes that override `attribute_tolerance`.
+    pub attribute_tolerances: BTreeMap<MeshVertexAttributeId, f32>,
+}
+
+impl Default for WeldSettings {
+    fn default() -> Self {
+        WeldSettings {
+            epsilon: 0.0001,
+            uv_tolerance: 0.0001,
+            normal_tolerance: 0.001,
+            attribute_tolerance: 0.0001,
+            attribute_tolerances: BTreeMap::new(),
+        }
+    }
+}
+
+impl WeldSettings {
+    /// Ignores all attributes and merges every pair of vertices closer than `epsilon`.
+    pub fn positions_only(epsilon: f32) -> Self {
+        WeldSettings {
+            epsilon,
+            uv_tolerance: f32::INFINITY,
+            normal_tolerance: f32::INFINITY,
+            attribute_tolerance: f32::INFINITY,
+            attribute_tolerances: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the tolerance for a specific attribute.
+    pub fn with_tolerance(mut self, id: impl Into<MeshVertexAttributeId>, tolerance: f32) -> Self {
+        self.attribute_tolerances.insert(id.into(), tolerance);
+        self
+    }
+}
+
+/// The smallest cell of the spatial hash grid. Smaller epsilons would push most coordinates out of the
+/// range of the cell indices.
+const MIN_CELL_SIZE: f32 = 1e-5;
+
+/// Returns the cell of the spatial hash grid that contains `p`. The cells are at least `epsilon` wide, so
+/// vertices closer than `epsilon` are always in neighbouring cells.
+fn grid_cell(p: Vec3, epsilon: f32) -> IVec3 {
+    (p / epsilon.max(MIN_CELL_SIZE)).floor().as_ivec3()
+}
+
+impl<T> PMesh<T>
+where
+    T: IndexType,
+{
+    /// Merges vertices with the same position and matching attributes (see `WeldSettings`) and removes
+    /// triangles that collapsed. Vertices that are not referenced by any triangle are removed.
+    ///
+    /// Uses a spatial hash grid, so it runs in near-linear time.
+    pub fn weld(&mut self, settings: &WeldSettings) -> &mut PMesh<T> {
+        let eps = settings.epsilon.max(f32::MIN_POSITIVE);
+        let attribute_tolerance = |id: MeshVertexAttributeId| {
+            *settings
+                .attribute_tolerances
+                .get(&id)
+                .unwrap_or(&settings.attribute_tolerance)
+        };
+        let matches = |i: usize, j: usize| {
+            let close =
+                |a: &[f32], b: &[f32], tol: f32| a.iter().zip(b).all(|(x, y)| (x - y).abs() <= tol);
+            self.vec3_at(i).distance(self.vec3_at(j)) < eps
+                && self
+                    .uv
+                    .as_ref()
+                    .is_none_or(|uv| close(&uv[i], &uv[j], settings.uv_tolerance))
+                && self
+                    .normals
+                    .as_ref()
+                    .is_none_or(|n| close(&n[i], &n[j], settings.normal_tolerance))
+                && self.attributes.close(i, j, attribute_tolerance)
+        };
+
+        // maps grid cells to the new vertices in them
+        let mut grid: HashMap<IVec3, Vec<usize>> = HashMap::new();
+        let mut remap: Vec<Option<usize>> = vec![None; self.vertices.len()];
+        let mut kept: Vec<usize> = Vec::new();
+        for i in self.indices.iter_usize() {
+            if remap[i].is_some() {
+                continue;
+            }
+            let c = grid_cell(self.vec3_at(i), eps);
+            let mut found = None;
+            'search: for x in -1..=1 {
+                for y in -1..=1 {
+                    for z in -1..=1 {
+                        let Some(candidates) = grid.get(&c.wrapping_add(IVec3::new(x, y, z)))
+                        else {
+                            continue;
+                        };
+                        if let Some(k) = candidates.iter().find(|k| matches(kept[**k], i)) {
+                            found = Some(*k);
+                            break 'search;
+                        }
+                    }
+                }
+            }
+            remap[i] = Some(found.unwrap_or_else(|| {
+                kept.push(i);
+                grid.entry(c).or_default().push(kept.len() - 1);
+                kept.len() - 1
+            }));
+        }
+
+        let mut indices = Vec::with_capacity(self.indices.len());
+        for [a, b, c] in self.iter_faces() {
+            let [a, b, c] = [a, b, c].map(|i| remap[i].unwrap());
+            if a != b && b != c && c != a {
+                indices.extend([a, b, c].map(T::new));
+            }
+        }
+
+        self.gather_vertices(&kept);
+        self.indices = PIndices::build(indices);
+        self
+    }
+
+    /// Assigns every vertex the index of a group of vertices at the same position (within `epsilon`).
+    pub(crate) fn position_groups(&self, epsilon: f32) -> Vec<usize> {
+        let eps = epsilon.max(f32::MIN_POSITIVE);
+        let mut grid: HashMap<IVec3, Vec<usize>> = HashMap::new();
+        let mut representatives: Vec<usize> = Vec::new();
+        let mut groups = Vec::with_capacity(self.vertices.len());
+        for i in 0..self.vertices.len() {
+            let p = self.vec3_at(i);
+            let c = grid_cell(p, eps);
+            let mut found = None;
+            'search: for x in -1..=1 {
+                for y in -1..=1 {
+                    for z in -1..=1 {
+                        let Some(candidates) = grid.get(&c.wrapping_add(IVec3::new(x, y, z)))
+                        else {
+                            continue;
+                        };
+                        if let Some(g) = candidates
+                            .iter()
+                            .find(|g| self.vec3_at(representatives[**g]).distance(p) < eps)
+                        {
+                            found = Some(*g);
+                            break 'search;
+                        }
+                    }
+                }
+            }
+            groups.push(found.unwrap_or_else(|| {
+                representatives.push(i);
+                grid.entry(c).or_default().push(representatives.len() - 1);
+                representatives.len() - 1
+            }));
+        }
+        groups
+    }
+
+    /// Tries to introduce more shared indices and remove empty triangles.
+    /// Only merges vertices whose uvs, normals, and attributes match, so seams are preserved.
+    ///
+    /// Same as `weld` with the default `WeldSettings`, so vertices that are not referenced by any triangle are removed.
+    pub fn optimize(&mut self) -> &mut PMesh<T> {
+        self.weld(&WeldSettings::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A unit quad made of two triangles that don't share any vertices. The second triangle is offset by `d`.
+    fn quad(d: f32) -> PMesh<u32> {
+        let o = [d, -d, 0.0];
+        let vertices = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]]
+            .into_iter()
+            .chain(
+                [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]]
+                    .map(|v: [f32; 3]| [v[0] + o[0], v[1] + o[1], v[2]]),
+            )
+            .map(|v| [v[0] - 0.5, v[1] - 0.5, v[2]])
+            .collect();
+        PMesh::build(vertices, vec![0, 1, 2, 3, 4, 5], None)
+    }
+
+    fn epsilon(epsilon: f32) -> WeldSettings {
+        WeldSettings {
+            epsilon,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn merges_only_within_epsilon() {
+        assert_eq!(quad(0.001).weld(&epsilon(0.1)).vertices.len(), 4);
+        assert_eq!(quad(0.049).weld(&epsilon(0.1)).vertices.len(), 4);
+        assert_eq!(quad(0.08).weld(&epsilon(0.1)).vertices.len(), 6);
+        assert_eq!(quad(0.001).weld(&epsilon(0.001)).vertices.len(), 6);
+        assert_eq!(quad(0.0).weld(&epsilon(0.0)).vertices.len(), 4);
+    }
+
+    #[test]
+    fn merges_across_cell_borders() {
+        let mut mesh = PMesh::<u32>::build(
+            vec![
+                [-0.0001, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [0.0001, 0.0, 0.0],
+                [0.0, -1.0, 0.0],
+                [1.0, 0.0, 0.0],
+            ],
+            vec![0, 1, 2, 3, 4, 5],
+            None,
+        );
+        mesh.weld(&epsilon(0.01));
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.indices.get_indices(), &[0, 1, 2, 0, 3, 1]);
+    }
+
+    #[test]
+    fn keeps_seams() {
+        let mut mesh = quad(0.0);
+        mesh.uv = Some(vec![
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [1.0, 1.0],
+            [0.5, 0.0],
+            [1.0, 1.0],
+            [0.0, 1.0],
+        ]);
+        assert_eq!(mesh.clone().weld(&default()).vertices.len(), 5);
+        let settings = WeldSettings {
+            uv_tolerance: f32::INFINITY,
+            ..default()
+        };
+        assert_eq!(mesh.clone().weld(&settings).vertices.len(), 4);
+
+        let mut mesh = quad(0.0);
+        let mut normals = vec![[0.0, 0.0, 1.0]; 6];
+        normals[3] = [0.0, 0.0, -1.0];
+        mesh.normals = Some(normals);
+        assert_eq!(mesh.clone().weld(&default()).vertices.len(), 5);
+        assert_eq!(
+            mesh.weld(&WeldSettings::positions_only(0.0001))
+                .vertices
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn attributes_stay_aligned() {
+        let mut mesh = PMesh::<u32>::cuboid(1.0, 1.0, 1.0, 2);
+        mesh.duplicate();
+        // an unreferenced vertex
+        mesh.vertices.get_vertices_mut().push([5.0, 5.0, 5.0]);
+        let uv = mesh
+            .vertices
+            .get_vertices()
+            .iter()
+            .map(|v| [v[0], v[1]])
+            .collect();
+        mesh.uv = Some(uv);
+        let normals = mesh
+            .vertices
+            .get_vertices()
+            .iter()
+            .map(|v| Vec3::from(*v).normalize_or_zero().to_array())
+            .collect();
+        mesh.normals = Some(normals);
+        mesh.weld(&default());
+
+        assert!(mesh.validate().is_ok());
+        let (uv, normals) = (mesh.uv.as_ref().unwrap(), mesh.normals.as_ref().unwrap());
+        for (i, v) in mesh.vertices.get_vertices().iter().enumerate() {
+            assert_eq!(uv[i], [v[0], v[1]]);
+            assert_eq!(normals[i], Vec3::from(*v).normalize_or_zero().to_array());
+        }
+        // 3x3 grid points on each side of the cube, shared at the edges
+        assert_eq!(mesh.vertices.len(), 26);
+    }
+}